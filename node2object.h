@@ -0,0 +1,54 @@
+#ifndef NODE2OBJECT_H
+#define NODE2OBJECT_H
+
+/* Generated by cbindgen, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * `xml` or `out` is a null pointer.
+ */
+#define NODE2OBJECT_ERR_NULL_POINTER -1
+
+/**
+ * The input is not well-formed XML.
+ */
+#define NODE2OBJECT_ERR_PARSE -2
+
+/**
+ * The input has no root element.
+ */
+#define NODE2OBJECT_ERR_NO_ROOT -3
+
+/**
+ * `out` can't hold the JSON output and its terminating NUL.
+ */
+#define NODE2OBJECT_ERR_BUFFER_TOO_SMALL -4
+
+/**
+ * The JSON output is longer than `INT_MAX` bytes.
+ */
+#define NODE2OBJECT_ERR_TOO_LARGE -5
+
+/**
+ * The conversion panicked.
+ */
+#define NODE2OBJECT_ERR_PANIC -6
+
+/**
+ * Converts the XML document of `xml_len` bytes at `xml` into JSON, see `node2object`. The
+ * JSON is written to `out` followed by a NUL terminator, and its length without the
+ * terminator is returned. On failure nothing is written and one of the negative
+ * `NODE2OBJECT_ERR_*` codes is returned.
+ *
+ * # Safety
+ *
+ * `xml` must point to `xml_len` readable bytes and `out` to `out_cap` writable bytes.
+ */
+int node2object_c(const char *xml, size_t xml_len, char *out, size_t out_cap);
+
+#endif  /* NODE2OBJECT_H */
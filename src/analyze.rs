@@ -0,0 +1,208 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    attribute_key, element_key, is_forced_array, node_attributes, overflows_f64, push_xml_step,
+    scan_xml_node, ConversionConfig, TextSource, XMLNodeType, XmlPath,
+};
+
+/// Kind of a `Finding`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FindingCategory {
+    /// Text next to child elements. It is stored apart from the children, so its position
+    /// among them is lost.
+    MixedContent,
+    /// An element occurs once under some parents and repeatedly under others, so its key
+    /// holds a single value in some objects and an array in others. Elements matched by
+    /// `force_array` or `ensure_array_keys` are always arrays and never reported.
+    ArrayShape,
+    /// Text that looks like a number but is out of range of `f64`, so it stays a string.
+    NumberOverflow,
+    /// Attributes or elements with different names that end up under the same key.
+    KeyCollision,
+}
+
+/// Something that would be lost or ambiguous when converting a document, see `analyze`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// Location of the node the finding is about.
+    pub path: XmlPath,
+    pub category: FindingCategory,
+    /// Human-readable description.
+    pub message: String,
+}
+
+/// Where an element of a given name path was seen holding a single value, and whether it was
+/// also seen repeated.
+#[derive(Default)]
+struct Shape {
+    single: Option<XmlPath>,
+    repeated: bool,
+}
+
+/// Findings and shapes collected by `analyze`.
+struct Analyzer<'a> {
+    cfg: &'a ConversionConfig,
+    findings: Vec<Finding>,
+    /// Shapes by the slash-separated names from the root down.
+    shapes: HashMap<String, Shape>,
+    /// Name paths in the order they were first seen, to report shapes in document order.
+    order: Vec<String>,
+}
+
+/// Reports what converting `e` with `cfg` would lose or make ambiguous: mixed content,
+/// elements stored as an array in some places and as a single value in others, numbers out of
+/// range of `f64` and key collisions. No JSON is built, so this is cheaper than converting a
+/// large document when only the report is needed. Findings are in document order, followed by the
+/// `FindingCategory::ArrayShape` ones; a clean document gives an empty vec.
+pub fn analyze(e: &treexml::Element, cfg: &ConversionConfig) -> Vec<Finding> {
+    let mut analyzer = Analyzer {
+        cfg,
+        findings: Vec::new(),
+        shapes: HashMap::new(),
+        order: Vec::new(),
+    };
+    let mut path = XmlPath::default();
+    push_xml_step(&mut path, None, e);
+    analyzer.visit(&mut vec![e], &path);
+
+    let Analyzer {
+        mut findings,
+        mut shapes,
+        order,
+        ..
+    } = analyzer;
+    for names in order {
+        if let Some(Shape {
+            single: Some(path),
+            repeated: true,
+        }) = shapes.remove(&names)
+        {
+            findings.push(Finding {
+                path,
+                category: FindingCategory::ArrayShape,
+                message: format!(
+                    "`{}` occurs once here but repeats elsewhere, so it is converted to a single value here and to an array elsewhere",
+                    names
+                ),
+            });
+        }
+    }
+    findings
+}
+
+impl<'a> Analyzer<'a> {
+    /// Visits the last element of `ancestors`, located at `path`.
+    fn visit(&mut self, ancestors: &mut Vec<&treexml::Element>, path: &XmlPath) {
+        let e = *ancestors.last().unwrap();
+        let kind = scan_xml_node(e, self.cfg);
+        if kind == XMLNodeType::SemiStructured {
+            self.report(
+                path.clone(),
+                FindingCategory::MixedContent,
+                "text next to child elements loses its position among them".into(),
+            );
+        }
+        if matches!(kind, XMLNodeType::Text | XMLNodeType::TextAndAttributes) {
+            for text in e.text.iter().chain(&e.cdata) {
+                self.check_number(text, TextSource::Text, path);
+            }
+        }
+
+        let mut keys = HashMap::new();
+        for (name, value) in node_attributes(e, None, self.cfg) {
+            let mut attribute_path = path.clone();
+            attribute_path.push_attribute(name);
+            self.check_number(value, TextSource::Attribute, &attribute_path);
+            if self.cfg.attributes_key.is_none() {
+                self.check_key(&mut keys, attribute_key(name, self.cfg), attribute_path);
+            }
+        }
+
+        let mut counts = HashMap::<_, usize>::new();
+        for c in &e.children {
+            *counts.entry(element_key(c, self.cfg)).or_default() += 1;
+        }
+        let mut seen = HashSet::new();
+        for c in &e.children {
+            let mut child_path = path.clone();
+            push_xml_step(&mut child_path, Some(e), c);
+            let key = element_key(c, self.cfg);
+            if seen.insert(qualified_name(c)) {
+                self.check_key(&mut keys, key.to_string(), child_path.clone());
+            }
+            if !is_forced_array(ancestors, c, self.cfg) {
+                self.record_shape(ancestors, c, counts[&key] > 1, &child_path);
+            }
+
+            ancestors.push(c);
+            self.visit(ancestors, &child_path);
+            ancestors.pop();
+        }
+    }
+
+    /// Reports `key` if another attribute or element of the current element already uses it.
+    fn check_key(&mut self, keys: &mut HashMap<String, XmlPath>, key: String, path: XmlPath) {
+        match keys.get(&key) {
+            Some(first) => {
+                let message = format!("`{}` and `{}` are both stored under `{}`", first, path, key);
+                self.report(path, FindingCategory::KeyCollision, message);
+            }
+            None => {
+                keys.insert(key, path);
+            }
+        }
+    }
+
+    /// Reports `text` if it would be coerced to a number but is out of range of `f64`.
+    fn check_number(&mut self, text: &str, source: TextSource, path: &XmlPath) {
+        if overflows_f64(text, source, self.cfg) {
+            self.report(
+                path.clone(),
+                FindingCategory::NumberOverflow,
+                format!("`{}` is out of range of f64 and is kept as a string", text),
+            );
+        }
+    }
+
+    /// Records whether the child `c` of the last element of `ancestors` is repeated there.
+    fn record_shape(
+        &mut self,
+        ancestors: &[&treexml::Element],
+        c: &treexml::Element,
+        repeated: bool,
+        path: &XmlPath,
+    ) {
+        let names = ancestors
+            .iter()
+            .copied()
+            .chain(std::iter::once(c))
+            .map(qualified_name)
+            .collect::<Vec<_>>()
+            .join("/");
+        if !self.shapes.contains_key(&names) {
+            self.order.push(names.clone());
+        }
+        let shape = self.shapes.entry(names).or_default();
+        if repeated {
+            shape.repeated = true;
+        } else if shape.single.is_none() {
+            shape.single = Some(path.clone());
+        }
+    }
+
+    fn report(&mut self, path: XmlPath, category: FindingCategory, message: String) {
+        self.findings.push(Finding {
+            path,
+            category,
+            message,
+        });
+    }
+}
+
+/// Name of `e` with its namespace prefix, if any.
+fn qualified_name(e: &treexml::Element) -> String {
+    match &e.prefix {
+        Some(prefix) => format!("{}:{}", prefix, e.name),
+        None => e.name.clone(),
+    }
+}
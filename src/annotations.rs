@@ -0,0 +1,107 @@
+use serde_json::{Map, Value};
+
+use crate::{text_key, ConversionConfig, ConversionError, EntityHandling, JsonPointer, XmlPath};
+
+/// Parts of an element that treexml doesn't keep, recorded by the backends that see them. The
+/// tree mirrors the element tree, `children` holding the annotations of the child elements by
+/// index.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Annotations {
+    /// Line and column of the start tag, both counted from 1.
+    pub(crate) position: Option<(u32, u32)>,
+    /// Comments directly inside the element, in document order.
+    pub(crate) comments: Vec<String>,
+    /// Processing instructions directly inside the element, as written between `<?` and `?>`.
+    pub(crate) pis: Vec<String>,
+    pub(crate) children: Vec<Annotations>,
+}
+
+/// What a parser backend kept of the source document beyond the treexml tree, which decides
+/// the options it supports, see `check_options`.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Recorded {
+    /// Text and attribute values were read with their references treated as
+    /// `ConversionConfig::expand_entities` and `ConversionConfig::entity_handling` ask.
+    pub(crate) references: bool,
+    /// Comments and processing instructions are in the `Annotations` of the tree.
+    pub(crate) comments: bool,
+    /// Source positions are in the `Annotations` of the tree.
+    pub(crate) positions: bool,
+}
+
+/// Fails with `ConversionError::UnsupportedOption` if `cfg` asks for something that the
+/// backend didn't record. Called once by each entry point, before anything is converted.
+pub(crate) fn check_options(
+    cfg: &ConversionConfig,
+    recorded: Recorded,
+) -> Result<(), ConversionError> {
+    let option = if !cfg.expand_entities && !recorded.references {
+        "expand_entities"
+    } else if cfg.entity_handling == EntityHandling::PreserveAsString && !recorded.references {
+        "entity_handling"
+    } else if cfg.include_positions && !recorded.positions {
+        "include_positions"
+    } else if cfg.comment_key.is_some() && !recorded.comments {
+        "comment_key"
+    } else if cfg.pi_key.is_some() && !recorded.comments {
+        "pi_key"
+    } else {
+        return Ok(());
+    };
+    Err(ConversionError::UnsupportedOption {
+        option,
+        path: JsonPointer::default(),
+        xml_path: XmlPath::default(),
+    })
+}
+
+/// Stores the comments and processing instructions of an element in its conversion result,
+/// under `ConversionConfig::comment_key` and `ConversionConfig::pi_key`, promoting scalars to
+/// objects. Several of them are stored as an array. Its position is stored under
+/// `ConversionConfig::position_key` if `ConversionConfig::include_positions` is set.
+pub(crate) fn annotate(
+    value: Option<Value>,
+    notes: Option<&Annotations>,
+    cfg: &ConversionConfig,
+) -> Option<Value> {
+    let notes = match notes {
+        Some(notes) => notes,
+        None => return value,
+    };
+    let entries = [
+        (&cfg.comment_key, &notes.comments),
+        (&cfg.pi_key, &notes.pis),
+    ]
+    .iter()
+    .filter_map(|&(key, items)| Some((key.as_ref()?, items)))
+    .filter(|(_, items)| !items.is_empty())
+    .collect::<Vec<_>>();
+    let position = notes.position.filter(|_| cfg.include_positions);
+    if entries.is_empty() && position.is_none() {
+        return value;
+    }
+
+    let mut data = match value? {
+        Value::Object(data) => data,
+        Value::Null => Map::new(),
+        v => vec![(text_key(cfg).to_string(), v)].into_iter().collect(),
+    };
+    for (key, items) in entries {
+        let value = match items.as_slice() {
+            [item] => Value::String(item.clone()),
+            items => items.iter().cloned().map(Value::String).collect(),
+        };
+        data.insert(key.clone(), value);
+    }
+    if let Some((line, col)) = position {
+        let position = vec![
+            ("line".to_string(), line.into()),
+            ("col".to_string(), col.into()),
+        ];
+        data.insert(
+            cfg.position_key.clone(),
+            Value::Object(position.into_iter().collect()),
+        );
+    }
+    Some(Value::Object(data))
+}
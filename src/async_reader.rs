@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+
+use serde_json::{Map, Value};
+use tokio::io::{AsyncRead, BufReader};
+
+use crate::{
+    coerce_scalar, pull::io_error, streaming::Streamer, text_key, wrap_root, ConversionConfig,
+    ReadError, StreamHandler,
+};
+
+/// Collects what `Streamer` reports into the object of the root element.
+#[derive(Default)]
+struct RootCollector {
+    data: Map<String, Value>,
+    /// Keys reported more than once, whose values are stored in an array.
+    repeated: HashSet<String>,
+    text: String,
+}
+
+impl StreamHandler for RootCollector {
+    fn on_text(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    fn on_object(&mut self, key: &str, value: Value) {
+        match self.data.get_mut(key) {
+            Some(Value::Array(items)) if self.repeated.contains(key) => items.push(value),
+            Some(first) => {
+                *first = Value::Array(vec![first.take(), value]);
+                self.repeated.insert(key.to_string());
+            }
+            None => {
+                self.data.insert(key.to_string(), value);
+            }
+        }
+    }
+}
+
+impl RootCollector {
+    /// Value of the root element: its text alone if nothing else was reported, otherwise its
+    /// object with the text under the text key.
+    fn finish(mut self, cfg: &ConversionConfig) -> Option<Value> {
+        let text = Some(&self.text)
+            .filter(|text| !text.is_empty())
+            .map(|text| coerce_scalar(text, cfg));
+        match text {
+            Some(v) if self.data.is_empty() => Some(v),
+            Some(v) => {
+                self.data.insert(text_key(cfg).to_string(), v);
+                Some(Value::Object(self.data))
+            }
+            None if self.data.is_empty() => None,
+            None => Some(Value::Object(self.data)),
+        }
+    }
+}
+
+/// Reads an XML document from `r` and converts it the way `stream_convert` does, collecting
+/// the reported parts into the object of the root element: the values of repeated keys are
+/// stored in an array, and text directly inside the root is stored under the text key. The
+/// input is read in small chunks as the parser needs them, so back-pressure reaches the
+/// reader, and each child of the root is converted and dropped as soon as its end tag is read,
+/// so only one child subtree is held in memory at a time.
+///
+/// As with `stream_convert`, options that look at the siblings of the children of the root,
+/// such as `force_array`, `merge_siblings` or the namespace options, don't apply to them.
+pub async fn convert_async_reader<R: AsyncRead + Unpin>(
+    r: R,
+    cfg: &ConversionConfig,
+) -> Result<Value, ReadError> {
+    let mut streamer = Streamer::new(cfg)?;
+    let mut collector = RootCollector::default();
+    let mut reader = quick_xml::Reader::from_reader(BufReader::new(r));
+    let mut buf = Vec::new();
+    loop {
+        let event = match reader.read_event_into_async(&mut buf).await {
+            Ok(event) => event,
+            Err(quick_xml::Error::Io(e)) => return Err(ReadError::Io(io_error(e))),
+            Err(e) => {
+                return Err(ReadError::Parse {
+                    message: e.to_string(),
+                    position: Some(reader.error_position()),
+                })
+            }
+        };
+        if streamer.event(event, reader.buffer_position(), &mut collector)? {
+            break;
+        }
+        buf.clear();
+    }
+
+    let (root, _) = streamer.finish()?;
+    let value = collector.finish(cfg);
+    Ok(Value::Object(wrap_root(&root, value, cfg)))
+}
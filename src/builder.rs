@@ -0,0 +1,207 @@
+use serde_json::{Map, Number, Value};
+
+use crate::{drive, ConversionConfig, ConversionError, JsonEventSink};
+
+/// Constructor of JSON values in some representation, for output types other than
+/// `serde_json::Value`.
+pub trait ValueBuilder {
+    type Value;
+
+    fn null(&self) -> Self::Value;
+    fn bool(&self, b: bool) -> Self::Value;
+    fn number_i64(&self, n: i64) -> Self::Value;
+    fn number_u64(&self, n: u64) -> Self::Value;
+    fn number_f64(&self, n: f64) -> Self::Value;
+    fn string(&self, s: String) -> Self::Value;
+    fn array(&self, items: Vec<Self::Value>) -> Self::Value;
+    fn object(&self, entries: Vec<(String, Self::Value)>) -> Self::Value;
+}
+
+/// Builder of `serde_json::Value`s, the representation used by the rest of the crate.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SerdeJsonBuilder;
+
+impl ValueBuilder for SerdeJsonBuilder {
+    type Value = Value;
+
+    fn null(&self) -> Value {
+        Value::Null
+    }
+
+    fn bool(&self, b: bool) -> Value {
+        Value::Bool(b)
+    }
+
+    fn number_i64(&self, n: i64) -> Value {
+        Value::Number(n.into())
+    }
+
+    fn number_u64(&self, n: u64) -> Value {
+        Value::Number(n.into())
+    }
+
+    /// Non-finite numbers become `null`, as in `serde_json`.
+    fn number_f64(&self, n: f64) -> Value {
+        Number::from_f64(n).map_or(Value::Null, Value::Number)
+    }
+
+    fn string(&self, s: String) -> Value {
+        Value::String(s)
+    }
+
+    fn array(&self, items: Vec<Value>) -> Value {
+        Value::Array(items)
+    }
+
+    fn object(&self, entries: Vec<(String, Value)>) -> Value {
+        Value::Object(entries.into_iter().collect::<Map<_, _>>())
+    }
+}
+
+/// Builder of `simd_json::OwnedValue`s.
+#[cfg(feature = "simd-json")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SimdJsonBuilder;
+
+#[cfg(feature = "simd-json")]
+impl ValueBuilder for SimdJsonBuilder {
+    type Value = simd_json::OwnedValue;
+
+    fn null(&self) -> Self::Value {
+        simd_json::StaticNode::Null.into()
+    }
+
+    fn bool(&self, b: bool) -> Self::Value {
+        simd_json::StaticNode::Bool(b).into()
+    }
+
+    fn number_i64(&self, n: i64) -> Self::Value {
+        simd_json::StaticNode::I64(n).into()
+    }
+
+    fn number_u64(&self, n: u64) -> Self::Value {
+        simd_json::StaticNode::U64(n).into()
+    }
+
+    fn number_f64(&self, n: f64) -> Self::Value {
+        simd_json::StaticNode::F64(n).into()
+    }
+
+    fn string(&self, s: String) -> Self::Value {
+        simd_json::OwnedValue::String(s)
+    }
+
+    fn array(&self, items: Vec<Self::Value>) -> Self::Value {
+        simd_json::OwnedValue::Array(Box::new(items))
+    }
+
+    fn object(&self, entries: Vec<(String, Self::Value)>) -> Self::Value {
+        simd_json::OwnedValue::Object(Box::new(entries.into_iter().collect()))
+    }
+}
+
+/// Rebuilds `v` with `builder`, keeping the order of object keys.
+pub fn build_value<B: ValueBuilder + ?Sized>(v: Value, builder: &B) -> B::Value {
+    match v {
+        Value::Null => builder.null(),
+        Value::Bool(b) => builder.bool(b),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => builder.number_u64(n),
+            (None, Some(n)) => builder.number_i64(n),
+            _ => builder.number_f64(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => builder.string(s),
+        Value::Array(items) => builder.array(
+            items
+                .into_iter()
+                .map(|item| build_value(item, builder))
+                .collect(),
+        ),
+        Value::Object(data) => builder.object(
+            data.into_iter()
+                .map(|(k, v)| (k, build_value(v, builder)))
+                .collect(),
+        ),
+    }
+}
+
+/// Container being filled by a `BuilderSink`.
+enum Open<V> {
+    Object(Vec<(String, V)>),
+    Array(Vec<V>),
+}
+
+/// Sink building the value described by the events it receives with a `ValueBuilder`.
+struct BuilderSink<'b, B: ValueBuilder + ?Sized> {
+    builder: &'b B,
+    open: Vec<(Option<String>, Open<B::Value>)>,
+    root: Option<B::Value>,
+}
+
+impl<B: ValueBuilder + ?Sized> BuilderSink<'_, B> {
+    /// Stores a finished value in the innermost open container, or makes it the root.
+    fn add(&mut self, key: Option<&str>, value: B::Value) {
+        match self.open.last_mut() {
+            Some((_, Open::Object(entries))) => {
+                entries.push((key.unwrap_or_default().to_string(), value));
+            }
+            Some((_, Open::Array(items))) => items.push(value),
+            None => self.root = Some(value),
+        }
+    }
+
+    fn end(&mut self) {
+        let (key, value) = match self.open.pop() {
+            Some((key, Open::Object(entries))) => (key, self.builder.object(entries)),
+            Some((key, Open::Array(items))) => (key, self.builder.array(items)),
+            None => return,
+        };
+        self.add(key.as_deref(), value);
+    }
+}
+
+impl<B: ValueBuilder + ?Sized> JsonEventSink for BuilderSink<'_, B> {
+    fn start_object(&mut self, key: Option<&str>) {
+        self.open
+            .push((key.map(str::to_string), Open::Object(Vec::new())));
+    }
+
+    fn end_object(&mut self) {
+        self.end();
+    }
+
+    fn start_array(&mut self, key: Option<&str>) {
+        self.open
+            .push((key.map(str::to_string), Open::Array(Vec::new())));
+    }
+
+    fn end_array(&mut self) {
+        self.end();
+    }
+
+    fn scalar(&mut self, key: Option<&str>, value: &Value) {
+        let value = build_value(value.clone(), self.builder);
+        self.add(key, value);
+    }
+
+    fn value(&mut self, key: Option<&str>, value: Value) {
+        let value = build_value(value, self.builder);
+        self.add(key, value);
+    }
+}
+
+/// Same as `node2object_with_config`, with the resulting object built by `builder` as the
+/// conversion goes, from the events that `drive` reports.
+pub fn node2object_with_builder<B: ValueBuilder + ?Sized>(
+    e: &treexml::Element,
+    cfg: &ConversionConfig,
+    builder: &B,
+) -> Result<B::Value, ConversionError> {
+    let mut sink = BuilderSink {
+        builder,
+        open: Vec::new(),
+        root: None,
+    };
+    drive(e, &mut sink, cfg)?;
+    Ok(sink.root.expect("documents are reported as objects"))
+}
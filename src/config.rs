@@ -0,0 +1,775 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+
+use serde_json::Value;
+
+use crate::{TextParser, XMLNodeType};
+
+/// Location of the element being converted, as seen by user-supplied hooks.
+#[derive(Clone, Copy, Debug)]
+pub struct NodeContext<'a> {
+    ancestors: &'a [&'a treexml::Element],
+    element: &'a treexml::Element,
+}
+
+impl<'a> NodeContext<'a> {
+    pub(crate) fn new(
+        ancestors: &'a [&'a treexml::Element],
+        element: &'a treexml::Element,
+    ) -> Self {
+        Self { ancestors, element }
+    }
+
+    /// The element being converted.
+    pub fn element(&self) -> &'a treexml::Element {
+        self.element
+    }
+
+    /// Ancestors of the element, starting with the root element.
+    pub fn ancestors(&self) -> &'a [&'a treexml::Element] {
+        self.ancestors
+    }
+
+    /// Checks whether the element is addressed by `pattern`, either an element name or a
+    /// slash-separated path from the root element.
+    pub fn matches(&self, pattern: &str) -> bool {
+        crate::path_matches(pattern, self.ancestors, self.element)
+    }
+}
+
+type AttributeParserFn = dyn Fn(&str, &str, &NodeContext) -> Option<Value> + Send + Sync;
+
+/// Hook converting attribute values, see `ConversionConfig::attribute_parser`.
+#[derive(Clone)]
+pub struct AttributeParser(Arc<AttributeParserFn>);
+
+impl AttributeParser {
+    /// Wraps a function receiving the attribute name, its raw value and the owning element.
+    /// Returning `None` falls back to the built-in coercion.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&str, &str, &NodeContext) -> Option<Value> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn parse(&self, name: &str, value: &str, node: &NodeContext) -> Option<Value> {
+        (self.0)(name, value, node)
+    }
+}
+
+impl fmt::Debug for AttributeParser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AttributeParser(..)")
+    }
+}
+
+/// Binary encodings that base64 payloads can be re-emitted in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryEncoding {
+    /// Lowercase hexadecimal, emitted as `{"#hex": "..."}`.
+    Hex,
+    /// URL-safe base64 alphabet, emitted as `{"#base64url": "..."}`.
+    Base64Url,
+}
+
+/// Treatment of text content known to carry a base64 payload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Base64Handling {
+    /// Validate the payload and pass it through as `{"#base64": "..."}`.
+    Tagged,
+    /// Decode the payload and emit it in another encoding.
+    Reencode(BinaryEncoding),
+    /// Replace the payload with `{"#binary": {"bytes": N}}`.
+    Metadata,
+}
+
+/// Resolution of key conflicts between caller-supplied context and converted XML data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContextConflict {
+    /// The context value replaces the XML-derived one.
+    ContextWins,
+    /// The XML-derived value is kept and the context value is dropped.
+    #[default]
+    XmlWins,
+    /// Conversion fails with `ConversionError::ContextConflict`.
+    Error,
+}
+
+/// Resolution of an attribute and a child element stored under the same key, e.g. with
+/// `merge_attributes_into_root`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CollisionStrategy {
+    /// The attribute is kept and the children are dropped.
+    AttributeWins,
+    /// The children replace the attribute.
+    #[default]
+    ChildWins,
+    /// Conversion fails with `ConversionError::AttributeChildCollision`.
+    Error,
+}
+
+/// Namespace prefixes in element and attribute keys, in standard mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PrefixHandling {
+    /// Element keys use the local name and attribute keys keep their prefix, e.g.
+    /// `{"item": {"@xml:lang": "en"}}` for `<acme:item xml:lang="en"/>`.
+    #[default]
+    AttributesOnly,
+    /// Element and attribute keys keep their prefix, e.g. `acme:item`.
+    Keep,
+    /// Element and attribute keys use the local name, e.g. `@lang`. Namespace declarations
+    /// are left alone. Not applied by `node2object_lazy` and the functions converting parts of
+    /// a document.
+    Strip,
+}
+
+/// Keys of unprefixed elements in a default namespace declared with `xmlns="..."`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DefaultNsHandling {
+    /// Local names, like elements in no namespace.
+    #[default]
+    Ignore,
+    /// The given prefix, e.g. `atom:feed`.
+    ApplyPrefix(String),
+    /// Clark notation, e.g. `{http://www.w3.org/2005/Atom}feed`.
+    Expand,
+}
+
+/// Position of attribute keys relative to the other keys of an object. Only observable with the
+/// `preserve_order` feature, since objects are otherwise ordered by key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttrPosition {
+    /// Attributes before children and text.
+    #[default]
+    First,
+    /// Attributes after children and text.
+    Last,
+    /// Attributes interleaved with children and text in alphabetical order, ignoring the `@`
+    /// prefix.
+    Sorted,
+}
+
+/// Character separating the integer and fractional parts of decimal numbers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecimalSeparator {
+    /// `12.50`
+    #[default]
+    Dot,
+    /// `12,50`
+    Comma,
+}
+
+/// Representation of empty elements, i.e. elements without attributes, children or text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyElement {
+    /// Leave the element out of its parent's object.
+    #[default]
+    Omit,
+    /// `null`
+    Null,
+    /// `""`
+    EmptyString,
+    /// `{}`
+    EmptyObject,
+    /// `true`, for flag-style elements such as `<enabled/>`.
+    Boolean,
+}
+
+/// Separator of the values in a multi-valued attribute.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SplitStrategy {
+    /// Runs of whitespace, as in `class="a b c"`.
+    #[default]
+    Whitespace,
+    Comma,
+    Semicolon,
+    Char(char),
+}
+
+/// Order of the text and CDATA portions of an element merged by `text_merge_separator`.
+/// treexml collects all text and all CDATA sections of an element separately, so their
+/// interleaving in the document is lost.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextMergeOrder {
+    #[default]
+    TextFirst,
+    CdataFirst,
+}
+
+/// Treatment of repeated siblings whose values have different JSON types.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeteroPolicy {
+    /// Keep the values as they are.
+    #[default]
+    Allow,
+    /// Replace every scalar in the array by the text of its element, e.g. `[1, "abc"]` becomes
+    /// `["1", "abc"]`. Objects are left as they are.
+    Stringify,
+    /// Fail with `ConversionError::InconsistentSiblingTypes`.
+    Error,
+}
+
+/// Representation of attributes with an empty value, such as `note=""`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyAttribute {
+    /// `""`
+    #[default]
+    EmptyString,
+    /// `null`
+    Null,
+    /// Leave the attribute out. An element whose attributes are all skipped is classified as if
+    /// it had none, e.g. `<e note=""/>` is an empty element and follows `empty_element_as`.
+    Skip,
+}
+
+/// Key under which the content of a collapsed wrapper element is stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WrapperKey {
+    /// The wrapper's own name.
+    #[default]
+    Wrapper,
+    /// The name of the innermost element of the collapsed chain.
+    Child,
+}
+
+/// Comparison function for `ArraySortKey::Custom`.
+pub type ValueComparator = dyn Fn(&Value, &Value) -> Ordering + Send + Sync;
+
+/// Replacement of reference attributes resolved through `ConversionConfig::id_references`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReferenceMode {
+    /// The converted value of the referenced element. References inside it are resolved as
+    /// well, up to `IdReferences::max_inlined` values, and reference cycles fail the conversion
+    /// with `ConversionError::ReferenceCycle`.
+    #[default]
+    Inline,
+    /// A JSON Pointer string to the referenced element's value in the output, e.g.
+    /// `"/library/author/0"`.
+    Pointer,
+}
+
+/// Attributes linking elements within a document, e.g. `<author id="a1"/>` and
+/// `<book author-ref="a1"/>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdReferences {
+    /// Attribute identifying an element. When several elements share an identifier, the first
+    /// one in document order is referenced.
+    pub id_attribute: String,
+    /// Attributes holding the identifier of another element.
+    pub reference_attributes: Vec<String>,
+    pub mode: ReferenceMode,
+    /// Maximum number of values inlined into a document by `ReferenceMode::Inline`, counting
+    /// those inlined into other inlined values, 10000 by default. Beyond it, the conversion
+    /// fails with `ConversionError::InliningLimitExceeded`, so that references fanning out at
+    /// every level can't blow up the output.
+    pub max_inlined: usize,
+}
+
+impl Default for IdReferences {
+    fn default() -> Self {
+        Self {
+            id_attribute: "id".into(),
+            reference_attributes: Vec::new(),
+            mode: ReferenceMode::default(),
+            max_inlined: 10_000,
+        }
+    }
+}
+
+/// Order imposed on arrays by `ConversionConfig::sort_arrays_by`.
+#[derive(Clone)]
+pub enum ArraySortKey {
+    /// Objects are ordered by their first key alphabetically, then by the value under it.
+    /// Other values are ordered as in `AllKeys`.
+    FirstKey,
+    /// Values are ordered by type (null, booleans, numbers, strings, arrays, objects), then by
+    /// content, comparing objects key by key.
+    AllKeys,
+    /// A caller-supplied comparison.
+    Custom(Arc<ValueComparator>),
+}
+
+impl fmt::Debug for ArraySortKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArraySortKey::FirstKey => f.write_str("FirstKey"),
+            ArraySortKey::AllKeys => f.write_str("AllKeys"),
+            ArraySortKey::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// Treatment of entity references in text and attribute values.
+///
+/// treexml (through xml-rs) resolves the predefined entities (`&amp;`, `&lt;`, ...) and
+/// character references while parsing, and fails to parse documents referencing any other
+/// entity, including ones declared in an internal DTD subset. Text reaching the converter is
+/// therefore always fully expanded. `qxml_node2object` reads references as written, so every
+/// variant applies there; undeclared entities are never resolved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EntityHandling {
+    /// Keep whatever the parser produced. With quick-xml, references to other entities than
+    /// the predefined ones fail with `ConversionError::Malformed`.
+    #[default]
+    Default,
+    /// Only expand the predefined entities and character references, keeping references to
+    /// other entities as written. With treexml this is the same as `Default`.
+    ExpandBuiltin,
+    /// Emit entity references unexpanded, e.g. `"&copy;"` or `"&amp;"`, still expanding
+    /// character references. Not supported by treexml; conversion fails with
+    /// `ConversionError::UnsupportedOption`.
+    PreserveAsString,
+    /// Fail with `ConversionError::UnresolvableEntity` on references that can't be resolved.
+    /// With treexml, such documents already fail to parse.
+    Error,
+}
+
+/// Overall shape of the generated JSON.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConversionMode {
+    /// The mapping described in the crate documentation: `@`-prefixed attributes, `#text` for
+    /// text next to attributes, and coercion of scalar values.
+    #[default]
+    Standard,
+    /// The GData convention used by legacy Google APIs: unprefixed attributes, text under `$t`,
+    /// every text leaf wrapped as `{"$t": "..."}`, namespace prefixes joined with `$`
+    /// (`openSearch$totalResults`), and all values kept as strings.
+    GData,
+}
+
+/// Options controlling the conversion. `ConversionConfig::default()` reproduces the behavior of `node2object`.
+#[derive(Clone, Debug)]
+pub struct ConversionConfig {
+    /// Output convention.
+    pub mode: ConversionMode,
+    /// Wrap the output in an object keyed by the root element's name. When disabled, the output
+    /// is the root's own object; a root converting to a scalar is stored under the text key.
+    pub include_root: bool,
+    /// Make `document_to_object` inject the XML declaration as `"@_xml": {"version", "encoding",
+    /// "standalone"}` into the root-level output map. treexml doesn't record the `standalone`
+    /// pseudo-attribute, so it is always `null`.
+    pub include_xml_declaration: bool,
+    /// Key under which text is stored next to attributes or children. Defaults to `#text`, or
+    /// `$t` in GData mode.
+    pub text_key: Option<String>,
+    /// Per node type override of `text_key`, e.g. `"content"` for `XMLNodeType::SemiStructured`.
+    /// Types missing from the map use `text_key`.
+    pub text_key_for_type: HashMap<XMLNodeType, String>,
+    /// Prefix added to element and attribute keys that would clash with a reserved key, e.g. a
+    /// `<value>` child becomes `_value` when the text key is `value`. The reserved keys are the
+    /// text key and the values of `text_key_for_type`, `attributes_key`, `root_element_name_key`,
+    /// `truncated_count_key`, `comment_key` and `pi_key`, along with `cdata_key` when
+    /// `separate_cdata` is set, `source_tag_key` when `inject_source_tag` is set, `position_key`
+    /// when `include_positions` is set, and `namespace_key` and `"#attr_ns"` when `namespace_key`
+    /// is set. In strict mode, such keys fail the conversion with `ConversionError::ReservedKey`
+    /// instead.
+    pub reserved_key_escape: String,
+    /// Inject the root element's tag name into the root's object under this key.
+    pub root_element_name_key: Option<String>,
+    /// Inject the tag name of every element converted to an object into that object, under
+    /// `source_tag_key`, e.g. for telling apart polymorphic children in downstream consumers.
+    pub inject_source_tag: bool,
+    /// Key under which `inject_source_tag` stores the tag name, `"_tag"` by default.
+    pub source_tag_key: String,
+    /// Store same-named siblings that all carry distinct `xml:lang` attributes in an object keyed
+    /// by language code, e.g. `{"title": {"en": "Hi", "de": "Hallo"}}`. The `xml:lang` attribute
+    /// itself is dropped from each converted element. Siblings with missing or duplicate codes
+    /// are converted as usual, or fail the conversion in strict mode.
+    pub group_by_xml_lang: bool,
+    /// Fail on malformed input instead of falling back to the lenient conversion.
+    pub strict: bool,
+    /// Elements whose text is a base64 payload. Keys are either element names or
+    /// slash-separated paths from the root element, e.g. `doc/attachment/data`; of a path and a
+    /// name addressing the same element, the path wins. Invalid payloads are converted as
+    /// ordinary text unless `strict` is set.
+    pub base64_paths: HashMap<String, Base64Handling>,
+    /// How `node2object_with_context` resolves context keys that clash with XML-derived keys.
+    pub context_conflict: ContextConflict,
+    /// Expand entity and character references such as `&copy;` and `&#169;` in text and
+    /// attribute values. Keeping them as written requires a backend reading raw text, such as
+    /// `qxml_node2object`; treexml doesn't, so the other functions fail with
+    /// `ConversionError::UnsupportedOption` when this is disabled.
+    pub expand_entities: bool,
+    /// Store attributes like child elements holding the attribute value as text, e.g.
+    /// `<e id="1"/>` becomes `{"e": {"id": {"#text": 1.0}}}`, with values coerced like text
+    /// content. Collisions with child elements are resolved by `attribute_child_collision`.
+    pub attribute_as_child: bool,
+    /// Store attributes next to child elements without the `@` prefix, e.g.
+    /// `<e id="1"><name>foo</name></e>` becomes `{"e": {"id": 1.0, "name": "foo"}}`.
+    /// Collisions with child elements are resolved by `attribute_child_collision`.
+    pub merge_attributes_into_root: bool,
+    /// Group the attributes of each element in an object stored under this key, e.g. `"$"`
+    /// gives `{"e": {"$": {"id": 1.0}}}` for `<e id="1"/>`. Grouped attributes are stored without
+    /// the `@` prefix.
+    pub attributes_key: Option<String>,
+    /// Position of attributes relative to children and text within each object.
+    pub attribute_position: AttrPosition,
+    /// Resolution of attributes and child elements stored under the same key.
+    pub attribute_child_collision: CollisionStrategy,
+    /// Maximum nesting depth of converted elements, counting the root element as depth 1.
+    /// Deeper elements fail the conversion with `ConversionError::DepthExceeded`, unless
+    /// `truncate_at_max_depth` is set.
+    pub max_depth: Option<usize>,
+    /// Replace elements nested deeper than `max_depth` with `truncation_placeholder` instead of
+    /// failing. Repeated truncated elements form an array of placeholders like any other values.
+    pub truncate_at_max_depth: bool,
+    /// Value standing in for truncated subtrees, `"#truncated"` by default.
+    pub truncation_placeholder: Value,
+    /// Store text-only children (no attributes, no children) like attributes of their parent,
+    /// with their text kept as a string, e.g. `<e><id>42</id></e>` becomes
+    /// `{"e": {"@id": "42"}}`. Repeated children and children sharing their name with an
+    /// attribute of the parent are converted as usual.
+    pub child_as_attribute: bool,
+    /// Drop all attributes, as in the Parker convention. Elements left with text only convert
+    /// to plain scalars.
+    pub drop_attributes: bool,
+    /// Names of attributes to leave out of the output, e.g. `xsi:schemaLocation`.
+    pub skip_attributes: Vec<String>,
+    /// Leave `xmlns` and `xmlns:*` namespace declarations out of the output. The backends that
+    /// resolve namespaces themselves, such as `roxmltree_node2object`, always leave them out.
+    pub strip_xmlns_attributes: bool,
+    /// Canonical prefixes by namespace URI, e.g. `urn:acme` → `acme`, so that documents binding
+    /// a namespace to different prefixes convert to the same keys. Elements and attributes in a
+    /// listed namespace, including the default namespace, are keyed with the canonical prefix
+    /// (`acme:order`, `@acme:id`), or without a prefix if it is empty. Other keys follow
+    /// `namespace_prefixes`. Namespaces are resolved from the `xmlns` declarations among the
+    /// attributes of the converted tree, which treexml leaves out when parsing, so they must
+    /// be added back by hand. In standard mode, path patterns in other options see the
+    /// prefixed names. Not applied by `node2object_lazy` and the functions converting parts of
+    /// a document.
+    pub prefix_map: HashMap<String, String>,
+    /// Namespace prefixes kept in keys of elements and attributes that are not listed in
+    /// `prefix_map` or `strip_namespaces`. GData mode always keeps element prefixes.
+    pub namespace_prefixes: PrefixHandling,
+    /// Character written between namespace prefix and local name in the keys of elements and
+    /// attributes that keep their prefix, e.g. `_` for `@xml_lang`. Namespace declarations
+    /// are keyed alike (`@xmlns_acme`). Standard mode only.
+    pub attribute_namespace_separator: char,
+    /// URIs of namespaces whose elements and attributes are keyed by their local name, e.g.
+    /// the SOAP envelope namespace, while other namespaces keep their prefix. Declarations of
+    /// these namespaces are left out. Elements ending up with the same key are grouped like
+    /// repeated elements and attributes clashing with children are resolved by
+    /// `attribute_child_collision`; of two attributes ending up with the same name, the one
+    /// written without a prefix is kept, unless `CollisionStrategy::Error` makes the conversion
+    /// fail. Namespaces are resolved as for `prefix_map`, which takes precedence.
+    pub strip_namespaces: HashSet<String>,
+    /// Keys of unprefixed elements in a default namespace that is not listed in `prefix_map` or
+    /// `strip_namespaces`. Elements below an `xmlns=""` declaration are in no namespace and
+    /// keep their local name. Namespaces are resolved as for `prefix_map`.
+    pub default_namespace: DefaultNsHandling,
+    /// Store the namespace URI of every element in a namespace in its object under this key,
+    /// e.g. `"#ns"`, and the URIs of its namespaced attributes in a map from attribute key to
+    /// URI under `"#attr_ns"`. Elements converting to a scalar are promoted to an object holding
+    /// the scalar under the text key when there is a namespace to record; elements in no
+    /// namespace are left alone. Namespaces are resolved as for `prefix_map`.
+    pub namespace_key: Option<String>,
+    /// Names of wrapper elements to remove from the output. A listed element with exactly one
+    /// child element and no attributes or text is replaced by the content of its child, so
+    /// `<Envelope><Body><Response>…</Response></Body></Envelope>` can flatten to a single level.
+    /// Chains of wrappers collapse all the way down.
+    pub collapse_wrappers: Vec<String>,
+    /// Key under which collapsed wrappers store the hoisted content.
+    pub collapsed_wrapper_key: WrapperKey,
+    /// Repeated elements to deep-merge into a single object instead of collecting them in an
+    /// array. Entries are either element names or slash-separated paths from the root element.
+    /// Nested objects are merged recursively and arrays are concatenated. When the occurrences
+    /// disagree on a scalar value, the last one wins, or the conversion fails with
+    /// `ConversionError::MergeConflict` in strict mode.
+    pub merge_siblings: HashSet<String>,
+    /// Elements that are always stored in an array, even when they occur once. Entries are
+    /// either element names or slash-separated paths from the root element.
+    pub force_array: HashSet<String>,
+    /// Child element names that are always present as arrays in the object of their parent,
+    /// even when they occur once or not at all. Keys locate the parents, either by element name
+    /// or by slash-separated path from the root element. Matching parents convert to objects
+    /// even when they are empty or only hold text.
+    pub ensure_array_keys: HashMap<String, Vec<String>>,
+    /// Maximum number of same-named siblings to convert, keyed by element name or by
+    /// slash-separated path from the root element. Further occurrences are skipped without
+    /// being converted, e.g. to preview a large export from its first records. The smallest
+    /// matching limit applies.
+    pub max_occurrences: HashMap<String, usize>,
+    /// Store the number of siblings skipped by `max_occurrences` in the object of their parent
+    /// under this key, e.g. `"#truncated_count"`, as a map from element key to count.
+    pub truncated_count_key: Option<String>,
+    /// Number of occurrences from which repeated elements are stored in an array. With the
+    /// default of 2, a single occurrence stays a plain value; with 0 or 1, every element is
+    /// wrapped in an array. Larger values behave like 2, since repeated elements can't be
+    /// stored under one key otherwise.
+    pub single_element_array_threshold: usize,
+    /// Give repeated text-only siblings a single JSON type. When their coerced values
+    /// disagree, integers mixed with floats are all widened to floats, and any other mix
+    /// falls back to the original strings. Groups are harmonized per parent element.
+    pub harmonize_sibling_types: bool,
+    /// Treatment of arrays of repeated siblings whose values have different JSON types. This
+    /// applies after `harmonize_sibling_types`.
+    pub heterogeneous_arrays: HeteroPolicy,
+    /// Let `null` values, e.g. from empty elements with `EmptyElement::Null`, appear next to
+    /// values of any other type without `heterogeneous_arrays` considering the array
+    /// inconsistent. Otherwise `null` counts as a type of its own and is stringified to `""`.
+    pub heterogeneous_arrays_allow_null: bool,
+    /// Keys under which arrays of repeated elements are stored, by element name, e.g.
+    /// `entry` → `entries`. Only applies when the element actually ends up in an array, either
+    /// by repetition, through `force_array` or `single_element_array_threshold`; a single
+    /// occurrence keeps its name. The rename is skipped when a sibling element already uses the
+    /// new key.
+    pub array_key_rename: HashMap<String, String>,
+    /// Sort every array in the output, so that reordered repeated elements don't show up when
+    /// diffing converted documents. The order of elements in the XML is lost.
+    pub sort_arrays_by: Option<ArraySortKey>,
+    /// Replace every array in the output, including those from `force_array`, by an object
+    /// keyed by item index, e.g. `{"0": .., "1": ..}`, for consumers that can't handle
+    /// arrays. Indices are zero-padded to a common width so the keys sort in array order.
+    pub arrays_as_indexed_objects: bool,
+    /// First index used by `arrays_as_indexed_objects`, usually 0 or 1.
+    pub indexed_object_base: usize,
+    /// Replace reference attributes by the element they point to, after conversion. Not
+    /// applied by `node2object_lazy`, which never holds the whole output. References to
+    /// unknown identifiers are left as they are, or fail the conversion with
+    /// `ConversionError::DanglingReference` in strict mode.
+    pub id_references: Option<IdReferences>,
+    /// Wrap each converted element in an object carrying its source position under
+    /// `position_key`, e.g. `{"#pos": {"line": 3, "col": 5}}`, promoting scalars to objects
+    /// under the text key. Lines and columns are counted from 1. Requires a backend that records
+    /// positions, such as `roxmltree_node2object`; treexml doesn't, so conversion fails with
+    /// `ConversionError::UnsupportedOption` when this is enabled.
+    pub include_positions: bool,
+    /// Key under which `include_positions` stores source positions.
+    pub position_key: String,
+    /// Store CDATA sections under `cdata_key` instead of merging them into the text. CDATA
+    /// content is kept as a string, and elements with CDATA are always converted to objects.
+    pub separate_cdata: bool,
+    /// Key under which `separate_cdata` stores CDATA sections.
+    pub cdata_key: String,
+    /// Inserted between the text and the CDATA content of an element when both are non-empty
+    /// and merged into one string.
+    pub text_merge_separator: String,
+    /// Which of the text and the CDATA content comes first when they are merged.
+    pub text_merge_order: TextMergeOrder,
+    /// Key under which to store the comments directly inside an element, as written, promoting
+    /// scalars to objects under the text key. Several comments are stored as an array. Requires
+    /// a backend that records comments, such as `qxml_node2object`; treexml drops them while
+    /// parsing, so conversion fails with `ConversionError::UnsupportedOption` when this is set.
+    pub comment_key: Option<String>,
+    /// Key under which to store the processing instructions directly inside an element, as
+    /// written between `<?` and `?>`, like `comment_key` does for comments.
+    pub pi_key: Option<String>,
+    /// Treatment of entity references, see `EntityHandling`.
+    pub entity_handling: EntityHandling,
+    /// Strip leading and trailing whitespace from text and attribute values before they are
+    /// coerced and stored. Whitespace is Unicode `White_Space`, which includes non-breaking spaces.
+    pub trim_text: bool,
+    /// Coerce padded values such as `"  42  "` as if they were trimmed, while values that don't
+    /// coerce keep their padding. Uses the same whitespace definition as `trim_text` and applies
+    /// to attributes and text alike. Implied by `trim_text`.
+    pub coerce_padded_numbers: bool,
+    /// Coerce numeric text and attribute values to JSON numbers. When disabled, numbers stay
+    /// strings regardless of the other number options, including `parse_radix_literals`.
+    pub coerce_numbers: bool,
+    /// Convert `0x`, `0o` and `0b` prefixed values (optionally negated) into JSON integers.
+    /// Values that don't fit i64/u64 stay strings. When disabled, such values are always strings.
+    pub parse_radix_literals: bool,
+    /// Decimal separator used when coercing text to numbers.
+    pub decimal_separator: DecimalSeparator,
+    /// Accept thousands separators (`,` with `DecimalSeparator::Dot`, `.` with `DecimalSeparator::Comma`)
+    /// in numbers, provided the digits are grouped in threes. Otherwise such values stay strings.
+    pub allow_thousands_separators: bool,
+    /// Coerce values with a leading `+` (e.g. `+5`) to numbers. Off by default, since a leading
+    /// `+` is not valid JSON number syntax and usually marks phone numbers and the like.
+    pub allow_leading_plus: bool,
+    /// Coerce values in scientific notation (`1E2`, `6e7`) to numbers. When disabled, any value
+    /// containing an exponent marker stays a string, while plain decimals are still coerced.
+    pub coerce_scientific_notation: bool,
+    /// Only coerce text to a number if rendering the number reproduces the text exactly (modulo
+    /// a leading `+`), so that values like `1.10`, `007` or `1e3` keep their lexical form.
+    /// Integers are emitted as JSON integers in this mode.
+    pub strict_number_roundtrip: bool,
+    /// Representation of empty elements that have no entry in `defaults`, unless
+    /// `default_text_value` is set.
+    pub empty_element_as: EmptyElement,
+    /// Representation of attributes with an empty value, after trimming if `trim_text` is set.
+    /// Independent from `empty_element_as`.
+    pub empty_attribute_as: EmptyAttribute,
+    /// Leave attributes with an empty value, as for `empty_attribute_as`, out of the object of
+    /// their element. Unlike `EmptyAttribute::Skip`, the element is still classified as having
+    /// attributes, e.g. `<e note=""/>` becomes `{"e": {}}`.
+    pub omit_empty_attributes: bool,
+    /// Attributes holding several values, keyed by attribute name (e.g. `class`), which are
+    /// split and converted to an array of strings. Values are trimmed around separators and
+    /// empty values are dropped. Takes precedence over scalar coercion, but not over
+    /// `attribute_parser`.
+    pub split_attribute_values: HashMap<String, SplitStrategy>,
+    /// Value substituted for empty elements that have no entry in `defaults`, overriding
+    /// `empty_element_as`.
+    pub default_text_value: Option<Value>,
+    /// Values substituted for empty elements (no children, no text or only whitespace text).
+    /// Keys are either element names or slash-separated paths from the root element, as in
+    /// `base64_paths`, and of a path and a name addressing the same element, the path wins.
+    /// Elements with attributes are left alone unless `defaults_for_attribute_only` is set.
+    pub defaults: HashMap<String, Value>,
+    /// Also apply `defaults` to elements that only carry attributes, storing the default under
+    /// the text key next to them.
+    pub defaults_for_attribute_only: bool,
+    /// Emit text content that is coerced to a number or boolean as `{"#value": 42.0, "#raw": "042"}`,
+    /// keeping the original text next to the typed value. Attribute values are not affected.
+    pub preserve_original_string: bool,
+    /// Hook consulted for every attribute value before the built-in coercion, e.g. to turn
+    /// `epoch="1614851200"` into a timestamp while text content is left alone.
+    pub attribute_parser: Option<AttributeParser>,
+    /// Parser for text content and attribute values, replacing the built-in coercion and the
+    /// options controlling it (`coerce_numbers`, `boolean_in_text`, ...) when set. Text is
+    /// trimmed first if `trim_text` is set; GData mode keeps strings regardless.
+    pub text_parser: Option<Arc<dyn TextParser>>,
+    /// Keep every text, CDATA and attribute value a JSON string, skipping `coerce_numbers`,
+    /// `boolean_in_text`, `boolean_in_attributes`, `text_parser` and the other coercions at
+    /// once. Empty attributes stay empty strings under `EmptyAttribute::Null`, while empty
+    /// elements are still converted according to `empty_element_as`.
+    pub all_scalars_as_strings: bool,
+    /// Coerce `true` and `false` attribute values to JSON booleans.
+    pub boolean_in_attributes: bool,
+    /// Coerce `true` and `false` text content to JSON booleans.
+    pub boolean_in_text: bool,
+    /// Normalize text values that look like dates or timestamps to RFC 3339 strings.
+    /// ISO 8601 / RFC 3339 input is always recognized; `date_patterns` adds more formats.
+    /// Detected dates are never coerced to numbers.
+    #[cfg(feature = "dates")]
+    pub detect_dates: bool,
+    /// Additional `chrono` format strings accepted by `detect_dates`, e.g. `%d/%m/%Y`.
+    /// Patterns without a time component produce an RFC 3339 full-date (`2021-03-04`),
+    /// patterns without an offset are interpreted as UTC.
+    #[cfg(feature = "dates")]
+    pub date_patterns: Vec<String>,
+}
+
+/// Configurations reproducing the output of other XML to JSON converters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// The default output of the Node.js [xml2js](https://github.com/Leonidas-from-XIV/node-xml2js)
+    /// parser with the given `explicitArray`, `attrkey` and `charkey` options: values are never
+    /// coerced, attributes are grouped under `attr_key`, text next to attributes or children is
+    /// stored under `char_key`, and empty elements become `""`. treexml drops whitespace-only
+    /// text between children and `xmlns` attributes, which xml2js keeps in mixed content and
+    /// among the attributes respectively.
+    Xml2js {
+        explicit_array: bool,
+        attr_key: String,
+        char_key: String,
+    },
+}
+
+impl ConversionConfig {
+    /// Configuration reproducing the output of `preset`.
+    pub fn preset(preset: Preset) -> Self {
+        match preset {
+            Preset::Xml2js {
+                explicit_array,
+                attr_key,
+                char_key,
+            } => Self {
+                text_key: Some(char_key),
+                attributes_key: Some(attr_key),
+                single_element_array_threshold: if explicit_array { 1 } else { 2 },
+                empty_element_as: EmptyElement::EmptyString,
+                coerce_numbers: false,
+                boolean_in_attributes: false,
+                boolean_in_text: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl Default for ConversionConfig {
+    fn default() -> Self {
+        Self {
+            mode: ConversionMode::default(),
+            include_root: true,
+            include_xml_declaration: false,
+            text_key: None,
+            text_key_for_type: HashMap::new(),
+            reserved_key_escape: "_".into(),
+            root_element_name_key: None,
+            inject_source_tag: false,
+            source_tag_key: "_tag".into(),
+            group_by_xml_lang: false,
+            strict: false,
+            base64_paths: HashMap::new(),
+            context_conflict: ContextConflict::default(),
+            expand_entities: true,
+            max_depth: None,
+            truncate_at_max_depth: false,
+            truncation_placeholder: Value::String("#truncated".into()),
+            attribute_as_child: false,
+            merge_attributes_into_root: false,
+            attributes_key: None,
+            attribute_position: AttrPosition::default(),
+            attribute_child_collision: CollisionStrategy::default(),
+            child_as_attribute: false,
+            drop_attributes: false,
+            skip_attributes: Vec::new(),
+            strip_xmlns_attributes: false,
+            prefix_map: HashMap::new(),
+            namespace_prefixes: PrefixHandling::default(),
+            attribute_namespace_separator: ':',
+            strip_namespaces: HashSet::new(),
+            default_namespace: DefaultNsHandling::default(),
+            namespace_key: None,
+            collapse_wrappers: Vec::new(),
+            collapsed_wrapper_key: WrapperKey::default(),
+            merge_siblings: HashSet::new(),
+            force_array: HashSet::new(),
+            ensure_array_keys: HashMap::new(),
+            max_occurrences: HashMap::new(),
+            truncated_count_key: None,
+            single_element_array_threshold: 2,
+            harmonize_sibling_types: false,
+            heterogeneous_arrays: HeteroPolicy::default(),
+            heterogeneous_arrays_allow_null: true,
+            array_key_rename: HashMap::new(),
+            sort_arrays_by: None,
+            arrays_as_indexed_objects: false,
+            indexed_object_base: 0,
+            id_references: None,
+            include_positions: false,
+            position_key: "#pos".into(),
+            separate_cdata: false,
+            cdata_key: "#cdata".into(),
+            text_merge_separator: String::new(),
+            text_merge_order: TextMergeOrder::default(),
+            comment_key: None,
+            pi_key: None,
+            entity_handling: EntityHandling::default(),
+            trim_text: false,
+            coerce_padded_numbers: false,
+            coerce_numbers: true,
+            parse_radix_literals: false,
+            decimal_separator: DecimalSeparator::default(),
+            allow_thousands_separators: false,
+            allow_leading_plus: false,
+            coerce_scientific_notation: true,
+            strict_number_roundtrip: false,
+            empty_element_as: EmptyElement::default(),
+            empty_attribute_as: EmptyAttribute::default(),
+            omit_empty_attributes: false,
+            split_attribute_values: HashMap::new(),
+            default_text_value: None,
+            defaults: HashMap::new(),
+            defaults_for_attribute_only: false,
+            preserve_original_string: false,
+            attribute_parser: None,
+            text_parser: None,
+            all_scalars_as_strings: false,
+            boolean_in_attributes: true,
+            boolean_in_text: true,
+            #[cfg(feature = "dates")]
+            detect_dates: false,
+            #[cfg(feature = "dates")]
+            date_patterns: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+
+fn format_datetime<Tz: chrono::TimeZone>(v: DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    v.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn parse_with(text: &str, pattern: &str) -> Option<String> {
+    if let Ok(v) = DateTime::parse_from_str(text, pattern) {
+        return Some(format_datetime(v));
+    }
+    if let Ok(v) = NaiveDateTime::parse_from_str(text, pattern) {
+        return Some(format_datetime(v.and_utc()));
+    }
+    if let Ok(v) = NaiveDate::parse_from_str(text, pattern) {
+        return Some(v.format("%Y-%m-%d").to_string());
+    }
+    None
+}
+
+/// Normalizes `text` to an RFC 3339 string if it is an ISO 8601 timestamp or date,
+/// or matches one of `patterns`.
+pub(crate) fn normalize(text: &str, patterns: &[String]) -> Option<String> {
+    if let Ok(v) = DateTime::parse_from_rfc3339(text) {
+        return Some(format_datetime(v));
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d"]
+        .iter()
+        .copied()
+        .chain(patterns.iter().map(String::as_str))
+        .find_map(|pattern| parse_with(text, pattern))
+}
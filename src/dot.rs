@@ -0,0 +1,60 @@
+use std::fmt::Write;
+
+/// Escapes text for use inside a quoted DOT record label.
+fn escape_record(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '{' | '}' | '|' | '<' | '>' | '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_node(out: &mut String, e: &treexml::Element, next_id: &mut usize) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+
+    let name = match &e.prefix {
+        Some(prefix) => format!("{}:{}", prefix, e.name),
+        None => e.name.clone(),
+    };
+    if e.attributes.is_empty() {
+        let _ = writeln!(out, "  n{} [label=\"{}\"];", id, escape_record(&name));
+    } else {
+        let mut attributes = e.attributes.iter().collect::<Vec<_>>();
+        attributes.sort_unstable();
+        let fields = attributes
+            .into_iter()
+            .map(|(k, v)| format!("{}={}\\l", escape_record(k), escape_record(v)))
+            .collect::<String>();
+        let _ = writeln!(
+            out,
+            "  n{} [shape=record, label=\"{{{}|{}}}\"];",
+            id,
+            escape_record(&name),
+            fields
+        );
+    }
+
+    for c in &e.children {
+        let child_id = write_node(out, c, next_id);
+        let _ = writeln!(out, "  n{} -> n{};", id, child_id);
+    }
+    id
+}
+
+/// Renders the element tree as a Graphviz DOT digraph for debugging: one node per element,
+/// labeled with its tag name, and one edge per parent-child relationship. Elements with
+/// attributes are drawn as record nodes listing them.
+pub fn element_to_dot(e: &treexml::Element) -> String {
+    let mut out = String::from("digraph xml {\n");
+    write_node(&mut out, e, &mut 0);
+    out.push_str("}\n");
+    out
+}
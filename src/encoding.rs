@@ -0,0 +1,89 @@
+use std::{borrow::Cow, ops::Range};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use serde_json::Value;
+
+use crate::{node2object_with_config, ConversionConfig, ReadError};
+
+/// Byte range of the `encoding` pseudo-attribute value in the XML declaration that starts
+/// `text`, if any.
+fn declared_encoding(text: &str) -> Option<Range<usize>> {
+    if !text.starts_with("<?xml") {
+        return None;
+    }
+    let declaration = &text[..text.find("?>")?];
+    let after_name = declaration.find("encoding")? + "encoding".len();
+    let rest = declaration[after_name..].trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let start = declaration.len() - rest.len() + 1;
+    let len = declaration[start..].find(quote)?;
+    Some(start..start + len)
+}
+
+/// Encoding of a document without a byte order mark, following appendix F of the XML
+/// specification: UTF-16 is recognized from the encoded `<?`, and ASCII-compatible encodings
+/// from the declaration. Documents declaring nothing are UTF-8.
+fn sniff_encoding(bytes: &[u8]) -> Result<&'static Encoding, ReadError> {
+    match bytes {
+        [0x3C, 0, 0x3F, 0, ..] => return Ok(UTF_16LE),
+        [0, 0x3C, 0, 0x3F, ..] => return Ok(UTF_16BE),
+        _ => {}
+    }
+    let end = bytes
+        .windows(2)
+        .position(|w| w == b"?>")
+        .map_or(0, |i| i + 2);
+    let prefix = String::from_utf8_lossy(&bytes[..end]);
+    let label = match declared_encoding(&prefix) {
+        Some(range) => &prefix[range],
+        None => return Ok(UTF_8),
+    };
+    match Encoding::for_label(label.as_bytes()) {
+        // A UTF-16 declaration in ASCII-compatible bytes can't be right.
+        Some(encoding) if encoding == UTF_16LE || encoding == UTF_16BE => Ok(UTF_8),
+        Some(encoding) => Ok(encoding),
+        None => Err(ReadError::UnknownEncoding(label.to_string())),
+    }
+}
+
+/// Makes the XML declaration of the transcoded `text` declare UTF-8, so that the parser
+/// doesn't decode it a second time.
+fn declare_utf8(text: Cow<'_, str>) -> Cow<'_, str> {
+    match declared_encoding(&text) {
+        Some(range) if !text[range.clone()].eq_ignore_ascii_case("UTF-8") => {
+            let mut text = text.into_owned();
+            text.replace_range(range, "UTF-8");
+            Cow::Owned(text)
+        }
+        _ => text,
+    }
+}
+
+/// Converts the root element of an XML document given as raw bytes, see
+/// `node2object_with_config`. The encoding is taken from the byte order mark, or else from the
+/// XML declaration, defaulting to UTF-8, and the document is transcoded to UTF-8 before
+/// parsing. Encodings are identified by their WHATWG labels, so e.g. `ISO-8859-1` decodes as
+/// windows-1252.
+pub fn xml_bytes_to_json(bytes: &[u8], cfg: &ConversionConfig) -> Result<Value, ReadError> {
+    let (encoding, bom_len) = match Encoding::for_bom(bytes) {
+        Some(found) => found,
+        None => (sniff_encoding(bytes)?, 0),
+    };
+    let (text, malformed) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+    if malformed {
+        return Err(ReadError::Malformed {
+            encoding: encoding.name(),
+        });
+    }
+
+    let root = treexml::Document::parse(declare_utf8(text).as_bytes())
+        .map_err(|e| ReadError::Parse {
+            message: e.to_string(),
+            position: None,
+        })?
+        .root
+        .ok_or(ReadError::NoRoot)?;
+    let data = node2object_with_config(&root, cfg).map_err(ReadError::Conversion)?;
+    Ok(Value::Object(data))
+}
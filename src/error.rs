@@ -0,0 +1,498 @@
+use std::fmt;
+
+/// RFC 6901 JSON Pointer into the converted output, e.g. `/root/items/3/price`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct JsonPointer(String);
+
+impl JsonPointer {
+    /// Appends a reference token, escaping `~` and `/`.
+    pub fn push(&mut self, token: &str) {
+        self.0.push('/');
+        self.0
+            .push_str(&token.replace('~', "~0").replace('/', "~1"));
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for JsonPointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Location of a node in the source document, e.g. `population/entry[3]/@id`. Same-named
+/// siblings are told apart by their 1-based position, as in XPath.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct XmlPath(String);
+
+impl XmlPath {
+    /// Appends an element step. `position` is only rendered when given.
+    pub fn push_element(&mut self, name: &str, position: Option<usize>) {
+        if !self.0.is_empty() {
+            self.0.push('/');
+        }
+        self.0.push_str(name);
+        if let Some(position) = position {
+            self.0.push_str(&format!("[{}]", position));
+        }
+    }
+
+    /// Appends an attribute step.
+    pub fn push_attribute(&mut self, name: &str) {
+        if !self.0.is_empty() {
+            self.0.push('/');
+        }
+        self.0.push('@');
+        self.0.push_str(name);
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for XmlPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Errors that can occur while converting XML nodes. Each error locates the offending node in
+/// the source document (`xml_path`) and in the converted output (`path`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    /// Text under a configured base64 path is not valid base64.
+    InvalidBase64 {
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// Same-named siblings can't be grouped by `xml:lang` because some lack the attribute or
+    /// share a language code. Only raised in strict mode.
+    AmbiguousXmlLang {
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// The configuration asks for something the XML backend can't provide.
+    UnsupportedOption {
+        option: &'static str,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// A context key passed to `node2object_with_context` clashes with a converted key.
+    ContextConflict {
+        key: String,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// Siblings listed in `merge_siblings` hold different scalar values for the same key.
+    /// Only raised in strict mode, or by `merge_documents` with `ScalarConflict::Error`.
+    MergeConflict {
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// An element is nested deeper than `max_depth` allows.
+    DepthExceeded {
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// A segment of the path given to `node2object_at` doesn't match any element. The paths
+    /// locate the last element that was reached.
+    NotFound {
+        segment: String,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// An element or attribute would be stored under a key reserved for structural data, such
+    /// as the text key. Only raised in strict mode.
+    ReservedKey {
+        key: String,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// An entity reference can't be resolved and `EntityHandling::Error` is set. Only raised
+    /// by `qxml_node2object`, as treexml rejects such documents while parsing.
+    UnresolvableEntity {
+        entity: String,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// Repeated siblings convert to values of different JSON types and
+    /// `HeteroPolicy::Error` is set. `first` and `second` are the first two types found, e.g.
+    /// `"number"` and `"string"`.
+    InconsistentSiblingTypes {
+        first: &'static str,
+        second: &'static str,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// An attribute and a child element, or two attributes renamed by the namespace options,
+    /// are stored under the same key and `CollisionStrategy::Error` is set.
+    AttributeChildCollision {
+        key: String,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// Documents passed to `merge_documents` have root elements with different names and
+    /// `RootNames::RequireEqual` is set.
+    RootMismatch {
+        expected: String,
+        found: String,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// A reference attribute names an identifier that no element carries. Only raised in
+    /// strict mode.
+    DanglingReference {
+        id: String,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// Inlining a reference attribute would require inlining the same element inside itself.
+    ReferenceCycle {
+        id: String,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// Inlining a reference attribute would inline more values than
+    /// `IdReferences::max_inlined`.
+    InliningLimitExceeded {
+        id: String,
+        limit: usize,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// The input read by `qxml_node2object` is not well-formed XML or can't be read. `xml_path`
+    /// locates the innermost element still open, from the converted element down. Also raised
+    /// when `roxmltree_node2object` is given a node other than an element or a document.
+    Malformed {
+        message: String,
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+    /// The converter reached a state it considers impossible. This is a bug in node2object.
+    Internal {
+        path: JsonPointer,
+        xml_path: XmlPath,
+    },
+}
+
+impl ConversionError {
+    /// Location in the converted output where the error occurred.
+    pub fn path(&self) -> &JsonPointer {
+        match self {
+            ConversionError::InvalidBase64 { path, .. }
+            | ConversionError::AmbiguousXmlLang { path, .. }
+            | ConversionError::UnsupportedOption { path, .. }
+            | ConversionError::ContextConflict { path, .. }
+            | ConversionError::MergeConflict { path, .. }
+            | ConversionError::DepthExceeded { path, .. }
+            | ConversionError::NotFound { path, .. }
+            | ConversionError::ReservedKey { path, .. }
+            | ConversionError::UnresolvableEntity { path, .. }
+            | ConversionError::InconsistentSiblingTypes { path, .. }
+            | ConversionError::AttributeChildCollision { path, .. }
+            | ConversionError::RootMismatch { path, .. }
+            | ConversionError::DanglingReference { path, .. }
+            | ConversionError::ReferenceCycle { path, .. }
+            | ConversionError::InliningLimitExceeded { path, .. }
+            | ConversionError::Malformed { path, .. }
+            | ConversionError::Internal { path, .. } => path,
+        }
+    }
+
+    /// Location in the source document where the error occurred.
+    pub fn xml_path(&self) -> &XmlPath {
+        match self {
+            ConversionError::InvalidBase64 { xml_path, .. }
+            | ConversionError::AmbiguousXmlLang { xml_path, .. }
+            | ConversionError::UnsupportedOption { xml_path, .. }
+            | ConversionError::ContextConflict { xml_path, .. }
+            | ConversionError::MergeConflict { xml_path, .. }
+            | ConversionError::DepthExceeded { xml_path, .. }
+            | ConversionError::NotFound { xml_path, .. }
+            | ConversionError::ReservedKey { xml_path, .. }
+            | ConversionError::UnresolvableEntity { xml_path, .. }
+            | ConversionError::InconsistentSiblingTypes { xml_path, .. }
+            | ConversionError::AttributeChildCollision { xml_path, .. }
+            | ConversionError::RootMismatch { xml_path, .. }
+            | ConversionError::DanglingReference { xml_path, .. }
+            | ConversionError::ReferenceCycle { xml_path, .. }
+            | ConversionError::InliningLimitExceeded { xml_path, .. }
+            | ConversionError::Malformed { xml_path, .. }
+            | ConversionError::Internal { xml_path, .. } => xml_path,
+        }
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConversionError::InvalidBase64 { xml_path, .. } => {
+                write!(f, "invalid base64 payload at {}", xml_path)
+            }
+            ConversionError::AmbiguousXmlLang { xml_path, .. } => {
+                write!(f, "siblings at {} can't be grouped by xml:lang", xml_path)
+            }
+            ConversionError::UnsupportedOption {
+                option, xml_path, ..
+            } => {
+                write!(
+                    f,
+                    "option {} is not supported by this backend (at {})",
+                    option, xml_path
+                )
+            }
+            ConversionError::ContextConflict { key, .. } => {
+                write!(f, "context key {} conflicts with converted data", key)
+            }
+            ConversionError::MergeConflict { path, xml_path } => {
+                write!(
+                    f,
+                    "merged elements at {} disagree on the value at {}",
+                    xml_path, path
+                )
+            }
+            ConversionError::DepthExceeded { xml_path, .. } => {
+                write!(f, "element at {} is nested too deeply", xml_path)
+            }
+            ConversionError::NotFound {
+                segment, xml_path, ..
+            } => {
+                write!(f, "no element matches {:?} below {}", segment, xml_path)
+            }
+            ConversionError::ReservedKey { key, xml_path, .. } => {
+                write!(f, "key {} of {} is reserved", key, xml_path)
+            }
+            ConversionError::UnresolvableEntity {
+                entity, xml_path, ..
+            } => {
+                write!(f, "entity &{}; at {} can't be resolved", entity, xml_path)
+            }
+            ConversionError::InconsistentSiblingTypes {
+                first,
+                second,
+                xml_path,
+                ..
+            } => {
+                write!(
+                    f,
+                    "siblings at {} mix values of type {} and {}",
+                    xml_path, first, second
+                )
+            }
+            ConversionError::AttributeChildCollision { key, xml_path, .. } => {
+                write!(
+                    f,
+                    "attributes or child elements of {} share the key {}",
+                    xml_path, key
+                )
+            }
+            ConversionError::RootMismatch {
+                expected, found, ..
+            } => {
+                write!(
+                    f,
+                    "root element {} doesn't match root element {} of the first document",
+                    found, expected
+                )
+            }
+            ConversionError::DanglingReference { id, path, .. } => {
+                write!(f, "reference at {} to unknown identifier {:?}", path, id)
+            }
+            ConversionError::ReferenceCycle { id, path, .. } => {
+                write!(
+                    f,
+                    "reference at {} to {:?} can't be inlined into itself",
+                    path, id
+                )
+            }
+            ConversionError::InliningLimitExceeded {
+                id, limit, path, ..
+            } => {
+                write!(
+                    f,
+                    "reference at {} to {:?} would inline more than {} values",
+                    path, id, limit
+                )
+            }
+            ConversionError::Malformed {
+                message, xml_path, ..
+            } => {
+                write!(f, "malformed XML in {}: {}", xml_path, message)
+            }
+            ConversionError::Internal { xml_path, .. } => {
+                write!(f, "internal error while converting {}", xml_path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Errors that can occur while converting JSON values back into XML elements.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReverseError {
+    /// A key can't be used as an element or attribute name.
+    InvalidName { name: String, path: JsonPointer },
+    /// An `@`-prefixed key holds an object or array.
+    NonScalarAttribute { path: JsonPointer },
+    /// A `#text` key holds an object or array.
+    NonScalarText { path: JsonPointer },
+    /// An array directly contains another array, or is passed where a single element is needed.
+    /// Only raised by `value_to_element`.
+    NestedArray { path: JsonPointer },
+    /// The input of `element_from` or `struct2node` can't be serialized, e.g. a map with keys
+    /// that aren't strings or scalars.
+    Serialize { message: String, path: JsonPointer },
+}
+
+impl ReverseError {
+    /// Location in the input value where the error occurred.
+    pub fn path(&self) -> &JsonPointer {
+        match self {
+            ReverseError::InvalidName { path, .. }
+            | ReverseError::NonScalarAttribute { path }
+            | ReverseError::NonScalarText { path }
+            | ReverseError::NestedArray { path }
+            | ReverseError::Serialize { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for ReverseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReverseError::InvalidName { name, path } => {
+                write!(f, "{:?} is not a valid XML name (at {})", name, path)
+            }
+            ReverseError::NonScalarAttribute { path } => {
+                write!(f, "attribute value at {} is not a scalar", path)
+            }
+            ReverseError::NonScalarText { path } => {
+                write!(f, "text value at {} is not a scalar", path)
+            }
+            ReverseError::NestedArray { path } => {
+                write!(f, "array at {} can't be represented as an element", path)
+            }
+            ReverseError::Serialize { message, .. } => {
+                write!(f, "value can't be serialized: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReverseError {}
+
+/// Errors that can occur while reading an XML document and converting its root element.
+#[derive(Debug)]
+pub enum ReadError {
+    /// Reading the input failed.
+    Io(std::io::Error),
+    /// The input is not well-formed XML. `position` is the byte offset where the problem was
+    /// found, if the parser reports it.
+    Parse {
+        message: String,
+        position: Option<u64>,
+    },
+    /// The document declares an encoding that isn't supported.
+    UnknownEncoding(String),
+    /// The input contains byte sequences that are invalid in its encoding.
+    Malformed { encoding: &'static str },
+    /// The document has no root element.
+    NoRoot,
+    /// The root element can't be converted.
+    Conversion(ConversionError),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "can't read XML input: {}", e),
+            ReadError::Parse {
+                message,
+                position: Some(position),
+            } => {
+                write!(f, "malformed XML at byte {}: {}", position, message)
+            }
+            ReadError::Parse { message, .. } => write!(f, "malformed XML: {}", message),
+            ReadError::UnknownEncoding(encoding) => {
+                write!(f, "XML encoding {:?} is not supported", encoding)
+            }
+            ReadError::Malformed { encoding } => {
+                write!(f, "XML input is not valid {}", encoding)
+            }
+            ReadError::NoRoot => f.write_str("XML document has no root element"),
+            ReadError::Conversion(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReadError::Io(e) => Some(e),
+            ReadError::Conversion(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Errors that can occur in `fragment_to_array`.
+#[derive(Debug)]
+pub enum FragmentError {
+    /// The fragment is not well-formed XML.
+    Parse(treexml::Error),
+    /// One of the top-level elements can't be converted.
+    Conversion(ConversionError),
+}
+
+impl fmt::Display for FragmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FragmentError::Parse(e) => write!(f, "malformed XML fragment: {}", e),
+            FragmentError::Conversion(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FragmentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FragmentError::Parse(_) => None,
+            FragmentError::Conversion(e) => Some(e),
+        }
+    }
+}
+
+/// Errors that can occur in `xmlrs_document_to_value`.
+#[cfg(feature = "xml-rs")]
+#[derive(Debug)]
+pub enum XmlRsConversionError {
+    /// The input is not well-formed XML or can't be read.
+    Xml(xml::reader::Error),
+    /// The root element or one of its descendants can't be converted.
+    Conversion(ConversionError),
+}
+
+#[cfg(feature = "xml-rs")]
+impl fmt::Display for XmlRsConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            XmlRsConversionError::Xml(e) => write!(f, "malformed XML: {}", e),
+            XmlRsConversionError::Conversion(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "xml-rs")]
+impl std::error::Error for XmlRsConversionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            XmlRsConversionError::Xml(e) => Some(e),
+            XmlRsConversionError::Conversion(e) => Some(e),
+        }
+    }
+}
@@ -0,0 +1,436 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    annotations::{annotate, check_options, Annotations, Recorded},
+    convert_attributes, convert_child, convert_node_aux, convert_root_with_stats, count_attributes,
+    count_element, element_key, ensured_array_keys, insert_text_contents, is_collapsed_wrapper,
+    namespaces::{self, ElementUris},
+    reshapes_output, root_object, scan_xml_node, tag_namespace, tag_source, AttrPosition, Context,
+    ConversionConfig, ConversionError, ConversionStats, HeteroPolicy, Placement, SiblingGroups,
+    XMLNodeType,
+};
+
+/// Receiver of the JSON produced by a conversion, as a stream of events. `key` is the object
+/// key a value is stored under, or `None` for array items and the root object.
+pub trait JsonEventSink {
+    /// An object starts. Its entries follow, up to the matching `end_object`.
+    fn start_object(&mut self, key: Option<&str>);
+    /// The innermost open object ends.
+    fn end_object(&mut self);
+    /// An array starts. Its items follow, up to the matching `end_array`.
+    fn start_array(&mut self, key: Option<&str>);
+    /// The innermost open array ends.
+    fn end_array(&mut self);
+    /// A null, boolean, number or string.
+    fn scalar(&mut self, key: Option<&str>, value: &Value);
+    /// A complete value, for the parts of the output that are converted at once, such as
+    /// text-only elements. Reported as the events describing it unless the sink can take the
+    /// value as it is.
+    fn value(&mut self, key: Option<&str>, value: Value) {
+        emit(key, &value, self);
+    }
+}
+
+/// Sink rebuilding the `Value` described by the events it receives.
+#[derive(Clone, Debug, Default)]
+pub struct ValueSink {
+    open: Vec<(Option<String>, Value)>,
+    root: Option<Value>,
+}
+
+impl ValueSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores a finished value in the innermost open container, or makes it the root.
+    fn add(&mut self, key: Option<&str>, value: Value) {
+        match self.open.last_mut() {
+            Some((_, Value::Object(object))) => {
+                object.insert(key.unwrap_or_default().to_string(), value);
+            }
+            Some((_, Value::Array(items))) => items.push(value),
+            _ => self.root = Some(value),
+        }
+    }
+
+    fn end(&mut self) {
+        if let Some((key, value)) = self.open.pop() {
+            self.add(key.as_deref(), value);
+        }
+    }
+
+    /// Returns the value built so far, or `None` if no complete value was received.
+    pub fn finish(self) -> Option<Value> {
+        self.root
+    }
+}
+
+impl JsonEventSink for ValueSink {
+    fn start_object(&mut self, key: Option<&str>) {
+        self.open
+            .push((key.map(str::to_string), Value::Object(Map::new())));
+    }
+
+    fn end_object(&mut self) {
+        self.end();
+    }
+
+    fn start_array(&mut self, key: Option<&str>) {
+        self.open
+            .push((key.map(str::to_string), Value::Array(Vec::new())));
+    }
+
+    fn end_array(&mut self) {
+        self.end();
+    }
+
+    fn scalar(&mut self, key: Option<&str>, value: &Value) {
+        self.add(key, value.clone());
+    }
+
+    fn value(&mut self, key: Option<&str>, value: Value) {
+        self.add(key, value);
+    }
+}
+
+/// Sends the events describing `value`, stored under `key`, to `sink`.
+fn emit<S: JsonEventSink + ?Sized>(key: Option<&str>, value: &Value, sink: &mut S) {
+    match value {
+        Value::Object(object) => {
+            sink.start_object(key);
+            for (k, v) in object {
+                emit(Some(k), v, sink);
+            }
+            sink.end_object();
+        }
+        Value::Array(items) => {
+            sink.start_array(key);
+            for v in items {
+                emit(None, v, sink);
+            }
+            sink.end_array();
+        }
+        v => sink.scalar(key, v),
+    }
+}
+
+/// Element whose object is reported entry by entry, as prepared by `enter`.
+struct Streamed<'a> {
+    e: &'a treexml::Element,
+    kind: XMLNodeType,
+    groups: SiblingGroups<'a>,
+    attributes: Map<String, Value>,
+    notes: Option<&'a Annotations>,
+    uris: Option<&'a ElementUris>,
+}
+
+/// Checks whether `e`, to be converted below the elements of `ctx`, converts to an object
+/// whatever its children convert to.
+fn is_object(e: &treexml::Element, ctx: &Context) -> bool {
+    matches!(
+        scan_xml_node(e, ctx.cfg),
+        XMLNodeType::Parent | XMLNodeType::SemiStructured
+    ) && !is_collapsed_wrapper(e, ctx.cfg)
+        && ctx.cfg.max_depth.is_none_or(|max| ctx.path.len() < max)
+}
+
+/// Starts converting `e` like `convert_node_aux` does, pushing it on `ctx`, if its object can
+/// be reported entry by entry: its children must be stored one value per child under their
+/// own keys, away from the keys of its attributes. Returns `None` otherwise, leaving `ctx`
+/// untouched.
+fn enter<'a>(
+    e: &'a treexml::Element,
+    ctx: &mut Context<'a>,
+) -> Result<Option<Streamed<'a>>, ConversionError> {
+    let cfg = ctx.cfg;
+    if !is_object(e, ctx)
+        || cfg.heterogeneous_arrays != HeteroPolicy::Allow
+        || cfg.attribute_position == AttrPosition::Sorted
+    {
+        return Ok(None);
+    }
+    ctx.path.push(e);
+    let (attributes, kind, count) = convert_attributes(e, None, ctx)?;
+    let groups = SiblingGroups::new(ctx)?;
+    let collides = e
+        .children
+        .iter()
+        .any(|c| attributes.contains_key(element_key(c, cfg).as_ref()));
+    if !groups.all_plain() || collides {
+        ctx.path.pop();
+        return Ok(None);
+    }
+    count_element(e, ctx);
+    count_attributes(&attributes, count, ctx);
+    Ok(Some(Streamed {
+        e,
+        kind,
+        groups,
+        attributes,
+        notes: ctx.annotations.take(),
+        uris: ctx.namespaces.take(),
+    }))
+}
+
+/// Reports the entries of the object of an element prepared by `enter`, in the order
+/// `convert_node_aux` inserts them, and pops it from `ctx`.
+fn emit_entries<'a, S: JsonEventSink + ?Sized>(
+    streamed: Streamed<'a>,
+    ctx: &mut Context<'a>,
+    sink: &mut S,
+) -> Result<(), ConversionError> {
+    let Streamed {
+        e,
+        kind,
+        mut groups,
+        attributes,
+        notes,
+        uris,
+    } = streamed;
+    #[cfg(feature = "tracing")]
+    let _span =
+        tracing::trace_span!("convert_node", element = %e.name, node_type = ?kind).entered();
+    let cfg = ctx.cfg;
+    let ensured = ensured_array_keys(e, ctx);
+    let mut present = match ensured.is_empty() {
+        true => Vec::new(),
+        false => attributes.keys().cloned().collect(),
+    };
+    let attributes_last = cfg.attribute_position == AttrPosition::Last;
+    let mut attributes = Some(attributes);
+    if !attributes_last {
+        for (k, v) in attributes.take().into_iter().flatten() {
+            sink.value(Some(&k), v);
+        }
+    }
+
+    let mut order = Vec::new();
+    let mut members = HashMap::<Cow<str>, Vec<_>>::new();
+    for (i, c) in e.children.iter().enumerate() {
+        let key = element_key(c, cfg);
+        members
+            .entry(key.clone())
+            .or_insert_with(|| {
+                order.push(key);
+                Vec::new()
+            })
+            .push((i, c));
+    }
+    for key in &order {
+        let members = &members[key];
+        if emit_group(key, members, &mut groups, notes, uris, ctx, sink)? && !ensured.is_empty() {
+            present.push(key.to_string());
+        }
+    }
+
+    let mut rest = Map::new();
+    groups.finish(&mut rest, ctx)?;
+    if kind == XMLNodeType::SemiStructured {
+        insert_text_contents(&mut rest, e, kind, ctx)?;
+    }
+    rest.extend(attributes.into_iter().flatten());
+    for (k, v) in rest {
+        sink.value(Some(&k), v);
+    }
+    for key in ensured {
+        if !present.contains(&key) {
+            sink.start_array(Some(&key));
+            sink.end_array();
+            ctx.stats.get_mut().array_count += 1;
+            present.push(key);
+        }
+    }
+    ctx.path.pop();
+
+    let tagged = tag_namespace(Some(Value::Object(Map::new())), e, uris, cfg);
+    if let Some(Value::Object(tags)) = tag_source(annotate(tagged, notes, cfg), e, cfg) {
+        for (k, v) in tags {
+            sink.value(Some(&k), v);
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether the child of the element on top of `ctx` at index `i` converts to an object
+/// that can be reported as it is converted.
+fn is_item(groups: &SiblingGroups, i: usize, c: &treexml::Element, ctx: &Context) -> bool {
+    !groups.is_skipped(i) && is_object(c, ctx)
+}
+
+/// Reports the children of the element on top of `ctx` stored under `key`, given with their
+/// indices as `members`, placing them with `SiblingGroups::place` like `SiblingGroups::insert`
+/// does. Objects are reported as they are converted once the group is known to be an array.
+/// Returns `false` if none of the children converts to a value.
+fn emit_group<'a, S: JsonEventSink + ?Sized>(
+    key: &str,
+    members: &[(usize, &'a treexml::Element)],
+    groups: &mut SiblingGroups<'a>,
+    notes: Option<&'a Annotations>,
+    uris: Option<&'a ElementUris>,
+    ctx: &mut Context<'a>,
+    sink: &mut S,
+) -> Result<bool, ConversionError> {
+    // Value held back until it is known whether the group is an array.
+    let mut single = None;
+    let mut opened = false;
+    for (j, &(i, c)) in members.iter().enumerate() {
+        ctx.annotations = notes.and_then(|n| n.children.get(i));
+        ctx.namespaces = uris.and_then(|u| u.children.get(i));
+        let item = is_item(groups, i, c, ctx);
+        let value = match item {
+            true => None,
+            false => match convert_child(i, c, groups, ctx)? {
+                Some(v) => Some(v),
+                None => continue,
+            },
+        };
+        let repeated = item
+            && members[j + 1..]
+                .iter()
+                .any(|&(i, c)| is_item(groups, i, c, ctx));
+        match groups.place(key, repeated, ctx) {
+            Placement::Single => {
+                single = match value {
+                    Some(v) => Some(v),
+                    None => convert_child(i, c, groups, ctx)?,
+                };
+                continue;
+            }
+            Placement::Start => sink.start_array(Some(key)),
+            Placement::Promote => {
+                sink.start_array(Some(key));
+                if let Some(v) = single.take() {
+                    sink.value(None, v);
+                }
+            }
+            Placement::Append => {}
+        }
+        opened = true;
+        if let Some(v) = value {
+            sink.value(None, v);
+            continue;
+        }
+        match enter(c, ctx)? {
+            Some(streamed) => {
+                sink.start_object(None);
+                emit_entries(streamed, ctx, sink)?;
+                sink.end_object();
+            }
+            None => {
+                if let Some(v) = convert_child(i, c, groups, ctx)? {
+                    sink.value(None, v);
+                }
+            }
+        }
+    }
+    ctx.annotations = None;
+    ctx.namespaces = None;
+
+    if opened {
+        sink.end_array();
+        return Ok(true);
+    }
+    match single {
+        Some(v) => {
+            sink.value(Some(key), v);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Reports the entries of the object that `root_object` makes of the root `e`.
+fn emit_root_entries<'a, S: JsonEventSink + ?Sized>(
+    e: &'a treexml::Element,
+    ctx: &mut Context<'a>,
+    sink: &mut S,
+) -> Result<(), ConversionError> {
+    let cfg = ctx.cfg;
+    match enter(e, ctx)? {
+        Some(streamed) => {
+            // The source tag is already there, as the root converts to an object.
+            emit_entries(streamed, ctx, sink)?;
+            if let Some(key) = &cfg.root_element_name_key {
+                sink.scalar(Some(key), &Value::String(e.name.clone()));
+            }
+        }
+        None => {
+            let value = convert_node_aux(e, ctx)?;
+            for (k, v) in root_object(e, value, cfg) {
+                sink.value(Some(&k), v);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Same as `drive` for a tree read by a backend that kept what `recorded` lists of the source
+/// document, in `notes` for what treexml has no place for. Also returns the counters collected
+/// along the way.
+pub(crate) fn drive_recorded<S: JsonEventSink + ?Sized>(
+    e: &treexml::Element,
+    notes: Option<&Annotations>,
+    recorded: Recorded,
+    sink: &mut S,
+    cfg: &ConversionConfig,
+) -> Result<ConversionStats, ConversionError> {
+    check_options(cfg, recorded)?;
+    let resolved = namespaces::resolve_namespaces(e, cfg)?;
+    if reshapes_output(cfg) {
+        let (data, stats) = convert_root_with_stats(&resolved, notes, cfg)?;
+        sink.start_object(None);
+        for (k, v) in data {
+            sink.value(Some(&k), v);
+        }
+        sink.end_object();
+        return Ok(stats);
+    }
+
+    let root = &*resolved.root;
+    let mut ctx = Context::new(cfg);
+    ctx.namespaces = Some(&resolved.uris);
+    ctx.annotations = notes;
+    let key = element_key(root, cfg);
+    sink.start_object(None);
+    match (cfg.include_root, &cfg.root_element_name_key) {
+        (true, None) => match enter(root, &mut ctx)? {
+            Some(streamed) => {
+                sink.start_object(Some(&key));
+                emit_entries(streamed, &mut ctx, sink)?;
+                sink.end_object();
+            }
+            None => {
+                let value = convert_node_aux(root, &mut ctx)?;
+                sink.value(Some(&key), value.unwrap_or(Value::Null));
+            }
+        },
+        (true, Some(_)) => {
+            sink.start_object(Some(&key));
+            emit_root_entries(root, &mut ctx, sink)?;
+            sink.end_object();
+        }
+        (false, _) => emit_root_entries(root, &mut ctx, sink)?,
+    }
+    sink.end_object();
+    Ok(ctx.stats.into_inner())
+}
+
+/// Converts `e` like `node2object_with_config` and reports the result to `sink` along the
+/// way: the objects of elements whose children are stored one value per child, which is the
+/// case unless options such as `merge_siblings` or `heterogeneous_arrays` regroup them, are
+/// reported entry by entry, and arrays of such elements item by item. Other elements are
+/// reported with `JsonEventSink::value` once converted, and so is the whole document when
+/// `sort_arrays_by`, `arrays_as_indexed_objects` or `id_references` reshape it.
+///
+/// `node2object_with_config` itself collects the events with a `ValueSink`. On error, the
+/// events sent so far describe an unfinished object.
+pub fn drive<S: JsonEventSink + ?Sized>(
+    e: &treexml::Element,
+    sink: &mut S,
+    cfg: &ConversionConfig,
+) -> Result<(), ConversionError> {
+    drive_recorded(e, None, Recorded::default(), sink, cfg).map(|_| ())
+}
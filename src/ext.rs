@@ -0,0 +1,39 @@
+use serde_json::{Map, Value};
+
+use crate::{node2object, node2object_with_config, ConversionConfig, ConversionError};
+
+/// Conversion methods on `treexml::Element`, as an alternative to calling `node2object` and
+/// friends directly.
+pub trait Node2ObjectExt {
+    /// Same as `node2object(self)`.
+    fn to_json_map(&self) -> Map<String, Value>;
+
+    /// Same as `to_json_map`, wrapped in `Value::Object`.
+    fn to_json_value(&self) -> Value {
+        Value::Object(self.to_json_map())
+    }
+
+    /// Same as `to_json_value`, serialized as compact JSON.
+    fn to_json_string(&self) -> String {
+        self.to_json_value().to_string()
+    }
+
+    /// Same as `node2object_with_config(self, cfg)`.
+    fn to_json_map_with_config(
+        &self,
+        cfg: &ConversionConfig,
+    ) -> Result<Map<String, Value>, ConversionError>;
+}
+
+impl Node2ObjectExt for treexml::Element {
+    fn to_json_map(&self) -> Map<String, Value> {
+        node2object(self)
+    }
+
+    fn to_json_map_with_config(
+        &self,
+        cfg: &ConversionConfig,
+    ) -> Result<Map<String, Value>, ConversionError> {
+        node2object_with_config(self, cfg)
+    }
+}
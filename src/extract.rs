@@ -0,0 +1,202 @@
+use serde_json::{Map, Value};
+
+use crate::{
+    annotations::{check_options, Recorded},
+    attribute_entries, convert_child, convert_node_aux, convert_root, element_key,
+    is_collapsed_wrapper,
+    namespaces::{self, ElementUris},
+    node_attributes, produces_value, reshapes_output, scan_xml_node, Context, ConversionConfig,
+    ConversionError, HeteroPolicy, NodeContext, SiblingGroups, XMLNodeType,
+};
+
+/// Outcome of walking down the element tree along a pointer.
+enum Located {
+    /// The value addressed by the pointer, if any.
+    Found(Option<Value>),
+    /// The root itself would have to be converted, so the whole document is.
+    AtRoot,
+}
+
+/// Reference tokens of a JSON pointer, or `None` if `pointer` is not one.
+fn reference_tokens(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    let tokens = pointer.strip_prefix('/')?.split('/');
+    Some(
+        tokens
+            .map(|t| t.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
+
+/// Array index written in `token`, following the same rules as `Value::pointer`.
+fn index(token: &str) -> Option<usize> {
+    if token.starts_with('+') || (token.starts_with('0') && token.len() != 1) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// Part of `v` addressed by `tokens`.
+fn lookup(mut v: Value, tokens: &[String]) -> Option<Value> {
+    for token in tokens {
+        v = match v {
+            Value::Object(mut data) => data.remove(token)?,
+            Value::Array(mut items) => {
+                let i = index(token).filter(|i| *i < items.len())?;
+                items.swap_remove(i)
+            }
+            _ => return None,
+        };
+    }
+    Some(v)
+}
+
+/// Walks down from the element on top of `ctx`, whose namespaces are `uris`, along `tokens`,
+/// converting only what the last token addresses. Steps that the grouping options make hard to
+/// follow are resolved by converting the element reached so far.
+fn locate<'a>(
+    ctx: &mut Context<'a>,
+    uris: Option<&'a ElementUris>,
+    mut tokens: &[String],
+) -> Result<Located, ConversionError> {
+    let cfg = ctx.cfg;
+    // Namespaces of the elements on the path of `ctx`.
+    let mut scopes = vec![uris];
+    loop {
+        let e = *ctx.path.last().expect("the root is on the path");
+        let uris = *scopes.last().expect("the root is on the path");
+        let token = tokens[0].as_str();
+        let kind = scan_xml_node(e, cfg);
+        let walkable =
+            !is_collapsed_wrapper(e, cfg) && cfg.max_depth.is_none_or(|max| ctx.path.len() <= max);
+        let has_child = e.children.iter().any(|c| element_key(c, cfg) == token);
+
+        if walkable
+            && !has_child
+            && cfg.attributes_key.is_none()
+            && !matches!(kind, XMLNodeType::Text | XMLNodeType::Empty)
+        {
+            let node = NodeContext::new(&ctx.path[..ctx.path.len() - 1], e);
+            let mut data = attribute_entries(node_attributes(e, None, cfg), &node, cfg);
+            if let Some(v) = data.remove(token) {
+                return Ok(Located::Found(lookup(v, &tokens[1..])));
+            }
+        }
+
+        let parent = matches!(kind, XMLNodeType::Parent | XMLNodeType::SemiStructured);
+        if walkable && has_child && parent {
+            let groups = SiblingGroups::new(ctx)?;
+            if groups.is_plain(token) {
+                let members = e
+                    .children
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, c)| {
+                        element_key(c, cfg) == token
+                            && !groups.is_skipped(i)
+                            && produces_value(c, &ctx.path, cfg)
+                    })
+                    .collect::<Vec<_>>();
+                let vectorized = members.len() > 1 || groups.forced.contains(token);
+                let next = match (vectorized, tokens.get(1)) {
+                    (false, _) => members.first().map(|m| (*m, &tokens[1..])),
+                    (true, Some(i)) if cfg.heterogeneous_arrays == HeteroPolicy::Allow => {
+                        match index(i).and_then(|i| members.get(i)) {
+                            Some(m) => Some((*m, &tokens[2..])),
+                            None => return Ok(Located::Found(None)),
+                        }
+                    }
+                    (true, _) => {
+                        let v = convert_group(e, token, groups, uris, ctx)?;
+                        return Ok(Located::Found(v.and_then(|v| lookup(v, &tokens[1..]))));
+                    }
+                };
+                match next {
+                    Some(((i, c), [])) => {
+                        ctx.namespaces = uris.and_then(|u| u.children.get(i));
+                        return Ok(Located::Found(convert_child(i, c, &groups, ctx)?));
+                    }
+                    Some(((i, c), rest)) => {
+                        ctx.path.push(c);
+                        scopes.push(uris.and_then(|u| u.children.get(i)));
+                        tokens = rest;
+                        continue;
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        if ctx.path.len() == 1 {
+            return Ok(Located::AtRoot);
+        }
+        ctx.path.pop();
+        ctx.namespaces = uris;
+        let v = convert_node_aux(e, ctx)?;
+        return Ok(Located::Found(v.and_then(|v| lookup(v, tokens))));
+    }
+}
+
+/// Converts the children of `e`, the element on top of `ctx`, stored under `key`.
+fn convert_group<'a>(
+    e: &'a treexml::Element,
+    key: &str,
+    mut groups: SiblingGroups<'a>,
+    uris: Option<&'a ElementUris>,
+    ctx: &mut Context<'a>,
+) -> Result<Option<Value>, ConversionError> {
+    let cfg = ctx.cfg;
+    let mut data = Map::new();
+    let members = e.children.iter().enumerate();
+    for (i, c) in members.filter(|(_, c)| element_key(c, cfg) == key) {
+        ctx.namespaces = uris.and_then(|u| u.children.get(i));
+        if let Some(v) = convert_child(i, c, &groups, ctx)? {
+            groups.insert(&mut data, c, v, ctx)?;
+        }
+    }
+    groups.finish(&mut data, ctx)?;
+    Ok(data.remove(key))
+}
+
+/// Returns the value that `pointer`, a JSON pointer such as `/report/summary/total`, addresses
+/// in the output of `node2object_with_config`, without converting the rest of the document.
+/// The element tree is walked down along the pointer, numeric tokens choosing among repeated
+/// siblings, and only the addressed subtree is converted. Attributes are addressed by their
+/// keys, e.g. `/report/@id`. `Ok(None)` is returned if nothing is found there, including for
+/// out-of-range indices.
+///
+/// Options that regroup siblings or reshape the output, such as `merge_siblings` or
+/// `sort_arrays_by`, make more of the document convert, up to all of it. Errors are only
+/// reported for the parts that are converted.
+pub fn extract(
+    e: &treexml::Element,
+    pointer: &str,
+    cfg: &ConversionConfig,
+) -> Result<Option<Value>, ConversionError> {
+    let tokens = match reference_tokens(pointer) {
+        Some(tokens) => tokens,
+        None => return Ok(None),
+    };
+    check_options(cfg, Recorded::default())?;
+    let resolved = namespaces::resolve_namespaces(e, cfg)?;
+    let root = &*resolved.root;
+
+    let below_root = match (cfg.include_root, tokens.split_first()) {
+        _ if reshapes_output(cfg) => None,
+        (true, Some((first, _))) if *first != element_key(root, cfg) => return Ok(None),
+        (true, Some((_, rest))) => Some(rest),
+        (false, _) => Some(&tokens[..]),
+        (true, None) => None,
+    };
+    if let Some(rest) = below_root.filter(|rest| !rest.is_empty()) {
+        let mut ctx = Context::with_parent(cfg, root);
+        if let Located::Found(v) = locate(&mut ctx, Some(&resolved.uris), rest)? {
+            return Ok(v);
+        }
+    }
+
+    let data = convert_root(&resolved, cfg)?;
+    Ok(lookup(Value::Object(data), &tokens))
+}
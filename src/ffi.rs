@@ -0,0 +1,69 @@
+use std::{
+    convert::TryFrom,
+    os::raw::{c_char, c_int},
+    panic, slice,
+};
+
+use serde_json::Value;
+
+use crate::node2object;
+
+/// `xml` or `out` is a null pointer.
+pub const NODE2OBJECT_ERR_NULL_POINTER: c_int = -1;
+/// The input is not well-formed XML.
+pub const NODE2OBJECT_ERR_PARSE: c_int = -2;
+/// The input has no root element.
+pub const NODE2OBJECT_ERR_NO_ROOT: c_int = -3;
+/// `out` can't hold the JSON output and its terminating NUL.
+pub const NODE2OBJECT_ERR_BUFFER_TOO_SMALL: c_int = -4;
+/// The JSON output is longer than `INT_MAX` bytes.
+pub const NODE2OBJECT_ERR_TOO_LARGE: c_int = -5;
+/// The conversion panicked.
+pub const NODE2OBJECT_ERR_PANIC: c_int = -6;
+
+/// Parses the XML document in `xml` and converts its root element into JSON.
+fn convert(xml: &[u8]) -> Result<String, c_int> {
+    let root = treexml::Document::parse(xml)
+        .map_err(|_| NODE2OBJECT_ERR_PARSE)?
+        .root
+        .ok_or(NODE2OBJECT_ERR_NO_ROOT)?;
+    Ok(Value::Object(node2object(&root)).to_string())
+}
+
+/// Converts the XML document of `xml_len` bytes at `xml` into JSON, see `node2object`. The
+/// JSON is written to `out` followed by a NUL terminator, and its length without the
+/// terminator is returned. On failure nothing is written and one of the negative
+/// `NODE2OBJECT_ERR_*` codes is returned.
+///
+/// # Safety
+///
+/// `xml` must point to `xml_len` readable bytes and `out` to `out_cap` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn node2object_c(
+    xml: *const c_char,
+    xml_len: usize,
+    out: *mut c_char,
+    out_cap: usize,
+) -> c_int {
+    if xml.is_null() || out.is_null() {
+        return NODE2OBJECT_ERR_NULL_POINTER;
+    }
+    let input = slice::from_raw_parts(xml as *const u8, xml_len);
+    let json = match panic::catch_unwind(|| convert(input)) {
+        Ok(Ok(json)) => json,
+        Ok(Err(code)) => return code,
+        Err(_) => return NODE2OBJECT_ERR_PANIC,
+    };
+
+    let len = match c_int::try_from(json.len()) {
+        Ok(len) => len,
+        Err(_) => return NODE2OBJECT_ERR_TOO_LARGE,
+    };
+    if json.len() >= out_cap {
+        return NODE2OBJECT_ERR_BUFFER_TOO_SMALL;
+    }
+    let out = slice::from_raw_parts_mut(out as *mut u8, out_cap);
+    out[..json.len()].copy_from_slice(json.as_bytes());
+    out[json.len()] = 0;
+    len
+}
@@ -0,0 +1,137 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    annotations::{check_options, Recorded},
+    convert_root, element_key,
+    namespaces::resolve_namespaces,
+    ConversionConfig, ConversionError,
+};
+
+/// Options for `node2flat`.
+#[derive(Clone, Debug)]
+pub struct FlattenConfig {
+    /// Conversion options applied to the element.
+    pub conversion: ConversionConfig,
+    /// Separator placed between the keys of a path.
+    pub separator: String,
+}
+
+impl Default for FlattenConfig {
+    fn default() -> Self {
+        Self {
+            conversion: ConversionConfig::default(),
+            separator: ".".into(),
+        }
+    }
+}
+
+/// Pairs collected by `node2flat`, with the options it was called with.
+struct Flattener<'a> {
+    cfg: &'a FlattenConfig,
+    pairs: Vec<(String, Value)>,
+}
+
+/// Children of an element by the key they are stored under, with the position of the first
+/// of each.
+type ChildrenByKey<'e> = HashMap<Cow<'e, str>, (usize, Vec<&'e treexml::Element>)>;
+
+fn children_by_key<'e>(
+    e: Option<&'e treexml::Element>,
+    cfg: &ConversionConfig,
+) -> ChildrenByKey<'e> {
+    let mut children = ChildrenByKey::new();
+    for (i, c) in e.iter().flat_map(|e| e.children.iter().enumerate()) {
+        children
+            .entry(element_key(c, cfg))
+            .or_insert_with(|| (i, Vec::new()))
+            .1
+            .push(c);
+    }
+    children
+}
+
+/// Entries of an object in document order: keys that don't come from child elements
+/// (attributes, text) first, then keys in the order their first element appears.
+fn document_order<'m>(
+    data: &'m Map<String, Value>,
+    children: &ChildrenByKey,
+) -> Vec<(&'m String, &'m Value)> {
+    let mut entries: Vec<_> = data.iter().collect();
+    entries.sort_by_key(|(k, _)| children.get(k.as_str()).map(|(first, _)| *first));
+    entries
+}
+
+impl Flattener<'_> {
+    fn join(&self, path: &str, key: &str) -> String {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}{}{}", path, self.cfg.separator, key)
+        }
+    }
+
+    /// Flattens an entry of an object, given the children stored under its key. Array items
+    /// are matched with these children when their counts agree.
+    fn entry(&mut self, path: String, v: &Value, elements: &[&treexml::Element]) {
+        match v {
+            Value::Array(items) if !items.is_empty() => {
+                for (i, item) in items.iter().enumerate() {
+                    let e = if elements.len() == items.len() {
+                        Some(elements[i])
+                    } else {
+                        None
+                    };
+                    self.value(format!("{}[{}]", path, i), item, e);
+                }
+            }
+            v => self.value(path, v, elements.first().copied()),
+        }
+    }
+
+    /// Flattens `v`, converted from `e` if known. Empty objects and arrays are leaves.
+    fn value(&mut self, path: String, v: &Value, e: Option<&treexml::Element>) {
+        match v {
+            Value::Object(data) if !data.is_empty() => {
+                let children = children_by_key(e, &self.cfg.conversion);
+                for (k, v) in document_order(data, &children) {
+                    let elements = children.get(k.as_str()).map_or(&[][..], |(_, c)| c);
+                    self.entry(self.join(&path, k), v, elements);
+                }
+            }
+            Value::Array(items) if !items.is_empty() => {
+                for (i, item) in items.iter().enumerate() {
+                    self.value(format!("{}[{}]", path, i), item, None);
+                }
+            }
+            v => self.pairs.push((path, v.clone())),
+        }
+    }
+}
+
+/// Converts `e` and flattens the result into leaf paths and values, e.g.
+/// `population.entry[0].name = "Alex"`. Object keys are joined with `cfg.separator`, array items
+/// are addressed by index and attribute keys keep their prefix (`entry[1].@id`). Pairs follow
+/// document order, with the attributes and text of an element before its children.
+pub fn node2flat(
+    e: &treexml::Element,
+    cfg: &FlattenConfig,
+) -> Result<Vec<(String, Value)>, ConversionError> {
+    check_options(&cfg.conversion, Recorded::default())?;
+    let resolved = resolve_namespaces(e, &cfg.conversion)?;
+    let e = &*resolved.root;
+    let data = convert_root(&resolved, &cfg.conversion)?;
+    let mut flattener = Flattener {
+        cfg,
+        pairs: Vec::new(),
+    };
+    if cfg.conversion.include_root {
+        for (k, v) in &data {
+            flattener.value(k.clone(), v, Some(e));
+        }
+    } else {
+        flattener.value(String::new(), &Value::Object(data), Some(e));
+    }
+    Ok(flattener.pairs)
+}
@@ -0,0 +1,44 @@
+use serde_json::Value;
+
+use crate::{node2object_with_config, ConversionConfig, ConversionError};
+
+/// Accumulates converted elements into a JSON array, for pipelines that receive elements one at
+/// a time (e.g. from an event-based parser) instead of as one document.
+///
+/// Each pushed element is converted with `node2object_with_config` using the configuration
+/// given at construction time.
+#[derive(Clone, Debug, Default)]
+pub struct IncrementalConverter {
+    cfg: ConversionConfig,
+    items: Vec<Value>,
+}
+
+impl IncrementalConverter {
+    pub fn new(cfg: ConversionConfig) -> Self {
+        Self {
+            cfg,
+            items: Vec::new(),
+        }
+    }
+
+    /// Converts `e` and appends it to the array. On error, nothing is appended.
+    pub fn push(&mut self, e: &treexml::Element) -> Result<(), ConversionError> {
+        let item = node2object_with_config(e, &self.cfg)?;
+        self.items.push(Value::Object(item));
+        Ok(())
+    }
+
+    /// Number of elements converted so far.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the array of converted elements, in push order.
+    pub fn finish(self) -> Value {
+        Value::Array(self.items)
+    }
+}
@@ -0,0 +1,27 @@
+use serde_json::{Map, Value};
+
+/// Replaces every array within `v` by an object keyed by the position of each item, counted
+/// from `base`. Indices are zero-padded to the width of the largest one, so that the keys sort
+/// in array order.
+pub(crate) fn index_arrays(v: &mut Value, base: usize) {
+    match v {
+        Value::Array(items) => {
+            let width = (base + items.len().saturating_sub(1)).to_string().len();
+            let data: Map<String, Value> = items
+                .drain(..)
+                .enumerate()
+                .map(|(i, mut item)| {
+                    index_arrays(&mut item, base);
+                    (format!("{:0width$}", base + i, width = width), item)
+                })
+                .collect();
+            *v = Value::Object(data);
+        }
+        Value::Object(data) => {
+            for v in data.values_mut() {
+                index_arrays(v, base);
+            }
+        }
+        _ => {}
+    }
+}
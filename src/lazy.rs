@@ -0,0 +1,199 @@
+use std::{borrow::Cow, collections::HashSet};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    annotations::{check_options, Recorded},
+    attribute_entries, check_reserved_keys, convert_child, convert_node_aux, element_key,
+    ensured_arrays, escape_reserved, finish_value, is_collapsed_wrapper, node_attributes,
+    produces_value, renamed_array_key, root_object, scan_xml_node, Context, ConversionConfig,
+    ConversionError, NodeContext, SiblingGroups, XMLNodeType,
+};
+
+/// Iterator behind `node2object_lazy`.
+struct LazyEntries<'a> {
+    e: &'a treexml::Element,
+    cfg: &'a ConversionConfig,
+    attributes: serde_json::map::IntoIter,
+    next_child: usize,
+    emitted: HashSet<Cow<'a, str>>,
+    /// Entries of non-`Parent` roots, which are converted eagerly.
+    fallback: Option<std::vec::IntoIter<Result<(String, Value), ConversionError>>>,
+}
+
+impl<'a> LazyEntries<'a> {
+    /// Converts the not yet visited children stored under `key`.
+    fn convert_group(&mut self, key: &str) -> Result<Option<(String, Value)>, ConversionError> {
+        let mut ctx = Context::with_parent(self.cfg, self.e);
+
+        let mut data = Map::new();
+        let mut groups = SiblingGroups::new(&ctx)?;
+        for (i, c) in self.e.children.iter().enumerate().skip(self.next_child) {
+            if element_key(c, self.cfg) == key {
+                if let Some(v) = convert_child(i, c, &groups, &mut ctx)? {
+                    groups.insert(&mut data, c, v, &ctx)?;
+                }
+            }
+        }
+        groups.finish(&mut data, &ctx)?;
+        if let Some(key) = &self.cfg.truncated_count_key {
+            data.remove(key);
+        }
+
+        Ok(data.into_iter().next())
+    }
+}
+
+impl<'a> Iterator for LazyEntries<'a> {
+    type Item = Result<(String, Value), ConversionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(fallback) = &mut self.fallback {
+            return fallback.next();
+        }
+
+        if let Some(entry) = self.attributes.next() {
+            return Some(Ok(entry));
+        }
+
+        while let Some(c) = self.e.children.get(self.next_child) {
+            let key = element_key(c, self.cfg);
+            if !self.emitted.contains(&key) {
+                self.emitted.insert(key.clone());
+                match self.convert_group(&key) {
+                    Ok(Some(entry)) => {
+                        self.next_child += 1;
+                        return Some(Ok(entry));
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        // Fuse the iterator after an error.
+                        self.next_child = self.e.children.len();
+                        return Some(Err(e));
+                    }
+                }
+            }
+            self.next_child += 1;
+        }
+
+        None
+    }
+}
+
+/// Lazily converts the root element, yielding its top-level key-value pairs one at a time:
+/// attributes first, then children in order of first occurrence. A child is only converted
+/// once the iterator reaches it; repeated children are converted together when the first of
+/// them is reached and yielded as a single array entry.
+///
+/// Collecting the iterator into a `Map` gives the same object as `node2object_with_config`
+/// produces with `include_root` disabled. Roots that are not parents of other elements are
+/// converted eagerly. Empty arrays required by `ensure_array_keys` come last.
+pub fn node2object_lazy<'a>(
+    e: &'a treexml::Element,
+    cfg: &'a ConversionConfig,
+) -> impl Iterator<Item = Result<(String, Value), ConversionError>> + 'a {
+    let checked = check_options(cfg, Recorded::default()).and_then(|()| {
+        check_reserved_keys(
+            &node_attributes(e, None, cfg),
+            &Context::with_parent(cfg, e),
+        )
+    });
+    let fallback = if let Err(err) = checked {
+        Some(vec![Err(err)].into_iter())
+    } else if scan_xml_node(e, cfg) == XMLNodeType::Parent && !is_collapsed_wrapper(e, cfg) {
+        None
+    } else {
+        let entries = match convert_node_aux(e, &mut Context::new(cfg)) {
+            Ok(value) => root_object(e, value, cfg).into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(err)],
+        };
+        Some(entries.into_iter())
+    };
+
+    let tag = match (&fallback, &cfg.root_element_name_key) {
+        (None, Some(key)) => Some(Ok((key.clone(), Value::String(e.name.clone())))),
+        _ => None,
+    };
+    let source_tag = match (&fallback, cfg.inject_source_tag) {
+        (None, true) => Some(Ok((
+            cfg.source_tag_key.clone(),
+            Value::String(e.name.clone()),
+        ))),
+        _ => None,
+    };
+
+    let mut ensured = Vec::new();
+    if fallback.is_none() {
+        for name in ensured_arrays(&[], e, cfg) {
+            let key = escape_reserved(Cow::Borrowed(name), cfg);
+            let present = e
+                .children
+                .iter()
+                .any(|c| element_key(c, cfg) == key && produces_value(c, &[e], cfg));
+            let key = renamed_array_key(e, &key, &[e], cfg).unwrap_or(&key);
+            if !present && !ensured.iter().any(|(k, _)| k == key) {
+                ensured.push((key.to_string(), Value::Array(Vec::new())));
+            }
+        }
+    }
+
+    tag.into_iter()
+        .chain(source_tag)
+        .chain(LazyEntries {
+            e,
+            cfg,
+            attributes: attribute_entries(
+                node_attributes(e, None, cfg),
+                &NodeContext::new(&[], e),
+                cfg,
+            )
+            .into_iter(),
+            next_child: 0,
+            emitted: HashSet::new(),
+            fallback,
+        })
+        .chain(ensured.into_iter().map(Ok))
+        .map(move |entry| {
+            entry.map(|(k, mut v)| {
+                finish_value(&mut v, cfg);
+                (k, v)
+            })
+        })
+}
+
+/// Lazily converts the direct children of `e` one at a time, yielding each child's name and
+/// value in document order. Unlike `node2object_lazy`, repeated children are yielded
+/// individually rather than grouped into arrays, so options acting on whole groups such as
+/// `harmonize_sibling_types` don't apply, and children that would be left out of the output
+/// (such as empty elements) are skipped. The iterator stops after the first error.
+pub fn iter_converted_children<'a>(
+    e: &'a treexml::Element,
+    cfg: &'a ConversionConfig,
+) -> impl Iterator<Item = Result<(&'a str, Value), ConversionError>> + 'a {
+    let mut ctx = Context::with_parent(cfg, e);
+    let checked = check_options(cfg, Recorded::default());
+    let (groups, error) = match checked.and_then(|()| SiblingGroups::new(&ctx)) {
+        Ok(groups) => (Some(groups), None),
+        Err(err) => (None, Some(err)),
+    };
+    let mut children = e.children.iter().enumerate();
+
+    let converted = std::iter::from_fn(move || {
+        let groups = groups.as_ref()?;
+        for (i, c) in children.by_ref() {
+            match convert_child(i, c, groups, &mut ctx) {
+                Ok(Some(mut v)) => {
+                    finish_value(&mut v, cfg);
+                    return Some(Ok((c.name.as_str(), v)));
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    children = [].iter().enumerate();
+                    return Some(Err(err));
+                }
+            }
+        }
+        None
+    });
+    error.map(Err).into_iter().chain(converted)
+}
@@ -28,28 +28,584 @@
 //!     }
 //! ));
 //! ```
+//!
+//! ## Tracing
+//! With the `tracing` feature enabled, every converted element is wrapped in a `convert_node`
+//! [tracing](https://github.com/tokio-rs/tracing) span recording the element name and its
+//! node type. Without the feature the spans are compiled out entirely. Any subscriber can
+//! capture them, e.g. with `tracing-subscriber`:
+//! ```ignore
+//! tracing_subscriber::fmt()
+//!     .with_max_level(tracing::Level::TRACE)
+//!     .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+//!     .init();
+//!
+//! let object = node2object::node2object(&dom_root);
+//! ```
+
+mod analyze;
+mod annotations;
+#[cfg(feature = "tokio")]
+mod async_reader;
+mod builder;
+mod config;
+#[cfg(feature = "dates")]
+mod dates;
+#[cfg(feature = "dot")]
+mod dot;
+#[cfg(feature = "encoding")]
+mod encoding;
+mod error;
+mod events;
+mod ext;
+mod extract;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod flat;
+mod incremental;
+mod indexed;
+mod lazy;
+#[cfg(feature = "minidom")]
+mod mdom;
+mod merge;
+mod namespaces;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "quick-xml")]
+mod pull;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "quick-xml")]
+mod qxml;
+mod references;
+mod reverse;
+#[cfg(feature = "roxmltree")]
+mod roxml;
+mod schema;
+mod ser;
+#[cfg(feature = "simd")]
+pub mod simd;
+mod sort;
+mod stats;
+#[cfg(feature = "streaming")]
+mod streaming;
+#[cfg(feature = "markdown")]
+mod table;
+mod text_parser;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "xml-rs")]
+mod xmlrs;
 
+pub use crate::analyze::{analyze, Finding, FindingCategory};
+#[cfg(feature = "tokio")]
+pub use crate::async_reader::convert_async_reader;
+#[cfg(feature = "simd-json")]
+pub use crate::builder::SimdJsonBuilder;
+pub use crate::builder::{build_value, node2object_with_builder, SerdeJsonBuilder, ValueBuilder};
+pub use crate::config::{
+    ArraySortKey, AttrPosition, AttributeParser, Base64Handling, BinaryEncoding, CollisionStrategy,
+    ContextConflict, ConversionConfig, ConversionMode, DecimalSeparator, DefaultNsHandling,
+    EmptyAttribute, EmptyElement, EntityHandling, HeteroPolicy, IdReferences, NodeContext,
+    PrefixHandling, Preset, ReferenceMode, SplitStrategy, TextMergeOrder, ValueComparator,
+    WrapperKey,
+};
+#[cfg(feature = "dot")]
+pub use crate::dot::element_to_dot;
+#[cfg(feature = "encoding")]
+pub use crate::encoding::xml_bytes_to_json;
+#[cfg(feature = "xml-rs")]
+pub use crate::error::XmlRsConversionError;
+pub use crate::error::{
+    ConversionError, FragmentError, JsonPointer, ReadError, ReverseError, XmlPath,
+};
+pub use crate::events::{drive, JsonEventSink, ValueSink};
+pub use crate::ext::Node2ObjectExt;
+pub use crate::extract::extract;
+#[cfg(feature = "ffi")]
+pub use crate::ffi::{
+    node2object_c, NODE2OBJECT_ERR_BUFFER_TOO_SMALL, NODE2OBJECT_ERR_NO_ROOT,
+    NODE2OBJECT_ERR_NULL_POINTER, NODE2OBJECT_ERR_PANIC, NODE2OBJECT_ERR_PARSE,
+    NODE2OBJECT_ERR_TOO_LARGE,
+};
+pub use crate::flat::{node2flat, FlattenConfig};
+pub use crate::incremental::IncrementalConverter;
+pub use crate::lazy::{iter_converted_children, node2object_lazy};
+#[cfg(feature = "minidom")]
+pub use crate::mdom::minidom_node2object;
+pub use crate::merge::{merge_documents, MergeOptions, RootNames, ScalarConflict};
+#[cfg(feature = "rayon")]
+pub use crate::parallel::node2object_parallel_children;
+#[cfg(feature = "quick-xml")]
+pub use crate::qxml::qxml_node2object;
+pub use crate::reverse::{
+    element_from, value_to_element, value_to_element_with_config, ReverseConfig,
+};
+#[cfg(feature = "roxmltree")]
+pub use crate::roxml::roxmltree_node2object;
+pub use crate::schema::infer_schema;
+pub use crate::ser::struct2node;
+pub use crate::stats::{element_tree_stats, ConversionStats, TreeStats};
+#[cfg(feature = "streaming")]
+pub use crate::streaming::{stream_convert, stream_convert_with_stats, StreamError, StreamHandler};
+#[cfg(feature = "markdown")]
+pub use crate::table::{element_to_markdown_table, MarkdownTableConfig};
+pub use crate::text_parser::{
+    IntegerPreferringTextParser, StandardTextParser, StrictStringParser, TextParser,
+};
+#[cfg(feature = "wasm")]
+pub use crate::wasm::{json2xml_js, node2json_js};
+#[cfg(feature = "xml-rs")]
+pub use crate::xmlrs::xmlrs_document_to_value;
+
+use crate::annotations::{annotate, check_options, Annotations, Recorded};
+use base64::Engine;
+use serde::de::DeserializeOwned;
 use serde_json::{Map, Number, Value};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum XMLNodeType {
+/// Shape of an element, which determines how it is converted. Text includes CDATA sections.
+/// treexml drops text made of whitespace only, so `<e> </e>` is `Empty` and whitespace between
+/// child elements doesn't make their parent `SemiStructured`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum XMLNodeType {
+    /// No text, attributes or children, e.g. `<e/>`.
     Empty,
+    /// Text only, e.g. `<e>text</e>`.
     Text,
+    /// Attributes only, e.g. `<e id="1"/>`.
     Attributes,
+    /// Text and attributes, no children, e.g. `<e id="1">text</e>`.
     TextAndAttributes,
+    /// Children and possibly attributes, no text, e.g. `<e><a/></e>`.
     Parent,
+    /// Mixed content: text next to children, e.g. `<e>some <a/></e>`.
     SemiStructured,
 }
 
-fn scan_xml_node(e: &treexml::Element) -> XMLNodeType {
+/// Classifies `e` the way `node2object` does before converting it, see `XMLNodeType`.
+pub fn classify(e: &treexml::Element) -> XMLNodeType {
+    scan_xml_node(e, &ConversionConfig::default())
+}
+
+/// Checks whether `e` has child elements, i.e. is classified as `XMLNodeType::Parent` or
+/// `XMLNodeType::SemiStructured`.
+pub fn is_structured(e: &treexml::Element) -> bool {
+    matches!(
+        classify(e),
+        XMLNodeType::Parent | XMLNodeType::SemiStructured
+    )
+}
+
+/// Key of the attribute namespaces stored with `ConversionConfig::namespace_key`.
+const ATTRIBUTE_NAMESPACES_KEY: &str = "#attr_ns";
+
+/// Traversal state shared by the conversion routines.
+struct Context<'a> {
+    cfg: &'a ConversionConfig,
+    /// Elements from the conversion root down to the current node.
+    path: Vec<&'a treexml::Element>,
+    /// Attribute of the next converted element that its parent has already consumed.
+    hidden_attribute: Option<&'static str>,
+    /// Namespaces of the next converted element, for `ConversionConfig::namespace_key`.
+    namespaces: Option<&'a namespaces::ElementUris>,
+    /// Annotations of the next converted element, if the backend recorded any.
+    annotations: Option<&'a Annotations>,
+    /// Counters updated along the way, see `node2object_with_stats`.
+    stats: RefCell<ConversionStats>,
+}
+
+impl<'a> Context<'a> {
+    fn new(cfg: &'a ConversionConfig) -> Self {
+        Self {
+            cfg,
+            path: Vec::new(),
+            hidden_attribute: None,
+            namespaces: None,
+            annotations: None,
+            stats: RefCell::default(),
+        }
+    }
+
+    /// Creates a context for converting the children of `parent`.
+    fn with_parent(cfg: &'a ConversionConfig, parent: &'a treexml::Element) -> Self {
+        let mut ctx = Self::new(cfg);
+        ctx.path.push(parent);
+        ctx
+    }
+
+    /// Value of the most specific of `patterns` addressing the current node, see
+    /// `most_specific`.
+    fn most_specific<'c, V>(&self, patterns: &'c HashMap<String, V>) -> Option<&'c V> {
+        let (e, ancestors) = self.path.split_last()?;
+        most_specific(patterns, ancestors, e)
+    }
+
+    /// Locates the current node in the source document. Like `pointer`, only called on failure.
+    fn xml_path(&self) -> XmlPath {
+        let mut path = XmlPath::default();
+        for (depth, e) in self.path.iter().enumerate() {
+            let parent = depth.checked_sub(1).map(|i| self.path[i]);
+            push_xml_step(&mut path, parent, e);
+        }
+        path
+    }
+
+    /// Locates the current node in the converted output. Only called on failure, so the
+    /// happy path never pays for building the pointer.
+    fn pointer(&self) -> JsonPointer {
+        let mut pointer = JsonPointer::default();
+        if let Some(root) = self.path.first() {
+            if self.cfg.include_root {
+                pointer.push(&element_key(root, self.cfg));
+            }
+        }
+
+        for depth in 1..self.path.len() {
+            let ancestors = &self.path[..depth];
+            let parent = ancestors[depth - 1];
+            let e = self.path[depth];
+            if is_collapsed_wrapper(parent, self.cfg) {
+                // The wrapper's entry already stands for its only child.
+                continue;
+            }
+
+            let key = element_key(e, self.cfg);
+            if lang_grouping(parent, &key, self.cfg) == LangGrouping::Grouped {
+                pointer.push(&key);
+                if let Some(lang) = e.attributes.get(XML_LANG) {
+                    pointer.push(lang);
+                }
+                continue;
+            }
+            if is_merged(ancestors, e, self.cfg) {
+                pointer.push(&key);
+                continue;
+            }
+
+            let mut index = 0;
+            let mut count = 0;
+            for c in &parent.children {
+                if element_key(c, self.cfg) == key && produces_value(c, ancestors, self.cfg) {
+                    if std::ptr::eq(c, e) {
+                        index = count;
+                    }
+                    count += 1;
+                }
+            }
+            if count > 1 || is_forced_array(ancestors, e, self.cfg) {
+                pointer.push(renamed_array_key(parent, &key, ancestors, self.cfg).unwrap_or(&key));
+                pointer.push(&index.to_string());
+            } else {
+                pointer.push(&key);
+            }
+        }
+        pointer
+    }
+}
+
+/// Appends the step to `e` below `parent` to `path`, with the position among same-named siblings
+/// if there are several.
+fn push_xml_step(path: &mut XmlPath, parent: Option<&treexml::Element>, e: &treexml::Element) {
+    let same_name = |c: &&treexml::Element| c.name == e.name && c.prefix == e.prefix;
+    let position = parent.and_then(|parent| {
+        let mut siblings = parent.children.iter().filter(same_name);
+        let position = siblings.position(|c| std::ptr::eq(c, e))?;
+        let more = position > 0 || siblings.next().is_some();
+        Some(position + 1).filter(|_| more)
+    });
+    match &e.prefix {
+        Some(prefix) => path.push_element(&format!("{}:{}", prefix, e.name), position),
+        None => path.push_element(&e.name, position),
+    }
+}
+
+/// Checks whether `pattern` addresses the element `e` below `ancestors`. Patterns are either a
+/// bare element name or a slash-separated path of names starting at the root element.
+fn path_matches(pattern: &str, ancestors: &[&treexml::Element], e: &treexml::Element) -> bool {
+    if pattern.contains('/') {
+        pattern.trim_start_matches('/').split('/').eq(ancestors
+            .iter()
+            .copied()
+            .chain(std::iter::once(e))
+            .map(|e| e.name.as_str()))
+    } else {
+        e.name == pattern
+    }
+}
+
+/// Value of the most specific of `patterns` addressing the element `e` below `ancestors`, see
+/// `path_matches`: paths win over bare names, and of equivalent paths such as `a/b` and `/a/b`,
+/// the first in alphabetical order wins.
+fn most_specific<'c, V>(
+    patterns: &'c HashMap<String, V>,
+    ancestors: &[&treexml::Element],
+    e: &treexml::Element,
+) -> Option<&'c V> {
+    patterns
+        .iter()
+        .filter(|(p, _)| path_matches(p, ancestors, e))
+        .max_by_key(|(p, _)| (p.contains('/'), std::cmp::Reverse(*p)))
+        .map(|(_, v)| v)
+}
+
+/// Checks whether the element `e` below `ancestors` is listed in `ConversionConfig::merge_siblings`.
+fn is_merged(
+    ancestors: &[&treexml::Element],
+    e: &treexml::Element,
+    cfg: &ConversionConfig,
+) -> bool {
+    cfg.merge_siblings
+        .iter()
+        .any(|p| path_matches(p, ancestors, e))
+}
+
+/// Checks whether the element `e` below `ancestors` is stored in an array even when it occurs
+/// once, see `ConversionConfig::force_array` and `ConversionConfig::single_element_array_threshold`.
+fn is_forced_array(
+    ancestors: &[&treexml::Element],
+    e: &treexml::Element,
+    cfg: &ConversionConfig,
+) -> bool {
+    cfg.single_element_array_threshold <= 1
+        || cfg
+            .force_array
+            .iter()
+            .any(|p| path_matches(p, ancestors, e))
+        || match ancestors.split_last() {
+            Some((parent, above)) => ensured_arrays(above, parent, cfg).any(|name| *name == e.name),
+            None => false,
+        }
+}
+
+/// Counts the children of an element against `ConversionConfig::max_occurrences`.
+#[derive(Default)]
+struct OccurrenceLimits {
+    seen: HashMap<String, usize>,
+    /// Number of skipped children by key, in order of the first skip.
+    skipped: Vec<(String, usize)>,
+}
+
+impl OccurrenceLimits {
+    /// Counts `c`, the next child of the last element of `ancestors`, and checks whether it is
+    /// within the limit of its name.
+    fn admit(
+        &mut self,
+        ancestors: &[&treexml::Element],
+        c: &treexml::Element,
+        cfg: &ConversionConfig,
+    ) -> bool {
+        let limit = cfg
+            .max_occurrences
+            .iter()
+            .filter(|(p, _)| path_matches(p, ancestors, c))
+            .map(|(_, limit)| *limit)
+            .min();
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return true,
+        };
+        let key = element_key(c, cfg);
+        let seen = self.seen.entry(key.to_string()).or_default();
+        *seen += 1;
+        if *seen <= limit {
+            return true;
+        }
+        match self.skipped.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, count)) => *count += 1,
+            None => self.skipped.push((key.into_owned(), 1)),
+        }
+        false
+    }
+
+    /// Entry recording the skipped children under `ConversionConfig::truncated_count_key`, if
+    /// set and any child was skipped.
+    fn truncated_counts(self, cfg: &ConversionConfig) -> Option<(String, Value)> {
+        let key = cfg.truncated_count_key.clone()?;
+        if self.skipped.is_empty() {
+            return None;
+        }
+        let counts = self
+            .skipped
+            .into_iter()
+            .map(|(k, count)| (k, Value::from(count)))
+            .collect();
+        Some((key, Value::Object(counts)))
+    }
+}
+
+/// Names of the children that are always stored in arrays under the element `e` below
+/// `ancestors`, see `ConversionConfig::ensure_array_keys`.
+fn ensured_arrays<'c>(
+    ancestors: &'c [&treexml::Element],
+    e: &'c treexml::Element,
+    cfg: &'c ConversionConfig,
+) -> impl Iterator<Item = &'c String> + 'c {
+    cfg.ensure_array_keys
+        .iter()
+        .filter(move |(p, _)| path_matches(p, ancestors, e))
+        .flat_map(|(_, names)| names)
+}
+
+/// Adds an empty array to the value of `e` for each name in `ensure_array_keys` that doesn't
+/// occur among its children, turning the value into an object if needed. Expects `e` to be
+/// the last element of `ctx.path`.
+fn insert_ensured_arrays(
+    value: Option<Value>,
+    e: &treexml::Element,
+    kind: XMLNodeType,
+    ctx: &Context,
+) -> Option<Value> {
+    let keys = ensured_array_keys(e, ctx);
+    if keys.is_empty() {
+        return value;
+    }
+    let mut data = match value {
+        Some(Value::Object(data)) => data,
+        Some(v) => vec![(text_key_for(ctx.cfg, kind).to_string(), v)]
+            .into_iter()
+            .collect(),
+        None => Map::new(),
+    };
+    for key in keys {
+        if !data.contains_key(&key) {
+            data.insert(key, Value::Array(Vec::new()));
+            ctx.stats.borrow_mut().array_count += 1;
+        }
+    }
+    Some(Value::Object(data))
+}
+
+/// Keys of the arrays that `insert_ensured_arrays` adds to the value of `e`, the last element
+/// of `ctx.path`, where missing.
+fn ensured_array_keys(e: &treexml::Element, ctx: &Context) -> Vec<String> {
+    let ancestors = &ctx.path[..ctx.path.len() - 1];
+    ensured_arrays(ancestors, e, ctx.cfg)
+        .map(|name| {
+            let key = escape_reserved(Cow::Borrowed(name), ctx.cfg);
+            let key = renamed_array_key(e, &key, &ctx.path, ctx.cfg).unwrap_or(&key);
+            key.to_string()
+        })
+        .collect()
+}
+
+/// Key replacing `key` in the object of `parent` once its elements are stored in an array,
+/// unless another child of `parent` already produces that key.
+fn renamed_array_key<'c>(
+    parent: &treexml::Element,
+    key: &str,
+    ancestors: &[&treexml::Element],
+    cfg: &'c ConversionConfig,
+) -> Option<&'c str> {
+    let renamed = cfg.array_key_rename.get(key)?;
+    let taken = parent
+        .children
+        .iter()
+        .any(|c| element_key(c, cfg) == renamed.as_str() && produces_value(c, ancestors, cfg));
+    if taken {
+        None
+    } else {
+        Some(renamed)
+    }
+}
+
+/// Deep-merges `v` into `target`. On a scalar conflict in strict mode, returns the keys leading
+/// to the conflicting value.
+fn merge_values(target: &mut Value, v: Value, strict: bool) -> Result<(), Vec<String>> {
+    match (target, v) {
+        (Value::Object(target), Value::Object(other)) => {
+            for (k, v) in other {
+                match target.get_mut(&k) {
+                    Some(existing) => merge_values(existing, v, strict).map_err(|mut keys| {
+                        keys.insert(0, k);
+                        keys
+                    })?,
+                    None => {
+                        target.insert(k, v);
+                    }
+                }
+            }
+            Ok(())
+        }
+        (Value::Array(target), Value::Array(other)) => {
+            target.extend(other);
+            Ok(())
+        }
+        (target, v) => {
+            if strict && *target != v {
+                return Err(Vec::new());
+            }
+            *target = v;
+            Ok(())
+        }
+    }
+}
+
+/// Value configured in `ConversionConfig::defaults` for the element `e` below `ancestors`.
+fn default_value<'c>(
+    ancestors: &[&treexml::Element],
+    e: &treexml::Element,
+    cfg: &'c ConversionConfig,
+) -> Option<&'c Value> {
+    if cfg.defaults.is_empty() {
+        return None;
+    }
+    most_specific(&cfg.defaults, ancestors, e)
+}
+
+/// Checks whether the element `e` below `ancestors` shows up in its parent's object; empty
+/// elements are omitted unless they have a default value.
+fn produces_value(
+    e: &treexml::Element,
+    ancestors: &[&treexml::Element],
+    cfg: &ConversionConfig,
+) -> bool {
+    scan_xml_node(e, cfg) != XMLNodeType::Empty
+        || cfg.empty_element_as != EmptyElement::Omit
+        || cfg.default_text_value.is_some()
+        || default_value(ancestors, e, cfg).is_some()
+        || ensured_arrays(ancestors, e, cfg).next().is_some()
+}
+
+/// Value of an element classified as `XMLNodeType::Empty`, if any.
+fn empty_value(default: Option<&Value>, cfg: &ConversionConfig) -> Option<Value> {
+    if let Some(v) = default.or(cfg.default_text_value.as_ref()) {
+        return Some(v.clone());
+    }
+    match cfg.empty_element_as {
+        EmptyElement::Omit => None,
+        EmptyElement::Null => Some(Value::Null),
+        EmptyElement::EmptyString => Some(Value::String(String::new())),
+        EmptyElement::EmptyObject => Some(Value::Object(Map::new())),
+        EmptyElement::Boolean => Some(Value::Bool(true)),
+    }
+}
+
+fn scan_xml_node(e: &treexml::Element, cfg: &ConversionConfig) -> XMLNodeType {
+    classify_with_attributes(e, has_converted_attributes(e, cfg), cfg)
+}
+
+/// Classifies `e` as if its attribute set were empty or not according to `has_attributes`,
+/// for callers that filter attributes before converting them. CDATA stored apart from the text
+/// counts as an attribute, since it turns the element into an object as well.
+fn classify_with_attributes(
+    e: &treexml::Element,
+    has_attributes: bool,
+    cfg: &ConversionConfig,
+) -> XMLNodeType {
+    let has_attributes = has_attributes || (cfg.separate_cdata && e.cdata.is_some());
     if e.children.is_empty() {
         if e.text.is_none() && e.cdata.is_none() {
-            if e.attributes.is_empty() {
+            if !has_attributes {
                 XMLNodeType::Empty
             } else {
                 XMLNodeType::Attributes
             }
-        } else if e.attributes.is_empty() {
+        } else if !has_attributes {
             XMLNodeType::Text
         } else {
             XMLNodeType::TextAndAttributes
@@ -61,174 +617,7096 @@ fn scan_xml_node(e: &treexml::Element) -> XMLNodeType {
     }
 }
 
-fn parse_text(text: &str) -> Value {
-    if let Ok(v) = text.parse::<f64>() {
-        if let Some(v) = Number::from_f64(v) {
-            return Value::Number(v);
+/// Parses `0x`, `0o` and `0b` prefixed integer literals. Values that don't fit i64/u64 are rejected.
+fn parse_radix_literal(text: &str) -> Option<Number> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let radix = match unsigned.get(..2)? {
+        "0x" | "0X" => 16,
+        "0o" | "0O" => 8,
+        "0b" | "0B" => 2,
+        _ => return None,
+    };
+    let digits = &unsigned[2..];
+    if digits.is_empty() || digits.starts_with(['+', '-']) {
+        return None;
+    }
+    let v = u64::from_str_radix(digits, radix).ok()?;
+
+    if negative {
+        if v <= i64::MAX as u64 {
+            Some(Number::from(-(v as i64)))
+        } else if v == i64::MIN.unsigned_abs() {
+            Some(Number::from(i64::MIN))
+        } else {
+            None
         }
+    } else {
+        Some(Number::from(v))
+    }
+}
+
+/// Checks that `digits` is grouped in threes with `separator`, e.g. `1.234.567`.
+fn valid_thousands_grouping(digits: &str, separator: char) -> bool {
+    let mut groups = digits.split(separator);
+    let leading = groups.next().unwrap_or("");
+    (1..=3).contains(&leading.len()) && groups.all(|g| g.len() == 3)
+}
+
+/// Rewrites a decimal number written with the configured decimal and thousands separators
+/// into the `.`-separated form understood by `str::parse`.
+fn normalize_decimal<'a>(text: &'a str, cfg: &ConversionConfig) -> Option<Cow<'a, str>> {
+    let (decimal, grouping) = match cfg.decimal_separator {
+        DecimalSeparator::Dot => ('.', ','),
+        DecimalSeparator::Comma => (',', '.'),
+    };
+
+    if !text.contains(grouping) {
+        return Some(match cfg.decimal_separator {
+            DecimalSeparator::Dot => Cow::Borrowed(text),
+            DecimalSeparator::Comma => Cow::Owned(text.replace(',', ".")),
+        });
+    }
+
+    if !cfg.allow_thousands_separators {
+        return None;
     }
 
-    if let Ok(v) = text.parse::<bool>() {
-        return Value::Bool(v);
+    let unsigned = text.trim_start_matches(['-', '+']);
+    let integer = unsigned.split(decimal).next().unwrap_or("");
+    if !integer.chars().all(|c| c.is_ascii_digit() || c == grouping)
+        || !valid_thousands_grouping(integer, grouping)
+    {
+        return None;
     }
 
-    Value::String(text.into())
+    Some(Cow::Owned(text.replace(grouping, "").replace(decimal, ".")))
 }
 
-fn parse_text_contents(e: &treexml::Element) -> Value {
-    let text = &[&e.text, &e.cdata]
-        .iter()
-        .map(|v| v.as_ref().map(String::as_str).unwrap_or(""))
-        .collect::<Vec<_>>()
-        .concat();
-    parse_text(&text)
+/// Parses a normalized decimal number. With `strict_number_roundtrip`, integers are kept as
+/// JSON integers and the number is rejected unless rendering it reproduces `text`.
+fn parse_number(text: &str, cfg: &ConversionConfig) -> Option<Number> {
+    if !cfg.strict_number_roundtrip {
+        return Number::from_f64(text.parse().ok()?);
+    }
+
+    let n = if let Ok(v) = text.parse::<i64>() {
+        Number::from(v)
+    } else if let Ok(v) = text.parse::<u64>() {
+        Number::from(v)
+    } else {
+        Number::from_f64(text.parse().ok()?)?
+    };
+
+    if n.to_string() == text.strip_prefix('+').unwrap_or(text) {
+        Some(n)
+    } else {
+        None
+    }
 }
 
-fn convert_node_aux(e: &treexml::Element) -> Option<Value> {
-    match scan_xml_node(e) {
-        XMLNodeType::Parent => {
-            let mut data = Map::new();
-            let mut firstpass = std::collections::HashSet::<&str>::new();
-            let mut vectorized = std::collections::HashSet::<&str>::new();
-
-            if !e.attributes.is_empty() {
-                for (k, v) in e.attributes.clone().into_iter() {
-                    data.insert(format!("@{}", k), parse_text(&v));
-                }
-            }
-
-            for c in &e.children {
-                if let Some(v) = convert_node_aux(c) {
-                    if firstpass.contains(&c.name.as_str()) {
-                        if vectorized.contains(&c.name.as_str()) {
-                            data.get_mut(&c.name)
-                                .unwrap()
-                                .as_array_mut()
-                                .unwrap()
-                                .push(v);
-                        } else {
-                            let elem = data.remove(&c.name).unwrap();
-                            data.insert(c.name.clone(), Value::Array(vec![elem, v]));
-                            vectorized.insert(c.name.as_str());
-                        }
-                    } else {
-                        data.insert(c.name.clone(), v);
-                        firstpass.insert(c.name.as_str());
-                    }
-                }
+/// Where a piece of text being coerced comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TextSource {
+    Attribute,
+    Text,
+}
+
+/// Normalized form of `text` if the configuration allows coercing it to a decimal number.
+fn numeric_text<'a>(text: &'a str, cfg: &ConversionConfig) -> Option<Cow<'a, str>> {
+    let numeric = cfg.coerce_numbers
+        && (cfg.allow_leading_plus || !text.starts_with('+'))
+        && (cfg.coerce_scientific_notation || !text.contains(['e', 'E']));
+    if numeric {
+        normalize_decimal(text, cfg)
+    } else {
+        None
+    }
+}
+
+/// Coerces `text` to a JSON scalar other than a string, if the configuration allows it.
+fn coerce_text(text: &str, source: TextSource, cfg: &ConversionConfig) -> Option<Value> {
+    #[cfg(feature = "dates")]
+    {
+        if cfg.detect_dates {
+            if let Some(v) = dates::normalize(text, &cfg.date_patterns) {
+                return Some(Value::String(v));
             }
-            Some(Value::Object(data))
         }
-        XMLNodeType::Text => Some(parse_text_contents(e)),
-        XMLNodeType::Attributes => Some(Value::Object(
-            e.attributes
-                .clone()
-                .into_iter()
-                .map(|(k, v)| (format!("@{}", k), parse_text(&v)))
-                .collect(),
-        )),
-        XMLNodeType::TextAndAttributes => Some(Value::Object(
-            e.attributes
-                .clone()
-                .into_iter()
-                .map(|(k, v)| (format!("@{}", k), parse_text(&v)))
-                .chain(vec![("#text".to_string(), parse_text_contents(&e))])
-                .collect(),
-        )),
-        _ => None,
     }
+
+    if cfg.coerce_numbers && cfg.parse_radix_literals {
+        if let Some(v) = parse_radix_literal(text) {
+            return Some(Value::Number(v));
+        }
+    }
+
+    if let Some(v) = numeric_text(text, cfg).and_then(|v| parse_number(&v, cfg)) {
+        return Some(Value::Number(v));
+    }
+
+    let parse_booleans = match source {
+        TextSource::Attribute => cfg.boolean_in_attributes,
+        TextSource::Text => cfg.boolean_in_text,
+    };
+    if parse_booleans {
+        if let Ok(v) = text.parse::<bool>() {
+            return Some(Value::Bool(v));
+        }
+    }
+
+    None
 }
 
-/// Converts treexml::Element into a serde_json hashmap. The latter can be wrapped in Value::Object.
-pub fn node2object(e: &treexml::Element) -> Map<String, Value> {
+/// Converts the text content of an element into the JSON value the converter stores for it,
+/// e.g. `42.0` for `"42"` or `true` for `"true"`, following the coercion options of `cfg`
+/// (`trim_text`, `coerce_numbers`, `text_parser`, ...). Attribute values are coerced the same
+/// way, except that `boolean_in_attributes` replaces `boolean_in_text` and
+/// `preserve_original_string` doesn't apply.
+pub fn coerce_scalar(text: &str, cfg: &ConversionConfig) -> Value {
+    parse_text(text, TextSource::Text, cfg)
+}
+
+/// Part of `text`, already trimmed if `trim_text` is set, that the built-in coercions look at,
+/// or the value stored for `text` if they don't apply.
+fn coercion_candidate<'a>(text: &'a str, cfg: &ConversionConfig) -> Result<&'a str, Value> {
+    if cfg.mode == ConversionMode::GData || cfg.all_scalars_as_strings {
+        return Err(Value::String(text.into()));
+    }
+    if let Some(parser) = &cfg.text_parser {
+        return Err(parser.parse(text));
+    }
+
+    if cfg.coerce_padded_numbers {
+        Ok(text.trim())
+    } else {
+        Ok(text)
+    }
+}
+
+/// Checks whether `text` would be coerced to a number but is out of range of `f64`, so that
+/// it is kept as a string instead.
+fn overflows_f64(text: &str, source: TextSource, cfg: &ConversionConfig) -> bool {
+    let text = if cfg.trim_text { text.trim() } else { text };
+    let candidate = match coercion_candidate(text, cfg) {
+        Ok(candidate) => candidate,
+        Err(_) => return false,
+    };
+    coerce_text(candidate, source, cfg).is_none()
+        && candidate.bytes().any(|b| b.is_ascii_digit())
+        && numeric_text(candidate, cfg)
+            .and_then(|v| v.parse::<f64>().ok())
+            .is_some_and(|v| v.is_infinite())
+}
+
+fn parse_text(text: &str, source: TextSource, cfg: &ConversionConfig) -> Value {
+    let text = if cfg.trim_text { text.trim() } else { text };
+    let candidate = match coercion_candidate(text, cfg) {
+        Ok(candidate) => candidate,
+        Err(v) => return v,
+    };
+    match coerce_text(candidate, source, cfg) {
+        Some(v)
+            if cfg.preserve_original_string
+                && source == TextSource::Text
+                && (v.is_number() || v.is_boolean()) =>
+        {
+            let mut data = Map::new();
+            data.insert("#value".into(), v);
+            data.insert("#raw".into(), Value::String(text.into()));
+            Value::Object(data)
+        }
+        Some(v) => v,
+        None => Value::String(text.into()),
+    }
+}
+
+fn parse_base64(text: &str, handling: &Base64Handling) -> Option<Value> {
+    let payload = text
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<String>();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(&payload)
+        .ok()?;
+
     let mut data = Map::new();
-    data.insert(e.name.clone(), convert_node_aux(e).unwrap_or(Value::Null));
-    data
+    match handling {
+        Base64Handling::Tagged => {
+            data.insert("#base64".into(), Value::String(payload));
+        }
+        Base64Handling::Reencode(BinaryEncoding::Hex) => {
+            data.insert(
+                "#hex".into(),
+                Value::String(bytes.iter().map(|b| format!("{:02x}", b)).collect()),
+            );
+        }
+        Base64Handling::Reencode(BinaryEncoding::Base64Url) => {
+            data.insert(
+                "#base64url".into(),
+                Value::String(base64::engine::general_purpose::URL_SAFE.encode(&bytes)),
+            );
+        }
+        Base64Handling::Metadata => {
+            let mut meta = Map::new();
+            meta.insert("bytes".into(), Value::from(bytes.len()));
+            data.insert("#binary".into(), Value::Object(meta));
+        }
+    }
+    Some(Value::Object(data))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Splits the value of an attribute listed in `ConversionConfig::split_attribute_values`.
+fn split_attribute_value(name: &str, value: &str, cfg: &ConversionConfig) -> Option<Value> {
+    let values: Vec<&str> = match cfg.split_attribute_values.get(name)? {
+        SplitStrategy::Whitespace => value.split_whitespace().collect(),
+        SplitStrategy::Comma => value.split(',').collect(),
+        SplitStrategy::Semicolon => value.split(';').collect(),
+        SplitStrategy::Char(c) => value.split(*c).collect(),
+    };
+    Some(Value::Array(
+        values
+            .into_iter()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| Value::String(v.to_string()))
+            .collect(),
+    ))
+}
 
-    use serde_json::json;
+fn convert_attribute(
+    name: &str,
+    value: &str,
+    node: &NodeContext,
+    cfg: &ConversionConfig,
+) -> (String, Value) {
+    let parsed = cfg
+        .attribute_parser
+        .as_ref()
+        .and_then(|parser| parser.parse(name, value, node))
+        .or_else(|| {
+            (cfg.empty_attribute_as == EmptyAttribute::Null
+                && !cfg.all_scalars_as_strings
+                && is_empty_attribute(value, cfg))
+            .then_some(Value::Null)
+        })
+        .or_else(|| split_attribute_value(name, value, cfg));
 
-    #[test]
-    fn spec_types() {
-        for (src, scan_result, conv_result) in vec![
-            (r#"<e/>"#, XMLNodeType::Empty, json!({ "e": null })),
-            (r#"<e>text</e>"#, XMLNodeType::Text, json!({"e": "text"})),
-            (
-                r#"<e name="value"/>"#,
-                XMLNodeType::Attributes,
-                json!({ "e": {"@name": "value"} }),
-            ),
-            (
-                r#"<e name="value">text</e>"#,
-                XMLNodeType::TextAndAttributes,
-                json!({ "e": { "@name": "value", "#text": "text" } }),
-            ),
-            (
-                r#"<e> <a>text</a> <b>text</b> </e>"#,
-                XMLNodeType::Parent,
-                json!({ "e": { "a": "text", "b": "text" } }),
-            ),
-            (
-                r#"<e> <a>text</a> <a>text</a> </e>"#,
-                XMLNodeType::Parent,
-                json!({ "e": { "a": ["text", "text"] } }),
-            ),
-        ] {
-            let fixture = treexml::Document::parse(src.as_bytes())
-                .unwrap()
-                .root
-                .unwrap();
+    if cfg.attribute_as_child {
+        let mut data = Map::new();
+        data.insert(
+            text_key(cfg).to_string(),
+            parsed.unwrap_or_else(|| coerce_scalar(value, cfg)),
+        );
+        return (attribute_key(name, cfg), Value::Object(data));
+    }
 
-            assert_eq!(scan_result, scan_xml_node(&fixture));
-            assert_eq!(conv_result, Value::Object(node2object(&fixture)));
+    (
+        attribute_key(name, cfg),
+        parsed.unwrap_or_else(|| parse_text(value, TextSource::Attribute, cfg)),
+    )
+}
+
+/// Converts `attributes` of the element described by `node` into the entries of its object,
+/// grouped under `ConversionConfig::attributes_key` if set.
+fn attribute_entries(
+    attributes: Vec<(&str, &str)>,
+    node: &NodeContext,
+    cfg: &ConversionConfig,
+) -> Map<String, Value> {
+    let entries = attributes
+        .into_iter()
+        .filter(|(_, v)| !(cfg.omit_empty_attributes && is_empty_attribute(v, cfg)))
+        .map(|(k, v)| convert_attribute(k, v, node, cfg))
+        .collect::<Map<_, _>>();
+    match &cfg.attributes_key {
+        Some(key) if !entries.is_empty() => vec![(key.clone(), Value::Object(entries))]
+            .into_iter()
+            .collect(),
+        _ => entries,
+    }
+}
+
+/// Keys of the attributes in `data`, for `position_attributes` to tell them apart once entries
+/// for the children are added. Left empty if the attributes stay where they are.
+fn attribute_keys(data: &Map<String, Value>, cfg: &ConversionConfig) -> Vec<String> {
+    match cfg.attribute_position {
+        AttrPosition::First => Vec::new(),
+        _ => data.keys().cloned().collect(),
+    }
+}
+
+/// Moves the `attributes` entries of `data` according to `ConversionConfig::attribute_position`.
+fn position_attributes(
+    data: Map<String, Value>,
+    attributes: &[String],
+    cfg: &ConversionConfig,
+) -> Map<String, Value> {
+    let is_attribute = |k: &String| attributes.contains(k);
+    match cfg.attribute_position {
+        AttrPosition::First => data,
+        AttrPosition::Last => {
+            let (attributes, others): (Vec<_>, Vec<_>) =
+                data.into_iter().partition(|(k, _)| is_attribute(k));
+            others.into_iter().chain(attributes).collect()
+        }
+        AttrPosition::Sorted => {
+            let sort_key = |k: &String| {
+                if is_attribute(k) {
+                    k.strip_prefix('@').unwrap_or(k).to_string()
+                } else {
+                    k.clone()
+                }
+            };
+            let mut entries = data.into_iter().collect::<Vec<_>>();
+            entries.sort_by_cached_key(|(k, _)| sort_key(k));
+            entries.into_iter().collect()
         }
     }
+}
 
-    #[test]
-    fn spec_examples() {
-        for (src, conv_result) in vec![(
-            r#"<e><a>some</a><b>textual</b><a>content</a></e>"#,
-            json!({ "e": { "a": [ "some", "content" ], "b": "textual"} }),
-        )] {
-            let fixture = treexml::Document::parse(src.as_bytes())
-                .unwrap()
-                .root
-                .unwrap();
+/// Writes the namespace prefix of `name` with `ConversionConfig::attribute_namespace_separator`.
+/// Names in Clark notation are left alone.
+fn separate_prefix<'a>(name: &'a str, cfg: &ConversionConfig) -> Cow<'a, str> {
+    if cfg.attribute_namespace_separator == ':' || !name.contains(':') || name.starts_with('{') {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(name.replace(
+            ':',
+            cfg.attribute_namespace_separator.encode_utf8(&mut [0; 4]),
+        ))
+    }
+}
 
-            assert_eq!(conv_result, Value::Object(node2object(&fixture)));
+/// Key under which an attribute is stored in its element's object, before escaping.
+fn raw_attribute_key(name: &str, cfg: &ConversionConfig) -> String {
+    match cfg.mode {
+        ConversionMode::Standard
+            if !cfg.attribute_as_child
+                && !cfg.merge_attributes_into_root
+                && cfg.attributes_key.is_none() =>
+        {
+            format!("@{}", separate_prefix(name, cfg))
         }
+        ConversionMode::Standard => separate_prefix(name, cfg).into_owned(),
+        ConversionMode::GData => name.replace(':', "$"),
     }
+}
 
-    #[test]
-    fn preserve_attributes_parents() {
-        let dom_root = treexml::Document::parse(
-            r#"
-        <a pizza="hotdog">
-          <b frenchfry="milkshake">
-            <c>scotch</c>
-          </b>
-        </a>
-    "#
-            .as_bytes(),
-        )
-        .unwrap()
-        .root
-        .unwrap();
+/// Key under which an attribute is stored in its element's object.
+fn attribute_key(name: &str, cfg: &ConversionConfig) -> String {
+    escape_reserved(Cow::Owned(raw_attribute_key(name, cfg)), cfg).into_owned()
+}
 
-        let json_result = Value::Object(node2object(&dom_root));
-        let expected = json!({
-            "a": json!({
-                "@pizza": "hotdog",
+/// Text of `e`, including CDATA sections unless `ConversionConfig::separate_cdata` is set.
+fn text_contents(e: &treexml::Element, cfg: &ConversionConfig) -> String {
+    let text = e.text.as_deref().unwrap_or("");
+    let cdata = match &e.cdata {
+        Some(cdata) if !cfg.separate_cdata => cdata.as_str(),
+        _ => "",
+    };
+    let (first, second) = match cfg.text_merge_order {
+        TextMergeOrder::TextFirst => (text, cdata),
+        TextMergeOrder::CdataFirst => (cdata, text),
+    };
+    if first.is_empty() || second.is_empty() {
+        [first, second].concat()
+    } else {
+        [first, &cfg.text_merge_separator, second].concat()
+    }
+}
+
+/// Text of `e` as a string value, trimmed if `trim_text` is set but otherwise not coerced.
+fn uncoerced_text(e: &treexml::Element, cfg: &ConversionConfig) -> Value {
+    let text = text_contents(e, cfg);
+    if cfg.trim_text {
+        Value::String(text.trim().into())
+    } else {
+        Value::String(text)
+    }
+}
+
+/// Checks whether the text of `e` is empty, after trimming if `trim_text` is set.
+fn is_blank(e: &treexml::Element, cfg: &ConversionConfig) -> bool {
+    let text = text_contents(e, cfg);
+    if cfg.trim_text {
+        text.trim().is_empty()
+    } else {
+        text.is_empty()
+    }
+}
+
+/// Stores the text of `e` in `data` under the text key for `kind`, and its CDATA under
+/// `ConversionConfig::cdata_key` if kept apart.
+fn insert_text_contents(
+    data: &mut Map<String, Value>,
+    e: &treexml::Element,
+    kind: XMLNodeType,
+    ctx: &Context,
+) -> Result<(), ConversionError> {
+    if ctx.cfg.separate_cdata {
+        if let Some(cdata) = &e.cdata {
+            data.insert(ctx.cfg.cdata_key.clone(), Value::String(cdata.clone()));
+        }
+        if e.text.is_none() {
+            return Ok(());
+        }
+    }
+    data.insert(
+        text_key_for(ctx.cfg, kind).to_string(),
+        parse_text_contents(e, ctx)?,
+    );
+    Ok(())
+}
+
+fn parse_text_contents(e: &treexml::Element, ctx: &Context) -> Result<Value, ConversionError> {
+    let text = &text_contents(e, ctx.cfg);
+
+    if let Some(handling) = ctx.most_specific(&ctx.cfg.base64_paths) {
+        match parse_base64(text, handling) {
+            Some(v) => return Ok(v),
+            None if ctx.cfg.strict => {
+                let mut path = ctx.pointer();
+                let kind = scan_xml_node(e, ctx.cfg);
+                if ctx.cfg.mode == ConversionMode::GData || kind != XMLNodeType::Text {
+                    path.push(text_key_for(ctx.cfg, kind));
+                }
+                return Err(ConversionError::InvalidBase64 {
+                    path,
+                    xml_path: ctx.xml_path(),
+                });
+            }
+            None => {}
+        }
+    }
+
+    let value = coerce_scalar(text, ctx.cfg);
+    ctx.stats.borrow_mut().count_coercions(&value);
+    Ok(value)
+}
+
+const XML_LANG: &str = "xml:lang";
+
+/// How the same-named children of an element are grouped by `xml:lang`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LangGrouping {
+    /// Not all siblings carry `xml:lang`, or the option is off.
+    None,
+    /// Every sibling carries a distinct `xml:lang`.
+    Grouped,
+    /// Some siblings lack `xml:lang`, or a language code repeats.
+    Ambiguous,
+}
+
+/// Languages seen so far among the same-named children of an element.
+#[derive(Default)]
+struct LangGroup<'a> {
+    langs: HashSet<&'a str>,
+    missing: bool,
+    repeated: bool,
+}
+
+impl<'a> LangGroup<'a> {
+    fn add(&mut self, c: &'a treexml::Element) {
+        match c.attributes.get(XML_LANG) {
+            Some(lang) => self.repeated |= !self.langs.insert(lang.as_str()),
+            None => self.missing = true,
+        }
+    }
+
+    fn grouping(&self) -> LangGrouping {
+        match (self.langs.is_empty(), self.missing || self.repeated) {
+            (true, _) => LangGrouping::None,
+            (false, false) => LangGrouping::Grouped,
+            (false, true) => LangGrouping::Ambiguous,
+        }
+    }
+}
+
+/// How the children of `parent` stored under `key` are grouped by `xml:lang`.
+fn lang_grouping(parent: &treexml::Element, key: &str, cfg: &ConversionConfig) -> LangGrouping {
+    if !cfg.group_by_xml_lang {
+        return LangGrouping::None;
+    }
+    let mut group = LangGroup::default();
+    for c in parent
+        .children
+        .iter()
+        .filter(|c| element_key(c, cfg) == key)
+    {
+        group.add(c);
+    }
+    group.grouping()
+}
+
+/// How the children of `parent` are grouped by `xml:lang`, for each key they are stored under,
+/// in order of the first child of each key.
+fn lang_groupings<'a>(
+    parent: &'a treexml::Element,
+    cfg: &ConversionConfig,
+) -> Vec<(Cow<'a, str>, LangGrouping)> {
+    let mut order = Vec::new();
+    let mut groups = HashMap::<Cow<str>, LangGroup>::new();
+    for c in &parent.children {
+        groups
+            .entry(element_key(c, cfg))
+            .or_insert_with_key(|key| {
+                order.push(key.clone());
+                LangGroup::default()
+            })
+            .add(c);
+    }
+    order
+        .into_iter()
+        .map(|key| {
+            let grouping = groups[&key].grouping();
+            (key, grouping)
+        })
+        .collect()
+}
+
+/// Reports a broken invariant while inserting the child `key` of the element on top of `ctx`.
+fn internal_error(ctx: &Context, key: &str) -> ConversionError {
+    let mut path = ctx.pointer();
+    path.push(key);
+    ConversionError::Internal {
+        path,
+        xml_path: ctx.xml_path(),
+    }
+}
+
+/// Tracks sibling names while children are inserted into their parent's object.
+struct SiblingGroups<'a> {
+    cfg: &'a ConversionConfig,
+    firstpass: HashSet<String>,
+    vectorized: HashSet<String>,
+    /// Keys whose elements are stored in an object keyed by `xml:lang`.
+    by_lang: HashSet<String>,
+    /// Keys whose elements are deep-merged into a single object.
+    merged: HashSet<String>,
+    /// Keys whose elements are stored in an array even when they occur once.
+    forced: HashSet<String>,
+    /// Keys to rename once their elements are stored in an array.
+    renamed: Vec<(String, &'a str)>,
+    /// Keys whose elements are stored like attributes.
+    promoted: HashSet<String>,
+    /// Keys of repeated text-only elements whose values are given a common type.
+    harmonized: HashSet<String>,
+    /// Texts of the elements stored under each key, in order, for replacing their values when
+    /// harmonizing them or stringifying heterogeneous arrays.
+    members: HashMap<String, Vec<MemberText>>,
+    /// Keys of the attributes of the parent, when children sharing them are looked for.
+    attributes: Option<HashSet<String>>,
+    /// Keys shared by an attribute of the parent and some of its children.
+    colliding: HashSet<String>,
+    /// Indices of the children skipped by `ConversionConfig::max_occurrences`.
+    skipped: HashSet<usize>,
+    limits: OccurrenceLimits,
+}
+
+/// Where the next value stored under a key goes, see `SiblingGroups::place`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Placement {
+    /// The only value so far, stored as it is.
+    Single,
+    /// First item of a new array.
+    Start,
+    /// Second value, turning the value stored so far into an array.
+    Promote,
+    /// Next item of the array.
+    Append,
+}
+
+/// Text of a child, kept for replacing its converted value, see `SiblingGroups::members`.
+struct MemberText {
+    /// `xml:lang` of the child, for groups keyed by language.
+    lang: Option<String>,
+    text: Value,
+}
+
+/// JSON type of a scalar value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScalarKind {
+    Integer,
+    Float,
+    Boolean,
+    String,
+}
+
+impl ScalarKind {
+    fn of(v: &Value) -> Option<Self> {
+        match v {
+            Value::Number(n) if n.is_f64() => Some(Self::Float),
+            Value::Number(_) => Some(Self::Integer),
+            Value::Bool(_) => Some(Self::Boolean),
+            Value::String(_) => Some(Self::String),
+            _ => None,
+        }
+    }
+}
+
+/// Type that repeated siblings are coerced to, see `ConversionConfig::harmonize_sibling_types`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CommonType {
+    Float,
+    String,
+}
+
+/// Common type of the converted values of a group of siblings, if their types disagree.
+/// Groups containing non-scalar values are left alone.
+fn common_type<'v>(values: impl IntoIterator<Item = &'v Value>) -> Option<CommonType> {
+    let mut first = None;
+    let mut mixed = false;
+    let mut numeric = true;
+    for v in values {
+        let kind = ScalarKind::of(v)?;
+        mixed |= *first.get_or_insert(kind) != kind;
+        numeric &= matches!(kind, ScalarKind::Integer | ScalarKind::Float);
+    }
+    match (mixed, numeric) {
+        (false, _) => None,
+        (true, true) => Some(CommonType::Float),
+        (true, false) => Some(CommonType::String),
+    }
+}
+
+impl<'a> SiblingGroups<'a> {
+    /// Prepares grouping of the children of `parent`, to be registered one at a time with
+    /// `admit`. Options that look at all siblings at once, such as `child_as_attribute`, are
+    /// left to `new`.
+    fn incremental(parent: Option<&treexml::Element>, cfg: &'a ConversionConfig) -> Self {
+        let attributes = parent
+            .filter(|_| {
+                cfg.attribute_child_collision != CollisionStrategy::ChildWins
+                    && cfg.attributes_key.is_none()
+            })
+            .map(|parent| {
+                node_attributes(parent, None, cfg)
+                    .into_iter()
+                    .map(|(name, _)| attribute_key(name, cfg))
+                    .collect()
+            });
+        Self {
+            cfg,
+            firstpass: HashSet::new(),
+            vectorized: HashSet::new(),
+            by_lang: HashSet::new(),
+            merged: HashSet::new(),
+            forced: HashSet::new(),
+            renamed: Vec::new(),
+            promoted: HashSet::new(),
+            harmonized: HashSet::new(),
+            members: HashMap::new(),
+            attributes,
+            colliding: HashSet::new(),
+            skipped: HashSet::new(),
+            limits: OccurrenceLimits::default(),
+        }
+    }
+
+    /// Prepares grouping of the children of the element on top of `ctx`.
+    fn new(ctx: &Context<'a>) -> Result<Self, ConversionError> {
+        let mut groups = Self::incremental(ctx.path.last().copied(), ctx.cfg);
+        let parent = match ctx.path.last() {
+            Some(parent) => parent,
+            None => return Ok(groups),
+        };
+        for (i, c) in parent.children.iter().enumerate() {
+            if !groups.admit(&ctx.path, c) {
+                groups.skipped.insert(i);
+            }
+        }
+        // Truncated groups are repeated, so they stay arrays even if one child is left.
+        for (key, _) in &groups.limits.skipped {
+            groups.forced.insert(key.clone());
+        }
+
+        if ctx.cfg.child_as_attribute {
+            let mut counts = HashMap::<Cow<str>, usize>::new();
+            for c in &parent.children {
+                *counts.entry(element_key(c, ctx.cfg)).or_default() += 1;
+            }
+            for c in &parent.children {
+                let key = element_key(c, ctx.cfg);
+                if counts[&key] == 1
+                    && scan_xml_node(c, ctx.cfg) == XMLNodeType::Text
+                    && !parent.attributes.contains_key(key.as_ref())
+                {
+                    groups.promoted.insert(key.into_owned());
+                }
+            }
+        }
+
+        if ctx.cfg.harmonize_sibling_types {
+            let mut text_only = HashMap::<Cow<str>, (usize, bool)>::new();
+            for c in &parent.children {
+                let (count, text) = text_only
+                    .entry(element_key(c, ctx.cfg))
+                    .or_insert((0, true));
+                *count += 1;
+                *text &= scan_xml_node(c, ctx.cfg) == XMLNodeType::Text;
+            }
+            groups.harmonized = text_only
+                .into_iter()
+                .filter(|(_, (count, text))| *count > 1 && *text)
+                .map(|(key, _)| key.into_owned())
+                .collect();
+        }
+
+        for key in ctx.cfg.array_key_rename.keys() {
+            if let Some(renamed) = renamed_array_key(parent, key, &ctx.path, ctx.cfg) {
+                groups.renamed.push((key.clone(), renamed));
+            }
+        }
+
+        if ctx.cfg.group_by_xml_lang {
+            for (key, grouping) in lang_groupings(parent, ctx.cfg) {
+                match grouping {
+                    LangGrouping::Grouped => {
+                        groups.by_lang.insert(key.into_owned());
+                    }
+                    LangGrouping::Ambiguous if ctx.cfg.strict => {
+                        let mut path = ctx.pointer();
+                        path.push(&key);
+                        let mut xml_path = ctx.xml_path();
+                        xml_path.push_element(&key, None);
+                        return Err(ConversionError::AmbiguousXmlLang { path, xml_path });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(groups)
+    }
+
+    /// Checks whether grouping children needs all of them at hand before the first is
+    /// inserted, so that `new` must be used rather than `incremental`.
+    #[cfg(feature = "xml-rs")]
+    fn needs_all_children(cfg: &ConversionConfig) -> bool {
+        cfg.child_as_attribute
+            || cfg.harmonize_sibling_types
+            || cfg.group_by_xml_lang
+            || !cfg.array_key_rename.is_empty()
+    }
+
+    /// Registers `c`, the next child of the last element of `ancestors`, and checks whether it
+    /// is within `ConversionConfig::max_occurrences`.
+    fn admit(&mut self, ancestors: &[&treexml::Element], c: &treexml::Element) -> bool {
+        let merged = is_merged(ancestors, c, self.cfg);
+        let forced = is_forced_array(ancestors, c, self.cfg);
+        let colliding = self
+            .attributes
+            .as_ref()
+            .is_some_and(|attributes| attributes.contains(element_key(c, self.cfg).as_ref()));
+        for (flagged, keys) in [
+            (merged, &mut self.merged),
+            (forced, &mut self.forced),
+            (colliding, &mut self.colliding),
+        ] {
+            let key = element_key(c, self.cfg);
+            if flagged && !keys.contains(key.as_ref()) {
+                keys.insert(key.into_owned());
+            }
+        }
+        self.cfg.max_occurrences.is_empty() || self.limits.admit(ancestors, c, self.cfg)
+    }
+
+    /// Attribute of `c` that is consumed by the grouping and must not be converted.
+    fn hidden_attribute(&self, c: &treexml::Element) -> Option<&'static str> {
+        if !self.by_lang.is_empty() && self.by_lang.contains(element_key(c, self.cfg).as_ref()) {
+            Some(XML_LANG)
+        } else {
+            None
+        }
+    }
+
+    /// Checks whether `c` is stored like an attribute, see `ConversionConfig::child_as_attribute`.
+    fn is_promoted(&self, c: &treexml::Element) -> bool {
+        !self.promoted.is_empty() && self.promoted.contains(element_key(c, self.cfg).as_ref())
+    }
+
+    /// Checks whether the child at index `i` is left out by `ConversionConfig::max_occurrences`.
+    fn is_skipped(&self, i: usize) -> bool {
+        !self.skipped.is_empty() && self.skipped.contains(&i)
+    }
+
+    /// Checks whether the children stored under `key` keep their converted values, one per
+    /// child, under `key` itself, rather than being merged, promoted, regrouped or renamed.
+    fn is_plain(&self, key: &str) -> bool {
+        !self.merged.contains(key)
+            && !self.promoted.contains(key)
+            && !self.by_lang.contains(key)
+            && !self.harmonized.contains(key)
+            && !self.colliding.contains(key)
+            && !self.renamed.iter().any(|(k, _)| k == key)
+    }
+
+    /// Checks whether `is_plain` holds for every key.
+    fn all_plain(&self) -> bool {
+        self.merged.is_empty()
+            && self.promoted.is_empty()
+            && self.by_lang.is_empty()
+            && self.harmonized.is_empty()
+            && self.colliding.is_empty()
+            && self.renamed.is_empty()
+    }
+
+    /// Inserts a converted child of the element on top of `ctx` into `data`, turning repeated
+    /// names into arrays.
+    fn insert(
+        &mut self,
+        data: &mut Map<String, Value>,
+        c: &treexml::Element,
+        v: Value,
+        ctx: &Context,
+    ) -> Result<(), ConversionError> {
+        let name = element_key(c, self.cfg);
+        if self.colliding.contains(name.as_ref()) {
+            match self.cfg.attribute_child_collision {
+                CollisionStrategy::AttributeWins => return Ok(()),
+                CollisionStrategy::ChildWins => {}
+                CollisionStrategy::Error => {
+                    let mut path = ctx.pointer();
+                    path.push(&name);
+                    return Err(ConversionError::AttributeChildCollision {
+                        key: name.into_owned(),
+                        path,
+                        xml_path: ctx.xml_path(),
+                    });
+                }
+            }
+        }
+        if self.promoted.contains(name.as_ref()) {
+            data.insert(attribute_key(&name, self.cfg), v);
+        } else if self.by_lang.contains(name.as_ref()) {
+            self.add_member(c);
+            let lang = c
+                .attributes
+                .get(XML_LANG)
+                .ok_or_else(|| internal_error(ctx, &name))?;
+            let entry = data
+                .entry(name.as_ref())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !entry.is_object() {
+                // Children replace same-named attributes, as they do without grouping.
+                *entry = Value::Object(Map::new());
+            }
+            if let Value::Object(langs) = entry {
+                langs.insert(lang.clone(), v);
+            }
+        } else if self.merged.contains(name.as_ref()) {
+            match data.get_mut(name.as_ref()) {
+                Some(existing) => {
+                    merge_values(existing, v, self.cfg.strict).map_err(|keys| {
+                        let mut path = ctx.pointer();
+                        path.push(&name);
+                        for k in &keys {
+                            path.push(k);
+                        }
+                        ConversionError::MergeConflict {
+                            path,
+                            xml_path: ctx.xml_path(),
+                        }
+                    })?;
+                }
+                None => {
+                    data.insert(name.into_owned(), v);
+                }
+            }
+        } else {
+            self.add_member(c);
+            match self.place(&name, false, ctx) {
+                Placement::Single => {
+                    data.insert(name.into_owned(), v);
+                }
+                Placement::Start => {
+                    data.insert(name.into_owned(), Value::Array(vec![v]));
+                }
+                Placement::Promote => match data.get_mut(name.as_ref()) {
+                    Some(first) => *first = Value::Array(vec![first.take(), v]),
+                    None => return Err(internal_error(ctx, &name)),
+                },
+                Placement::Append => match data.get_mut(name.as_ref()) {
+                    Some(Value::Array(items)) => items.push(v),
+                    _ => return Err(internal_error(ctx, &name)),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Registers the next value stored under the plain key `name` and tells where it goes:
+    /// values are stored in an array once there are several, or from the first one for forced
+    /// keys. `repeated` tells that another value is known to follow.
+    fn place(&mut self, name: &str, repeated: bool, ctx: &Context) -> Placement {
+        if self.vectorized.contains(name) {
+            return Placement::Append;
+        }
+        let placement = if self.firstpass.contains(name) {
+            Placement::Promote
+        } else if repeated || self.forced.contains(name) {
+            self.firstpass.insert(name.to_string());
+            Placement::Start
+        } else {
+            self.firstpass.insert(name.to_string());
+            return Placement::Single;
+        };
+        ctx.stats.borrow_mut().array_count += 1;
+        self.vectorized.insert(name.to_string());
+        placement
+    }
+
+    /// Keeps the text of `c`, stored under its key, when its value may have to be replaced.
+    fn add_member(&mut self, c: &treexml::Element) {
+        let name = element_key(c, self.cfg);
+        if self.cfg.heterogeneous_arrays == HeteroPolicy::Stringify
+            || self.harmonized.contains(name.as_ref())
+        {
+            let member = MemberText {
+                lang: c.attributes.get(XML_LANG).cloned(),
+                text: uncoerced_text(c, self.cfg),
+            };
+            self.members
+                .entry(name.into_owned())
+                .or_default()
+                .push(member);
+        }
+    }
+
+    /// Gives the values of each group of repeated text-only siblings a common type, see
+    /// `ConversionConfig::harmonize_sibling_types`.
+    fn harmonize(&self, data: &mut Map<String, Value>) {
+        for key in &self.harmonized {
+            let members = match self.members.get(key) {
+                Some(members) => members,
+                None => continue,
+            };
+            let items = match data.get_mut(key) {
+                Some(Value::Array(items)) if self.vectorized.contains(key) => {
+                    items.iter_mut().zip(members).collect()
+                }
+                Some(Value::Object(langs)) if self.by_lang.contains(key) => {
+                    let mut langs = langs.iter_mut().collect::<HashMap<_, _>>();
+                    members
+                        .iter()
+                        .filter_map(|m| Some((langs.remove(m.lang.as_ref()?)?, m)))
+                        .collect()
+                }
+                _ => Vec::new(),
+            };
+            match common_type(items.iter().map(|(v, _)| &**v)) {
+                Some(CommonType::Float) => {
+                    for (v, _) in items {
+                        if let Some(n) = v.as_f64().and_then(Number::from_f64) {
+                            *v = Value::Number(n);
+                        }
+                    }
+                }
+                Some(CommonType::String) => {
+                    for (v, m) in items {
+                        *v = m.text.clone();
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Applies `ConversionConfig::heterogeneous_arrays` to the arrays of repeated siblings.
+    fn check_array_types(
+        &self,
+        data: &mut Map<String, Value>,
+        ctx: &Context,
+    ) -> Result<(), ConversionError> {
+        if self.cfg.heterogeneous_arrays == HeteroPolicy::Allow {
+            return Ok(());
+        }
+        for key in &self.vectorized {
+            let items = match data.get_mut(key) {
+                Some(Value::Array(items)) => items,
+                _ => return Err(internal_error(ctx, key)),
+            };
+            let allow_null = self.cfg.heterogeneous_arrays_allow_null;
+            let mut types = items
+                .iter()
+                .filter(|v| !(allow_null && v.is_null()))
+                .map(schema::type_name);
+            let first = match types.next() {
+                Some(first) => first,
+                None => continue,
+            };
+            let second = match types.find(|t| *t != first) {
+                Some(second) => second,
+                None => continue,
+            };
+
+            if self.cfg.heterogeneous_arrays == HeteroPolicy::Error {
+                let key = self
+                    .renamed
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map_or(key.as_str(), |(_, renamed)| renamed);
+                let mut path = ctx.pointer();
+                path.push(key);
+                let mut xml_path = ctx.xml_path();
+                xml_path.push_element(key, None);
+                return Err(ConversionError::InconsistentSiblingTypes {
+                    first,
+                    second,
+                    path,
+                    xml_path,
+                });
+            }
+            let members = match self.members.get(key) {
+                Some(members) if members.len() == items.len() => members,
+                _ => return Err(internal_error(ctx, key)),
+            };
+            for (item, m) in items.iter_mut().zip(members) {
+                if item.is_object() || item.is_string() || (allow_null && item.is_null()) {
+                    continue;
+                }
+                *item = m.text.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the types of arrays and applies `ConversionConfig::array_key_rename` once all
+    /// children are inserted.
+    fn finish(
+        mut self,
+        data: &mut Map<String, Value>,
+        ctx: &Context,
+    ) -> Result<(), ConversionError> {
+        for (key, _) in &self.limits.skipped {
+            if self.firstpass.contains(key) && !self.vectorized.contains(key) {
+                if let Some(v) = data.get_mut(key) {
+                    *v = Value::Array(vec![v.take()]);
+                    ctx.stats.borrow_mut().array_count += 1;
+                    self.vectorized.insert(key.clone());
+                }
+            }
+        }
+        self.harmonize(data);
+        self.check_array_types(data, ctx)?;
+        for (key, renamed) in self.renamed {
+            if self.vectorized.contains(&key) {
+                if let Some(v) = data.remove(&key) {
+                    data.insert(renamed.to_string(), v);
+                }
+            }
+        }
+        if let Some((key, counts)) = self.limits.truncated_counts(self.cfg) {
+            data.insert(key, counts);
+        }
+        Ok(())
+    }
+}
+
+/// Converts a child of the element on top of `ctx`, honoring its sibling grouping.
+fn convert_child<'a>(
+    i: usize,
+    c: &'a treexml::Element,
+    groups: &SiblingGroups<'a>,
+    ctx: &mut Context<'a>,
+) -> Result<Option<Value>, ConversionError> {
+    if groups.is_skipped(i) {
+        return Ok(None);
+    }
+    if groups.is_promoted(c) {
+        return Ok(Some(uncoerced_text(c, ctx.cfg)));
+    }
+    ctx.hidden_attribute = groups.hidden_attribute(c);
+    convert_node_aux(c, ctx)
+}
+
+/// Checks whether an attribute value is empty, see `ConversionConfig::empty_attribute_as`.
+fn is_empty_attribute(value: &str, cfg: &ConversionConfig) -> bool {
+    if cfg.trim_text {
+        value.trim().is_empty()
+    } else {
+        value.is_empty()
+    }
+}
+
+/// Checks whether the attribute `name` survives the attribute filters of `cfg`.
+fn is_converted_attribute(name: &str, value: &str, cfg: &ConversionConfig) -> bool {
+    !cfg.drop_attributes
+        && !cfg.skip_attributes.iter().any(|skipped| skipped == name)
+        && !(cfg.strip_xmlns_attributes && (name == "xmlns" || name.starts_with("xmlns:")))
+        && !(cfg.empty_attribute_as == EmptyAttribute::Skip && is_empty_attribute(value, cfg))
+}
+
+fn has_converted_attributes(e: &treexml::Element, cfg: &ConversionConfig) -> bool {
+    e.attributes
+        .iter()
+        .any(|(k, v)| is_converted_attribute(k, v, cfg))
+}
+
+/// Attributes of `e` that take part in the conversion, sorted by name.
+fn node_attributes<'a>(
+    e: &'a treexml::Element,
+    hidden: Option<&str>,
+    cfg: &ConversionConfig,
+) -> Vec<(&'a str, &'a str)> {
+    let mut attributes = e
+        .attributes
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .filter(|(k, v)| Some(*k) != hidden && is_converted_attribute(k, v, cfg))
+        .collect::<Vec<_>>();
+    attributes.sort_unstable();
+    attributes
+}
+
+/// Checks whether `e` is removed from the output according to `ConversionConfig::collapse_wrappers`.
+fn is_collapsed_wrapper(e: &treexml::Element, cfg: &ConversionConfig) -> bool {
+    e.children.len() == 1
+        && !has_converted_attributes(e, cfg)
+        && e.text.is_none()
+        && e.cdata.is_none()
+        && cfg.collapse_wrappers.contains(&e.name)
+}
+
+/// Key under which an element is stored in its parent's object, before escaping.
+fn raw_element_key<'a>(mut e: &'a treexml::Element, cfg: &ConversionConfig) -> Cow<'a, str> {
+    if cfg.collapsed_wrapper_key == WrapperKey::Child {
+        while is_collapsed_wrapper(e, cfg) {
+            e = &e.children[0];
+        }
+    }
+    match (cfg.mode, &e.prefix) {
+        (ConversionMode::GData, Some(prefix)) => Cow::Owned(format!("{}${}", prefix, e.name)),
+        (ConversionMode::Standard, Some(prefix))
+            if cfg.namespace_prefixes == PrefixHandling::Keep =>
+        {
+            Cow::Owned(format!(
+                "{}{}{}",
+                prefix, cfg.attribute_namespace_separator, e.name
+            ))
+        }
+        (ConversionMode::Standard, _) => separate_prefix(&e.name, cfg),
+        _ => Cow::Borrowed(&e.name),
+    }
+}
+
+/// Key under which an element is stored in its parent's object.
+fn element_key<'a>(e: &'a treexml::Element, cfg: &ConversionConfig) -> Cow<'a, str> {
+    escape_reserved(raw_element_key(e, cfg), cfg)
+}
+
+/// Key under which text content is stored next to attributes or children.
+fn text_key(cfg: &ConversionConfig) -> &str {
+    match (&cfg.text_key, cfg.mode) {
+        (Some(key), _) => key,
+        (None, ConversionMode::Standard) => "#text",
+        (None, ConversionMode::GData) => "$t",
+    }
+}
+
+/// Key under which the text of an element of type `kind` is stored, see
+/// `ConversionConfig::text_key_for_type`.
+fn text_key_for(cfg: &ConversionConfig, kind: XMLNodeType) -> &str {
+    cfg.text_key_for_type
+        .get(&kind)
+        .map_or_else(|| text_key(cfg), String::as_str)
+}
+
+/// Checks whether `key` is used for structural data, see `ConversionConfig::reserved_key_escape`.
+fn is_reserved_key(key: &str, cfg: &ConversionConfig) -> bool {
+    key == text_key(cfg)
+        || cfg.text_key_for_type.values().any(|k| k == key)
+        || (cfg.separate_cdata && key == cfg.cdata_key)
+        || cfg.attributes_key.as_deref() == Some(key)
+        || cfg.root_element_name_key.as_deref() == Some(key)
+        || (cfg.inject_source_tag && key == cfg.source_tag_key)
+        || (cfg.namespace_key.is_some()
+            && (cfg.namespace_key.as_deref() == Some(key) || key == ATTRIBUTE_NAMESPACES_KEY))
+        || cfg.truncated_count_key.as_deref() == Some(key)
+        || cfg.comment_key.as_deref() == Some(key)
+        || cfg.pi_key.as_deref() == Some(key)
+        || (cfg.include_positions && key == cfg.position_key)
+}
+
+fn escape_reserved<'a>(key: Cow<'a, str>, cfg: &ConversionConfig) -> Cow<'a, str> {
+    if is_reserved_key(&key, cfg) {
+        Cow::Owned(format!("{}{}", cfg.reserved_key_escape, key))
+    } else {
+        key
+    }
+}
+
+/// In strict mode, fails if an attribute or child of the element on top of `ctx` would be stored
+/// under a reserved key.
+fn check_reserved_keys(attributes: &[(&str, &str)], ctx: &Context) -> Result<(), ConversionError> {
+    let e = match ctx.path.last() {
+        Some(e) if ctx.cfg.strict => e,
+        _ => return Ok(()),
+    };
+    let reserved_attribute = attributes
+        .iter()
+        .map(|(k, _)| (raw_attribute_key(k, ctx.cfg), k))
+        .find(|(key, _)| is_reserved_key(key, ctx.cfg));
+    if let Some((key, name)) = reserved_attribute {
+        let mut xml_path = ctx.xml_path();
+        xml_path.push_attribute(name);
+        return Err(ConversionError::ReservedKey {
+            key,
+            path: ctx.pointer(),
+            xml_path,
+        });
+    }
+
+    let reserved_child = e
+        .children
+        .iter()
+        .map(|c| (raw_element_key(c, ctx.cfg), c))
+        .find(|(key, _)| is_reserved_key(key, ctx.cfg));
+    if let Some((key, c)) = reserved_child {
+        let mut xml_path = ctx.xml_path();
+        push_xml_step(&mut xml_path, Some(e), c);
+        return Err(ConversionError::ReservedKey {
+            key: key.into_owned(),
+            path: ctx.pointer(),
+            xml_path,
+        });
+    }
+    Ok(())
+}
+
+/// Counts `e`, the last element of `ctx.path`, in the conversion stats.
+fn count_element(e: &treexml::Element, ctx: &mut Context) {
+    let depth = ctx.path.len();
+    let stats = ctx.stats.get_mut();
+    stats.element_count += 1;
+    stats.max_depth = stats.max_depth.max(depth);
+    stats.text_bytes += e
+        .text
+        .iter()
+        .chain(&e.cdata)
+        .map(String::len)
+        .sum::<usize>();
+}
+
+/// Converts the attributes of `e`, the last element of `ctx.path`, other than `hidden` into
+/// object entries. Also returns the node type of `e` given those attributes and their number.
+fn convert_attributes(
+    e: &treexml::Element,
+    hidden: Option<&str>,
+    ctx: &Context,
+) -> Result<(Map<String, Value>, XMLNodeType, usize), ConversionError> {
+    let attributes = node_attributes(e, hidden, ctx.cfg);
+    check_reserved_keys(&attributes, ctx)?;
+    let kind = classify_with_attributes(e, !attributes.is_empty(), ctx.cfg);
+    let count = attributes.len();
+    let node = NodeContext::new(&ctx.path[..ctx.path.len() - 1], e);
+    Ok((attribute_entries(attributes, &node, ctx.cfg), kind, count))
+}
+
+/// Counts the `count` attributes converted to the entries `data` in the conversion stats.
+fn count_attributes(data: &Map<String, Value>, count: usize, ctx: &mut Context) {
+    let stats = ctx.stats.get_mut();
+    stats.attribute_count += count;
+    stats.count_coercions_in(data);
+}
+
+fn convert_node_aux<'a>(
+    e: &'a treexml::Element,
+    ctx: &mut Context<'a>,
+) -> Result<Option<Value>, ConversionError> {
+    convert_classified(e, ctx).map(|(value, _)| value)
+}
+
+/// Same as `convert_node_aux`, also returning the node type that determined the conversion:
+/// the one of the only child of a collapsed wrapper, and the one of `e` without the attribute
+/// consumed by its parent. Elements truncated by `max_depth` keep their own.
+fn convert_classified<'a>(
+    e: &'a treexml::Element,
+    ctx: &mut Context<'a>,
+) -> Result<(Option<Value>, XMLNodeType), ConversionError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::trace_span!(
+        "convert_node",
+        element = %e.name,
+        node_type = ?scan_xml_node(e, ctx.cfg)
+    )
+    .entered();
+    let hidden = ctx.hidden_attribute.take();
+    let notes = ctx.annotations.take();
+    let uris = ctx.namespaces.take();
+    ctx.path.push(e);
+    if ctx.cfg.max_depth.is_some_and(|max| ctx.path.len() > max) {
+        if !ctx.cfg.truncate_at_max_depth {
+            return Err(ConversionError::DepthExceeded {
+                path: ctx.pointer(),
+                xml_path: ctx.xml_path(),
+            });
+        }
+        ctx.path.pop();
+        let placeholder = ctx.cfg.truncation_placeholder.clone();
+        return Ok((Some(placeholder), scan_xml_node(e, ctx.cfg)));
+    }
+    count_element(e, ctx);
+    if is_collapsed_wrapper(e, ctx.cfg) {
+        ctx.annotations = notes.and_then(|n| n.children.first());
+        ctx.namespaces = uris.and_then(|u| u.children.first());
+        let converted = convert_classified(&e.children[0], ctx)?;
+        ctx.path.pop();
+        return Ok(converted);
+    }
+    let (mut data, kind, count) = convert_attributes(e, hidden, ctx)?;
+    // Elements classified as `Text` or `Empty` have no attributes, so `data` stays empty.
+    count_attributes(&data, count, ctx);
+    let default = default_value(&ctx.path[..ctx.path.len() - 1], e, ctx.cfg);
+    let attribute_keys = attribute_keys(&data, ctx.cfg);
+    let value = match kind {
+        XMLNodeType::Parent | XMLNodeType::SemiStructured => {
+            let mut groups = SiblingGroups::new(ctx)?;
+
+            for (i, c) in e.children.iter().enumerate() {
+                ctx.annotations = notes.and_then(|n| n.children.get(i));
+                ctx.namespaces = uris.and_then(|u| u.children.get(i));
+                if let Some(v) = convert_child(i, c, &groups, ctx)? {
+                    groups.insert(&mut data, c, v, ctx)?;
+                }
+            }
+            ctx.annotations = None;
+            ctx.namespaces = None;
+            groups.finish(&mut data, ctx)?;
+            if kind == XMLNodeType::SemiStructured {
+                insert_text_contents(&mut data, e, kind, ctx)?;
+            }
+            Some(Value::Object(position_attributes(
+                data,
+                &attribute_keys,
+                ctx.cfg,
+            )))
+        }
+        XMLNodeType::Text if is_blank(e, ctx.cfg) && default.is_some() => default.cloned(),
+        XMLNodeType::Text => Some(match ctx.cfg.mode {
+            ConversionMode::Standard => parse_text_contents(e, ctx)?,
+            ConversionMode::GData => Value::Object(
+                vec![(
+                    text_key_for(ctx.cfg, kind).to_string(),
+                    parse_text_contents(e, ctx)?,
+                )]
+                .into_iter()
+                .collect(),
+            ),
+        }),
+        XMLNodeType::Attributes => {
+            if let Some(v) = default.filter(|_| ctx.cfg.defaults_for_attribute_only) {
+                data.insert(text_key_for(ctx.cfg, kind).to_string(), v.clone());
+            }
+            Some(Value::Object(position_attributes(
+                data,
+                &attribute_keys,
+                ctx.cfg,
+            )))
+        }
+        XMLNodeType::TextAndAttributes => {
+            insert_text_contents(&mut data, e, kind, ctx)?;
+            Some(Value::Object(position_attributes(
+                data,
+                &attribute_keys,
+                ctx.cfg,
+            )))
+        }
+        XMLNodeType::Empty => empty_value(default, ctx.cfg),
+    };
+    let value = insert_ensured_arrays(value, e, kind, ctx);
+    ctx.path.pop();
+    let value = tag_namespace(value, e, uris, ctx.cfg);
+    let value = annotate(value, notes, ctx.cfg);
+    Ok((tag_source(value, e, ctx.cfg), kind))
+}
+
+/// Stores the name of `e` in its object-valued conversion result if
+/// `ConversionConfig::inject_source_tag` is set.
+fn tag_source(
+    mut value: Option<Value>,
+    e: &treexml::Element,
+    cfg: &ConversionConfig,
+) -> Option<Value> {
+    if let (true, Some(Value::Object(data))) = (cfg.inject_source_tag, &mut value) {
+        data.insert(cfg.source_tag_key.clone(), Value::String(e.name.clone()));
+    }
+    value
+}
+
+/// Stores the namespace URIs of `e` and its attributes in its conversion result if
+/// `ConversionConfig::namespace_key` is set, promoting scalars to objects. `uris` are the
+/// namespaces recorded for `e` when resolving them.
+fn tag_namespace(
+    value: Option<Value>,
+    e: &treexml::Element,
+    uris: Option<&namespaces::ElementUris>,
+    cfg: &ConversionConfig,
+) -> Option<Value> {
+    let (key, uris) = match (&cfg.namespace_key, uris) {
+        (Some(key), Some(uris)) => (key, uris),
+        _ => return value,
+    };
+    let attributes = uris
+        .attributes
+        .iter()
+        .filter(|(name, _)| {
+            let value = e.attributes.get(name).map_or("", String::as_str);
+            is_converted_attribute(name, value, cfg)
+        })
+        .map(|(name, uri)| (attribute_key(name, cfg), Value::String(uri.clone())))
+        .collect::<Map<_, _>>();
+    if uris.element.is_none() && attributes.is_empty() {
+        return value;
+    }
+
+    let mut data = match value? {
+        Value::Object(data) => data,
+        Value::Null => Map::new(),
+        v => vec![(text_key(cfg).to_string(), v)].into_iter().collect(),
+    };
+    if let Some(uri) = &uris.element {
+        data.insert(key.clone(), Value::String(uri.clone()));
+    }
+    if !attributes.is_empty() {
+        data.insert(ATTRIBUTE_NAMESPACES_KEY.into(), Value::Object(attributes));
+    }
+    Some(Value::Object(data))
+}
+
+/// Converts treexml::Element into a serde_json hashmap. The latter can be wrapped in Value::Object.
+pub fn node2object(e: &treexml::Element) -> Map<String, Value> {
+    node2object_with_config(e, &ConversionConfig::default())
+        .expect("default configuration never fails")
+}
+
+/// Same as `node2object`, but with conversion controlled by `cfg`.
+pub fn node2object_with_config(
+    e: &treexml::Element,
+    cfg: &ConversionConfig,
+) -> Result<Map<String, Value>, ConversionError> {
+    convert_recorded(e, None, Recorded::default(), cfg).map(|(data, _)| data)
+}
+
+/// Same as `node2object_with_stats` for a tree read by a backend that kept what `recorded`
+/// lists of the source document, in `notes` for what treexml has no place for.
+pub(crate) fn convert_recorded(
+    e: &treexml::Element,
+    notes: Option<&Annotations>,
+    recorded: Recorded,
+    cfg: &ConversionConfig,
+) -> Result<(Map<String, Value>, ConversionStats), ConversionError> {
+    let mut sink = ValueSink::new();
+    let stats = events::drive_recorded(e, notes, recorded, &mut sink, cfg)?;
+    match sink.finish() {
+        Some(Value::Object(data)) => Ok((data, stats)),
+        _ => unreachable!("documents are reported as objects"),
+    }
+}
+
+/// Same as `node2object_with_config`, but also returns counters collected during the
+/// conversion, see `ConversionStats`.
+pub fn node2object_with_stats(
+    e: &treexml::Element,
+    cfg: &ConversionConfig,
+) -> Result<(Map<String, Value>, ConversionStats), ConversionError> {
+    convert_recorded(e, None, Recorded::default(), cfg)
+}
+
+/// Converts the root of a tree whose namespaces are already resolved.
+pub(crate) fn convert_root(
+    resolved: &namespaces::Resolved,
+    cfg: &ConversionConfig,
+) -> Result<Map<String, Value>, ConversionError> {
+    convert_root_with_stats(resolved, None, cfg).map(|(data, _)| data)
+}
+
+fn convert_root_with_stats<'a>(
+    resolved: &'a namespaces::Resolved,
+    notes: Option<&'a Annotations>,
+    cfg: &'a ConversionConfig,
+) -> Result<(Map<String, Value>, ConversionStats), ConversionError> {
+    let e = &*resolved.root;
+    let mut ctx = Context::new(cfg);
+    ctx.namespaces = Some(&resolved.uris);
+    ctx.annotations = notes;
+    let value = convert_node_aux(e, &mut ctx)?;
+    let mut data = wrap_root(e, value, cfg);
+    references::resolve_references(&mut data, e, cfg)?;
+    Ok((data, ctx.stats.into_inner()))
+}
+
+/// Converts each element of `siblings` as if they were children of one parent and returns
+/// their values as an array, in slice order. The elements may have different names and come
+/// from different documents; names are not part of the output. Elements that convert to no
+/// value, such as omitted empty elements, are left out. The result stays an array with
+/// `arrays_as_indexed_objects`, which only applies within the items.
+pub fn convert_siblings_as_array(
+    siblings: &[&treexml::Element],
+    cfg: &ConversionConfig,
+) -> Result<Value, ConversionError> {
+    check_options(cfg, Recorded::default())?;
+    let mut items = Vec::with_capacity(siblings.len());
+    for e in siblings {
+        items.extend(convert_node_aux(e, &mut Context::new(cfg))?);
+    }
+    let mut value = Value::Array(items);
+    if let Some(key) = &cfg.sort_arrays_by {
+        sort::sort_arrays(&mut value, key);
+    }
+    if let (true, Value::Array(items)) = (cfg.arrays_as_indexed_objects, &mut value) {
+        for item in items {
+            indexed::index_arrays(item, cfg.indexed_object_base);
+        }
+    }
+    Ok(value)
+}
+
+/// Converts an XML fragment holding any number of top-level elements, such as a chunk of a
+/// streaming protocol, into an array of their values in document order. The fragment is
+/// parsed inside a synthetic `<_root>` element and its elements are converted as with
+/// `convert_siblings_as_array`. Text between the top-level elements is ignored.
+///
+/// A leading XML declaration is skipped, but other prolog content such as a doctype can't be
+/// wrapped and fails to parse. The fragment mustn't contain a `</_root>` end tag either, since
+/// it would close the synthetic element.
+pub fn fragment_to_array(
+    xml_fragment: &str,
+    cfg: &ConversionConfig,
+) -> Result<Value, FragmentError> {
+    let wrapped = format!("<_root>{}</_root>", skip_xml_declaration(xml_fragment));
+    let root = treexml::Document::parse(wrapped.as_bytes())
+        .map_err(FragmentError::Parse)?
+        .root
+        .expect("the synthetic root is always present");
+    let children = root.children.iter().collect::<Vec<_>>();
+    convert_siblings_as_array(&children, cfg).map_err(FragmentError::Conversion)
+}
+
+/// Part of `text` after its XML declaration, if it starts with one.
+fn skip_xml_declaration(text: &str) -> &str {
+    let start = text.trim_start_matches('\u{feff}').trim_start();
+    match start.strip_prefix("<?xml") {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_whitespace()) => {
+            rest.find("?>").map_or(text, |end| &rest[end + 2..])
+        }
+        _ => text,
+    }
+}
+
+/// Converts `e` and deserializes the resulting object into `T`, for callers that don't need the
+/// intermediate `Value`.
+pub fn node_to<T: DeserializeOwned>(e: &treexml::Element) -> Result<T, serde_json::Error> {
+    serde_json::from_value(Value::Object(node2object(e)))
+}
+
+/// Same as `node_to`, but with conversion controlled by `cfg`. Conversion errors are reported
+/// as custom `serde_json::Error`s carrying the `ConversionError` message.
+pub fn node_to_with_config<T: DeserializeOwned>(
+    e: &treexml::Element,
+    cfg: &ConversionConfig,
+) -> Result<T, serde_json::Error> {
+    let data = node2object_with_config(e, cfg).map_err(serde::de::Error::custom)?;
+    serde_json::from_value(Value::Object(data))
+}
+
+/// Turns the converted root value into an object, promoting scalars to `{"#text": value}`.
+fn root_object(
+    e: &treexml::Element,
+    value: Option<Value>,
+    cfg: &ConversionConfig,
+) -> Map<String, Value> {
+    let mut data = match value {
+        Some(Value::Object(data)) => data,
+        Some(v) => vec![(text_key(cfg).to_string(), v)].into_iter().collect(),
+        None => Map::new(),
+    };
+    if let Some(key) = &cfg.root_element_name_key {
+        data.insert(key.clone(), Value::String(e.name.clone()));
+    }
+    if cfg.inject_source_tag {
+        data.insert(cfg.source_tag_key.clone(), Value::String(e.name.clone()));
+    }
+    data
+}
+
+/// Checks whether some option reshapes the output after the elements are converted, so that
+/// parts of it can't be told apart from the element tree.
+fn reshapes_output(cfg: &ConversionConfig) -> bool {
+    cfg.sort_arrays_by.is_some() || cfg.arrays_as_indexed_objects || cfg.id_references.is_some()
+}
+
+/// Applies the options that reshape the converted value as a whole: array sorting, then
+/// replacing arrays by indexed objects.
+pub(crate) fn finish_value(v: &mut Value, cfg: &ConversionConfig) {
+    if let Some(key) = &cfg.sort_arrays_by {
+        sort::sort_arrays(v, key);
+    }
+    if cfg.arrays_as_indexed_objects {
+        indexed::index_arrays(v, cfg.indexed_object_base);
+    }
+}
+
+/// Builds the top-level output map from the converted root value.
+fn wrap_root(
+    e: &treexml::Element,
+    mut value: Option<Value>,
+    cfg: &ConversionConfig,
+) -> Map<String, Value> {
+    if let Some(v) = &mut value {
+        finish_value(v, cfg);
+    }
+    if !cfg.include_root {
+        return root_object(e, value, cfg);
+    }
+
+    let value = match cfg.root_element_name_key {
+        Some(_) => Value::Object(root_object(e, value, cfg)),
+        None => value.unwrap_or(Value::Null),
+    };
+    let mut data = Map::new();
+    data.insert(element_key(e, cfg).into_owned(), value);
+    data
+}
+
+/// Same as `node2object_with_config`, with the entries of `context` (e.g. `"source"` or
+/// `"schema_version"`) merged into the root-level output map. Clashing keys are resolved
+/// according to `ConversionConfig::context_conflict`.
+pub fn node2object_with_context(
+    e: &treexml::Element,
+    context: Map<String, Value>,
+    cfg: &ConversionConfig,
+) -> Result<Map<String, Value>, ConversionError> {
+    let mut data = node2object_with_config(e, cfg)?;
+    for (k, v) in context {
+        if data.contains_key(&k) {
+            match cfg.context_conflict {
+                ContextConflict::ContextWins => {}
+                ContextConflict::XmlWins => continue,
+                ContextConflict::Error => {
+                    let mut path = JsonPointer::default();
+                    path.push(&k);
+                    let mut xml_path = XmlPath::default();
+                    push_xml_step(&mut xml_path, None, e);
+                    return Err(ConversionError::ContextConflict {
+                        key: k,
+                        path,
+                        xml_path,
+                    });
+                }
+            }
+        }
+        data.insert(k, v);
+    }
+    Ok(data)
+}
+
+/// Converts the root element of `doc` like `node2object_with_config`. With
+/// `ConversionConfig::include_xml_declaration` set, the XML declaration is added to the
+/// root-level output map under `"@_xml"`. A document without a root element converts to an
+/// empty map, or to the declaration alone.
+pub fn document_to_object(
+    doc: &treexml::Document,
+    cfg: &ConversionConfig,
+) -> Result<Map<String, Value>, ConversionError> {
+    let mut data = match &doc.root {
+        Some(e) => node2object_with_config(e, cfg)?,
+        None => Map::new(),
+    };
+    if cfg.include_xml_declaration {
+        let version = match doc.version {
+            treexml::XmlVersion::Version10 => "1.0",
+            treexml::XmlVersion::Version11 => "1.1",
+        };
+        let declaration = vec![
+            ("version".to_string(), Value::String(version.to_string())),
+            ("encoding".to_string(), Value::String(doc.encoding.clone())),
+            ("standalone".to_string(), Value::Null),
+        ];
+        data.insert(
+            "@_xml".to_string(),
+            Value::Object(declaration.into_iter().collect()),
+        );
+    }
+    Ok(data)
+}
+
+/// Converts `e` and nests the result in one object per ancestor, outermost first:
+/// `{"grandparent": {"parent": {"element": {...}}}}`. Ancestors only contribute their keys;
+/// their attributes and other children are left out.
+///
+/// `ancestors` should run from the root element down to the parent of `e`, so that path-based
+/// options and error locations resolve as they would when converting the whole document.
+pub fn element_with_ancestors_to_object<'a>(
+    ancestors: &[&'a treexml::Element],
+    e: &'a treexml::Element,
+    cfg: &ConversionConfig,
+) -> Result<Map<String, Value>, ConversionError> {
+    check_options(cfg, Recorded::default())?;
+    let mut ctx = Context::new(cfg);
+    ctx.path.extend_from_slice(ancestors);
+    let mut value = convert_node_aux(e, &mut ctx)?.unwrap_or(Value::Null);
+    finish_value(&mut value, cfg);
+
+    let mut data = Map::new();
+    data.insert(element_key(e, cfg).into_owned(), value);
+    for ancestor in ancestors.iter().rev() {
+        let mut outer = Map::new();
+        outer.insert(element_key(ancestor, cfg).into_owned(), Value::Object(data));
+        data = outer;
+    }
+    Ok(data)
+}
+
+/// Splits a path segment like `entry[2]` into the name and the 1-based index.
+fn parse_segment(segment: &str) -> Option<(&str, usize)> {
+    match segment.strip_suffix(']') {
+        Some(rest) => {
+            let (name, index) = rest.split_once('[')?;
+            Some((name, index.parse().ok().filter(|&i| i > 0)?))
+        }
+        None => Some((segment, 1)),
+    }
+}
+
+/// Converts only the subtree of `e` addressed by `path` and returns its value, as it would
+/// appear in the output of `node2object_with_config`.
+///
+/// `path` is a slash-separated list of element names starting with the name of `e`, e.g.
+/// `report/body/rows`. A segment like `entry[2]` selects the second of the same-named children
+/// (indices start at 1, as in XPath); plain segments select the first. Path-based options such
+/// as `base64_paths` keep matching against the full path from `e`.
+pub fn node2object_at(
+    e: &treexml::Element,
+    path: &str,
+    cfg: &ConversionConfig,
+) -> Result<Value, ConversionError> {
+    check_options(cfg, Recorded::default())?;
+    let mut ctx = Context::new(cfg);
+    let mut candidates = std::slice::from_ref(e);
+    for segment in path.trim_start_matches('/').split('/') {
+        let found = parse_segment(segment)
+            .and_then(|(name, index)| candidates.iter().filter(|c| c.name == name).nth(index - 1));
+        match found {
+            Some(c) => {
+                ctx.path.push(c);
+                candidates = &c.children;
+            }
+            None => {
+                return Err(ConversionError::NotFound {
+                    segment: segment.into(),
+                    path: ctx.pointer(),
+                    xml_path: ctx.xml_path(),
+                })
+            }
+        }
+    }
+
+    let target = ctx.path.pop().expect("paths have at least one segment");
+    let mut value = convert_node_aux(target, &mut ctx)?.unwrap_or(Value::Null);
+    finish_value(&mut value, cfg);
+    Ok(value)
+}
+
+/// Converts `e` on its own, like a child of another element, and returns its value together
+/// with the node type that determined the conversion, which is the one of its child if `e` is
+/// a collapsed wrapper. The value is `None` for elements that convert to nothing, such as
+/// omitted empty elements.
+pub fn convert_node_with_metadata(
+    e: &treexml::Element,
+    cfg: &ConversionConfig,
+) -> Result<(Option<Value>, XMLNodeType), ConversionError> {
+    check_options(cfg, Recorded::default())?;
+    let (mut value, kind) = convert_classified(e, &mut Context::new(cfg))?;
+    if let Some(v) = &mut value {
+        finish_value(v, cfg);
+    }
+    Ok((value, kind))
+}
+
+/// Returns all direct children of `e` with the given tag name, in document order.
+pub fn element_children_of_type<'a>(
+    e: &'a treexml::Element,
+    tag_name: &str,
+) -> Vec<&'a treexml::Element> {
+    e.children.iter().filter(|c| c.name == tag_name).collect()
+}
+
+/// Converts all direct children of `e` with the given tag name into a JSON array.
+/// Empty children are represented as `null`.
+pub fn convert_children_of_type(
+    e: &treexml::Element,
+    tag_name: &str,
+    cfg: &ConversionConfig,
+) -> Result<Value, ConversionError> {
+    check_options(cfg, Recorded::default())?;
+    let mut ctx = Context::with_parent(cfg, e);
+    let mut value = element_children_of_type(e, tag_name)
+        .into_iter()
+        .map(|c| Ok(convert_node_aux(c, &mut ctx)?.unwrap_or(Value::Null)))
+        .collect::<Result<_, _>>()
+        .map(Value::Array)?;
+    finish_value(&mut value, cfg);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn spec_types() {
+        for (src, scan_result, conv_result) in [
+            (r#"<e/>"#, XMLNodeType::Empty, json!({ "e": null })),
+            (r#"<e> </e>"#, XMLNodeType::Empty, json!({ "e": null })),
+            (r#"<e>text</e>"#, XMLNodeType::Text, json!({"e": "text"})),
+            (
+                r#"<e name="value"/>"#,
+                XMLNodeType::Attributes,
+                json!({ "e": {"@name": "value"} }),
+            ),
+            (
+                r#"<e name="value">text</e>"#,
+                XMLNodeType::TextAndAttributes,
+                json!({ "e": { "@name": "value", "#text": "text" } }),
+            ),
+            (
+                r#"<e> <a>text</a> <b>text</b> </e>"#,
+                XMLNodeType::Parent,
+                json!({ "e": { "a": "text", "b": "text" } }),
+            ),
+            (
+                r#"<e> <a>text</a> <a>text</a> </e>"#,
+                XMLNodeType::Parent,
+                json!({ "e": { "a": ["text", "text"] } }),
+            ),
+            (
+                r#"<e>some <a>text</a></e>"#,
+                XMLNodeType::SemiStructured,
+                json!({ "e": { "a": "text", "#text": "some " } }),
+            ),
+        ] {
+            let fixture = treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+
+            assert_eq!(scan_result, classify(&fixture));
+            assert_eq!(
+                is_structured(&fixture),
+                matches!(
+                    scan_result,
+                    XMLNodeType::Parent | XMLNodeType::SemiStructured
+                )
+            );
+            assert_eq!(conv_result, Value::Object(node2object(&fixture)));
+        }
+    }
+
+    #[test]
+    fn spec_examples() {
+        for (src, conv_result) in [(
+            r#"<e><a>some</a><b>textual</b><a>content</a></e>"#,
+            json!({ "e": { "a": [ "some", "content" ], "b": "textual"} }),
+        )] {
+            let fixture = treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+
+            assert_eq!(conv_result, Value::Object(node2object(&fixture)));
+        }
+    }
+
+    #[test]
+    fn children_of_type() {
+        let dom_root =
+            treexml::Document::parse(r#"<e><a>1</a><b>text</b><a/><a>3</a></e>"#.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+
+        assert_eq!(element_children_of_type(&dom_root, "a").len(), 3);
+        assert!(element_children_of_type(&dom_root, "c").is_empty());
+        assert_eq!(
+            convert_children_of_type(&dom_root, "a", &ConversionConfig::default()).unwrap(),
+            json!([1.0, null, 3.0])
+        );
+    }
+
+    #[test]
+    fn preserve_attributes_parents() {
+        let dom_root = treexml::Document::parse(
+            r#"
+        <a pizza="hotdog">
+          <b frenchfry="milkshake">
+            <c>scotch</c>
+          </b>
+        </a>
+    "#
+            .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let json_result = Value::Object(node2object(&dom_root));
+        let expected = json!({
+            "a": json!({
+                "@pizza": "hotdog",
                 "b": json!({
                     "@frenchfry": "milkshake",
                     "c":  "scotch"
                 })
             })
         });
-        assert_eq!(json_result, expected);
+        assert_eq!(json_result, expected);
+    }
+
+    #[test]
+    fn base64_payloads() {
+        let dom_root = treexml::Document::parse(
+            r#"<doc><blob>aGVs
+            bG8=</blob><other>aGVsbG8=</other></doc>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        for (handling, expected) in [
+            (Base64Handling::Tagged, json!({ "#base64": "aGVsbG8=" })),
+            (
+                Base64Handling::Reencode(BinaryEncoding::Hex),
+                json!({ "#hex": "68656c6c6f" }),
+            ),
+            (
+                Base64Handling::Reencode(BinaryEncoding::Base64Url),
+                json!({ "#base64url": "aGVsbG8=" }),
+            ),
+            (
+                Base64Handling::Metadata,
+                json!({ "#binary": { "bytes": 5 } }),
+            ),
+        ] {
+            let cfg = ConversionConfig {
+                base64_paths: vec![("doc/blob".to_string(), handling)]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            };
+            assert_eq!(
+                Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+                json!({ "doc": { "blob": expected, "other": "aGVsbG8=" } })
+            );
+        }
+
+        // The path wins over the name, whatever the order of the map.
+        let cfg = ConversionConfig {
+            base64_paths: vec![
+                ("blob".to_string(), Base64Handling::Tagged),
+                ("doc/blob".to_string(), Base64Handling::Metadata),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            node2object_with_config(&dom_root, &cfg).unwrap()["doc"]["blob"],
+            json!({ "#binary": { "bytes": 5 } })
+        );
+    }
+
+    #[test]
+    fn base64_invalid_payload() {
+        let dom_root =
+            treexml::Document::parse(r#"<doc><blob>not base64!</blob></doc>"#.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+
+        let mut cfg = ConversionConfig {
+            base64_paths: vec![("blob".to_string(), Base64Handling::Tagged)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "doc": { "blob": "not base64!" } })
+        );
+
+        cfg.strict = true;
+        assert_eq!(
+            node2object_with_config(&dom_root, &cfg)
+                .unwrap_err()
+                .path()
+                .as_str(),
+            "/doc/blob"
+        );
+    }
+
+    #[test]
+    fn context_injection() {
+        let dom_root = treexml::Document::parse(r#"<e><a>1</a></e>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let context = json!({ "source": "feed", "e": "shadowed" })
+            .as_object()
+            .unwrap()
+            .clone();
+
+        for (conflict, expected) in [
+            (
+                ContextConflict::XmlWins,
+                Ok(json!({ "source": "feed", "e": { "a": 1.0 } })),
+            ),
+            (
+                ContextConflict::ContextWins,
+                Ok(json!({ "source": "feed", "e": "shadowed" })),
+            ),
+            (ContextConflict::Error, Err("/e".to_string())),
+        ] {
+            let cfg = ConversionConfig {
+                context_conflict: conflict,
+                ..Default::default()
+            };
+            assert_eq!(
+                node2object_with_context(&dom_root, context.clone(), &cfg)
+                    .map(Value::Object)
+                    .map_err(|e| e.path().to_string()),
+                expected
+            );
+        }
+    }
+
+    #[cfg(feature = "dates")]
+    #[test]
+    fn date_detection() {
+        let dom_root = treexml::Document::parse(
+            r#"<e at="2021-03-04"><iso>2021-03-04T05:06:07+00:00</iso><custom>04/03/2021</custom><compact>20210304</compact><other>tomorrow</other><n>42</n></e>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            detect_dates: true,
+            date_patterns: vec!["%d/%m/%Y".into(), "%Y%m%d".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "e": {
+                    "@at": "2021-03-04",
+                    "iso": "2021-03-04T05:06:07Z",
+                    "custom": "2021-03-04",
+                    "compact": "2021-03-04",
+                    "other": "tomorrow",
+                    "n": 42.0
+                }
+            })
+        );
+
+        assert_eq!(
+            Value::Object(node2object(&dom_root))["e"]["compact"],
+            json!(20210304.0)
+        );
+    }
+
+    #[test]
+    fn lazy_conversion() {
+        let dom_root = treexml::Document::parse(
+            r#"<e id="1"><a>some</a><b>textual</b><c/><a>content</a></e>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let cfg = ConversionConfig::default();
+
+        let entries = node2object_lazy(&dom_root, &cfg)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ("@id".to_string(), json!(1.0)),
+                ("a".to_string(), json!(["some", "content"])),
+                ("b".to_string(), json!("textual")),
+            ]
+        );
+        assert_eq!(
+            Value::Object(entries.into_iter().collect()),
+            node2object(&dom_root)["e"]
+        );
+
+        let leaf = treexml::Document::parse(r#"<e id="1">text</e>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        assert_eq!(
+            node2object_lazy(&leaf, &cfg)
+                .collect::<Result<Map<_, _>, _>>()
+                .map(Value::Object)
+                .unwrap(),
+            json!({ "@id": 1.0, "#text": "text" })
+        );
+    }
+
+    #[test]
+    fn radix_literals() {
+        let dom_root = treexml::Document::parse(
+            r#"<e mode="0o755"><hex>0x1F4</hex><bin>0b101</bin><neg>-0x10</neg><big>0x1FFFFFFFFFFFFFFFF</big><max>0xFFFFFFFFFFFFFFFF</max></e>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        assert_eq!(
+            Value::Object(node2object(&dom_root)),
+            json!({
+                "e": {
+                    "@mode": "0o755",
+                    "hex": "0x1F4",
+                    "bin": "0b101",
+                    "neg": "-0x10",
+                    "big": "0x1FFFFFFFFFFFFFFFF",
+                    "max": "0xFFFFFFFFFFFFFFFF"
+                }
+            })
+        );
+
+        let cfg = ConversionConfig {
+            parse_radix_literals: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "e": {
+                    "@mode": 493,
+                    "hex": 500,
+                    "bin": 5,
+                    "neg": -16,
+                    "big": "0x1FFFFFFFFFFFFFFFF",
+                    "max": u64::MAX
+                }
+            })
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_children() {
+        let src = format!(
+            "<e id=\"x\">{}<b>tail</b><c/></e>",
+            (0..1000)
+                .map(|i| format!("<a><n>{}</n></a>", i))
+                .collect::<String>()
+        );
+        let dom_root = treexml::Document::parse(src.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let cfg = ConversionConfig::default();
+
+        let parallel = node2object_parallel_children(&dom_root, &cfg).unwrap();
+        assert_eq!(parallel, node2object(&dom_root));
+        assert_eq!(parallel["e"]["a"][999]["n"], json!(999.0));
+    }
+
+    #[test]
+    fn decimal_separators() {
+        let dom_root = treexml::Document::parse(
+            r#"<e><a>12,50</a><b>1.234,56</b><c>12.50</c><d>-1.234.567</d><f>12.3456,7</f></e>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        assert_eq!(
+            Value::Object(node2object(&dom_root)),
+            json!({ "e": { "a": "12,50", "b": "1.234,56", "c": 12.5, "d": "-1.234.567", "f": "12.3456,7" } })
+        );
+
+        let mut cfg = ConversionConfig {
+            decimal_separator: DecimalSeparator::Comma,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "a": 12.5, "b": "1.234,56", "c": "12.50", "d": "-1.234.567", "f": "12.3456,7" } })
+        );
+
+        cfg.allow_thousands_separators = true;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "a": 12.5, "b": 1234.56, "c": "12.50", "d": -1234567.0, "f": "12.3456,7" } })
+        );
+    }
+
+    #[test]
+    fn boolean_contexts() {
+        let dom_root =
+            treexml::Document::parse(r#"<e enabled="true"><flag>false</flag></e>"#.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+
+        for (boolean_in_attributes, boolean_in_text, expected) in [
+            (
+                true,
+                true,
+                json!({ "e": { "@enabled": true, "flag": false } }),
+            ),
+            (
+                false,
+                true,
+                json!({ "e": { "@enabled": "true", "flag": false } }),
+            ),
+            (
+                true,
+                false,
+                json!({ "e": { "@enabled": true, "flag": "false" } }),
+            ),
+        ] {
+            let cfg = ConversionConfig {
+                boolean_in_attributes,
+                boolean_in_text,
+                ..Default::default()
+            };
+            assert_eq!(
+                Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn strict_number_roundtrip() {
+        let dom_root = treexml::Document::parse(
+            r#"<e><version>1.10</version><scale>2.0</scale><count>10</count><id>007</id><exp>1e3</exp><ok>1.1</ok><neg>-3</neg></e>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        assert_eq!(
+            Value::Object(node2object(&dom_root)),
+            json!({ "e": { "version": 1.1, "scale": 2.0, "count": 10.0, "id": 7.0, "exp": 1000.0, "ok": 1.1, "neg": -3.0 } })
+        );
+
+        let cfg = ConversionConfig {
+            strict_number_roundtrip: true,
+            ..Default::default()
+        };
+        let result = Value::Object(node2object_with_config(&dom_root, &cfg).unwrap());
+        assert_eq!(
+            result,
+            json!({ "e": { "version": "1.10", "scale": 2.0, "count": 10, "id": "007", "exp": "1e3", "ok": 1.1, "neg": -3 } })
+        );
+        assert_eq!(result["e"]["scale"].to_string(), "2.0");
+        assert_eq!(result["e"]["count"].to_string(), "10");
+    }
+
+    #[test]
+    fn gdata_mode() {
+        let dom_root = treexml::Document::parse(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:openSearch="http://a9.com/-/spec/opensearchrss/1.0/">
+                 <title type="text">Hello</title>
+                 <openSearch:totalResults>1</openSearch:totalResults>
+                 <entry><title type="text">Entry</title></entry>
+                 <link rel="self" href="http://example.com/feed"/>
+               </feed>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            mode: ConversionMode::GData,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "feed": {
+                    "title": { "type": "text", "$t": "Hello" },
+                    "openSearch$totalResults": { "$t": "1" },
+                    "entry": { "title": { "type": "text", "$t": "Entry" } },
+                    "link": { "rel": "self", "href": "http://example.com/feed" }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn leading_plus() {
+        let dom_root = treexml::Document::parse(
+            r#"<e n="+5"><phone>+49301234567</phone><exp>1e+2</exp></e>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        assert_eq!(
+            Value::Object(node2object(&dom_root)),
+            json!({ "e": { "@n": "+5", "phone": "+49301234567", "exp": 100.0 } })
+        );
+
+        let cfg = ConversionConfig {
+            allow_leading_plus: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "@n": 5.0, "phone": 49301234567.0, "exp": 100.0 } })
+        );
+    }
+
+    #[test]
+    fn root_handling() {
+        let dom_root = treexml::Document::parse(r#"<e id="1"><a>x</a></e>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let leaf = treexml::Document::parse(r#"<e>text</e>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+
+        let mut cfg = ConversionConfig {
+            include_root: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "@id": 1.0, "a": "x" })
+        );
+        assert_eq!(
+            Value::Object(node2object_with_config(&leaf, &cfg).unwrap()),
+            json!({ "#text": "text" })
+        );
+
+        cfg.root_element_name_key = Some("_tag".into());
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "_tag": "e", "@id": 1.0, "a": "x" })
+        );
+        assert_eq!(
+            node2object_lazy(&dom_root, &cfg)
+                .collect::<Result<Map<_, _>, _>>()
+                .unwrap(),
+            node2object_with_config(&dom_root, &cfg).unwrap()
+        );
+
+        cfg.include_root = true;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "_tag": "e", "@id": 1.0, "a": "x" } })
+        );
+    }
+
+    #[test]
+    fn padded_values() {
+        let dom_root = treexml::Document::parse(
+            "<e n=\"  42  \"><int>  42  </int><bool>\ttrue\n</bool><nbsp>\u{a0}7\u{a0}</nbsp><s>  x  </s></e>"
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        assert_eq!(
+            Value::Object(node2object(&dom_root)),
+            json!({ "e": { "@n": "  42  ", "int": "  42  ", "bool": "\ttrue\n", "nbsp": "\u{a0}7\u{a0}", "s": "  x  " } })
+        );
+
+        let mut cfg = ConversionConfig {
+            coerce_padded_numbers: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "@n": 42.0, "int": 42.0, "bool": true, "nbsp": 7.0, "s": "  x  " } })
+        );
+
+        cfg.coerce_padded_numbers = false;
+        cfg.trim_text = true;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "@n": 42.0, "int": 42.0, "bool": true, "nbsp": 7.0, "s": "x" } })
+        );
+    }
+
+    #[cfg(feature = "markdown")]
+    #[test]
+    fn markdown_table() {
+        let dom_root = treexml::Document::parse(
+            r#"<population>
+                 <entry><name>Alex</name><height>173.5</height></entry>
+                 <entry><name>Mel | Co</name></entry>
+               </population>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        // Columns follow the order of the keys of the first row.
+        let expected = if cfg!(feature = "preserve_order") {
+            "| name | height |\n| --- | --- |\n| Alex | 173.5 |\n| Mel \\| Co |  |\n"
+        } else {
+            "| height | name |\n| --- | --- |\n| 173.5 | Alex |\n|  | Mel \\| Co |\n"
+        };
+        assert_eq!(
+            element_to_markdown_table(&dom_root, &MarkdownTableConfig::default()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn scientific_notation() {
+        let dom_root = treexml::Document::parse(
+            r#"<e code="6e7"><a>1E2</a><b>1.5</b><c>true</c></e>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        assert_eq!(
+            Value::Object(node2object(&dom_root)),
+            json!({ "e": { "@code": 60000000.0, "a": 100.0, "b": 1.5, "c": true } })
+        );
+
+        let cfg = ConversionConfig {
+            coerce_scientific_notation: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "@code": "6e7", "a": "1E2", "b": 1.5, "c": true } })
+        );
+    }
+
+    #[test]
+    fn error_pointer() {
+        let dom_root = treexml::Document::parse(
+            r#"<root>
+                 <items>
+                   <item><price>AA==</price></item>
+                   <item/>
+                   <item><price>AA==</price></item>
+                   <item><price>AA==</price><price currency="EUR">!!</price></item>
+                 </items>
+                 <a-b><c/><c>!!</c></a-b>
+               </root>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let mut cfg = ConversionConfig {
+            strict: true,
+            base64_paths: vec![("price".to_string(), Base64Handling::Tagged)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            node2object_with_config(&dom_root, &cfg)
+                .unwrap_err()
+                .path()
+                .as_str(),
+            "/root/items/item/2/price/1/#text"
+        );
+
+        cfg.include_root = false;
+        cfg.base64_paths = vec![("c".to_string(), Base64Handling::Tagged)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            node2object_with_config(&dom_root, &cfg)
+                .unwrap_err()
+                .path()
+                .as_str(),
+            "/a-b/c"
+        );
+
+        let mut pointer = JsonPointer::default();
+        pointer.push("a/b~c");
+        assert_eq!(pointer.as_str(), "/a~1b~0c");
+    }
+
+    #[test]
+    fn xml_lang_grouping() {
+        let cfg = ConversionConfig {
+            group_by_xml_lang: true,
+            ..Default::default()
+        };
+        for (src, expected) in [
+            (
+                r#"<e><title xml:lang="en">Hi</title><title xml:lang="de">Hallo</title></e>"#,
+                json!({ "e": { "title": { "en": "Hi", "de": "Hallo" } } }),
+            ),
+            (
+                r#"<e><title xml:lang="en">Hi</title><title>Hallo</title></e>"#,
+                json!({ "e": { "title": [ { "@xml:lang": "en", "#text": "Hi" }, "Hallo" ] } }),
+            ),
+            (
+                r#"<e><title xml:lang="en">Hi</title><title xml:lang="en">Hello</title></e>"#,
+                json!({ "e": { "title": [ { "@xml:lang": "en", "#text": "Hi" }, { "@xml:lang": "en", "#text": "Hello" } ] } }),
+            ),
+            (
+                r#"<e><desc xml:lang="en" id="1"><p>One</p><p>Two</p></desc><desc xml:lang="fr"><p>Un</p></desc></e>"#,
+                json!({ "e": { "desc": { "en": { "@id": 1.0, "p": ["One", "Two"] }, "fr": { "p": "Un" } } } }),
+            ),
+        ] {
+            let fixture = treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+            assert_eq!(
+                Value::Object(node2object_with_config(&fixture, &cfg).unwrap()),
+                expected
+            );
+        }
+
+        let fixture = treexml::Document::parse(
+            r#"<e><title xml:lang="en">Hi</title><title>Hallo</title></e>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let strict = ConversionConfig {
+            strict: true,
+            ..cfg
+        };
+        assert_eq!(
+            node2object_with_config(&fixture, &strict)
+                .unwrap_err()
+                .path()
+                .as_str(),
+            "/e/title"
+        );
+    }
+
+    #[cfg(feature = "dot")]
+    #[test]
+    fn dot_output() {
+        let dom_root = treexml::Document::parse(
+            r#"<population><entry id="1" note="a|b"><name>Alex</name></entry><entry><name>Mel</name></entry></population>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let dot = element_to_dot(&dom_root);
+        assert!(dot.starts_with("digraph xml {\n") && dot.ends_with("}\n"));
+        assert_eq!(dot.lines().filter(|l| l.contains("[")).count(), 5);
+        assert_eq!(dot.lines().filter(|l| l.contains("->")).count(), 4);
+        assert!(dot.contains(r#"n1 [shape=record, label="{entry|id=1\lnote=a\|b\l}"];"#));
+    }
+
+    #[test]
+    fn unexpanded_entities_unsupported() {
+        let dom_root = treexml::Document::parse(r#"<e>&#169; &amp; co</e>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+
+        assert_eq!(
+            Value::Object(node2object(&dom_root)),
+            json!({ "e": "\u{a9} & co" })
+        );
+
+        let cfg = ConversionConfig {
+            expand_entities: false,
+            ..Default::default()
+        };
+        let err = node2object_with_config(&dom_root, &cfg).unwrap_err();
+        assert!(matches!(
+            err,
+            ConversionError::UnsupportedOption {
+                option: "expand_entities",
+                ..
+            }
+        ));
+        // The configuration is rejected before anything is converted.
+        assert_eq!(err.path().as_str(), "");
+    }
+
+    #[test]
+    fn reverse_conversion() {
+        let value = json!({
+            "@id": 7.0,
+            "entry": [
+                { "name": "Alex", "height": 173.5 },
+                { "@kind": "guest", "#text": "Mel" }
+            ],
+            "note": null,
+            "flag": true
+        });
+
+        let e = value_to_element("population", &value).unwrap();
+        assert_eq!(e.name, "population");
+        assert_eq!(e.attributes["id"], "7");
+        assert_eq!(element_children_of_type(&e, "entry").len(), 2);
+        assert_eq!(
+            Value::Object(node2object(&e)),
+            json!({
+                "population": {
+                    "@id": 7.0,
+                    "entry": [
+                        { "name": "Alex", "height": 173.5 },
+                        { "@kind": "guest", "#text": "Mel" }
+                    ],
+                    "flag": true
+                }
+            })
+        );
+
+        for (value, path) in [
+            (json!({ "@a": { "b": 1 } }), "/@a"),
+            (json!({ "#text": [1] }), "/#text"),
+            (json!({ "a": [[1]] }), "/a/0"),
+            (json!({ "not valid": 1 }), "/not valid"),
+        ] {
+            assert_eq!(
+                value_to_element("e", &value).unwrap_err().path().as_str(),
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn empty_element_defaults() {
+        let dom_root = treexml::Document::parse(
+            r#"<order currency="x"><quantity/><currency/><note/><q>1</q><q/><q>3</q><tagged unit="kg"/><blank>   </blank></order>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let mut cfg = ConversionConfig {
+            defaults: vec![
+                ("quantity".to_string(), json!(0)),
+                ("order/currency".to_string(), json!("EUR")),
+                ("q".to_string(), json!(0)),
+                ("tagged".to_string(), json!(0)),
+                ("blank".to_string(), json!("n/a")),
+            ]
+            .into_iter()
+            .collect(),
+            trim_text: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "order": {
+                    "@currency": "x",
+                    "quantity": 0,
+                    "currency": "EUR",
+                    "q": [1.0, 0, 3.0],
+                    "tagged": { "@unit": "kg" },
+                    "blank": "n/a"
+                }
+            })
+        );
+
+        cfg.defaults_for_attribute_only = true;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap())["order"]["tagged"],
+            json!({ "@unit": "kg", "#text": 0 })
+        );
+
+        // The path wins over the name, whatever the order of the map.
+        cfg.defaults.insert("currency".to_string(), json!("USD"));
+        cfg.defaults.insert("/order/note".to_string(), json!("-"));
+        cfg.defaults.insert("note".to_string(), json!(""));
+        let data = node2object_with_config(&dom_root, &cfg).unwrap();
+        assert_eq!(data["order"]["currency"], json!("EUR"));
+        assert_eq!(data["order"]["note"], json!("-"));
+    }
+
+    #[test]
+    fn xml_declaration() {
+        let doc = treexml::Document::parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?><root><a>1</a></root>"#.as_bytes(),
+        )
+        .unwrap();
+
+        let mut cfg = ConversionConfig::default();
+        assert_eq!(
+            Value::Object(document_to_object(&doc, &cfg).unwrap()),
+            json!({ "root": { "a": 1.0 } })
+        );
+
+        cfg.include_xml_declaration = true;
+        assert_eq!(
+            Value::Object(document_to_object(&doc, &cfg).unwrap()),
+            json!({
+                "@_xml": { "version": "1.0", "encoding": "UTF-8", "standalone": null },
+                "root": { "a": 1.0 }
+            })
+        );
+    }
+
+    #[test]
+    fn empty_elements_as_flags() {
+        let dom_root = treexml::Document::parse(
+            r#"<settings><enabled/><archived/><theme name="dark"/></settings>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            empty_element_as: EmptyElement::Boolean,
+            ..Default::default()
+        };
+        let out = Value::Object(node2object_with_config(&dom_root, &cfg).unwrap());
+        assert_eq!(
+            out,
+            json!({
+                "settings": {
+                    "enabled": true,
+                    "archived": true,
+                    "theme": { "@name": "dark" }
+                }
+            })
+        );
+        assert!(out["settings"].get("hidden").is_none());
+
+        assert_eq!(
+            Value::Object(node2object(&dom_root)),
+            json!({ "settings": { "theme": { "@name": "dark" } } })
+        );
+    }
+
+    #[test]
+    fn incremental_conversion() {
+        let mut converter = IncrementalConverter::new(ConversionConfig {
+            include_root: false,
+            ..Default::default()
+        });
+        assert!(converter.is_empty());
+
+        for src in &[
+            r#"<item id="1">a</item>"#,
+            r#"<item id="2"><name>b</name></item>"#,
+        ] {
+            let e = treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+            converter.push(&e).unwrap();
+        }
+        assert_eq!(converter.len(), 2);
+        assert_eq!(
+            converter.finish(),
+            json!([
+                { "@id": 1.0, "#text": "a" },
+                { "@id": 2.0, "name": "b" }
+            ])
+        );
+
+        let mut converter = IncrementalConverter::new(ConversionConfig {
+            expand_entities: false,
+            ..Default::default()
+        });
+        let e = treexml::Document::parse("<item/>".as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        assert!(converter.push(&e).is_err());
+        assert_eq!(converter.finish(), json!([]));
+    }
+
+    #[test]
+    fn collapse_wrappers() {
+        let dom_root = treexml::Document::parse(
+            r#"<Envelope><Body><GetUserResponse><name>Ann</name><id>7</id></GetUserResponse></Body></Envelope>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let mut cfg = ConversionConfig {
+            collapse_wrappers: vec!["Envelope".to_string(), "Body".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "Envelope": { "name": "Ann", "id": 7.0 } })
+        );
+
+        cfg.collapsed_wrapper_key = WrapperKey::Child;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "GetUserResponse": { "name": "Ann", "id": 7.0 } })
+        );
+
+        let dom_root = treexml::Document::parse(
+            r#"<list><Body kind="x"><a>1</a></Body><item><v>1</v></item><item><v>2</v></item><item><v>3</v><w/></item></list>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let mut cfg = ConversionConfig {
+            collapse_wrappers: vec!["Body".to_string(), "item".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "list": {
+                    "Body": { "@kind": "x", "a": 1.0 },
+                    "item": [1.0, 2.0, { "v": 3.0 }]
+                }
+            })
+        );
+
+        cfg.collapsed_wrapper_key = WrapperKey::Child;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "list": {
+                    "Body": { "@kind": "x", "a": 1.0 },
+                    "v": [1.0, 2.0],
+                    "item": { "v": 3.0 }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn preserve_original_string() {
+        let dom_root = treexml::Document::parse(
+            r#"<r n="042"><a>042</a><b>true</b><c>text</c><d>1.0</d></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            preserve_original_string: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "r": {
+                    "@n": 42.0,
+                    "a": { "#value": 42.0, "#raw": "042" },
+                    "b": { "#value": true, "#raw": "true" },
+                    "c": "text",
+                    "d": { "#value": 1.0, "#raw": "1.0" }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn filtered_attributes_collapse() {
+        let dom_root = treexml::Document::parse(
+            r#"<r id="1"><price currency="EUR">10</price><tag kind="x"/><note>hi</note></r>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            drop_attributes: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": { "price": 10.0, "note": "hi" } })
+        );
+
+        let cfg = ConversionConfig {
+            skip_attributes: vec!["currency".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": { "@id": 1.0, "price": 10.0, "tag": { "@kind": "x" }, "note": "hi" } })
+        );
+
+        // xml-rs consumes namespace declarations while parsing, so build the element by hand.
+        let mut e = treexml::Element::new("title");
+        e.attributes
+            .insert("xmlns".to_string(), "http://example.com/ns".to_string());
+        e.attributes
+            .insert("xmlns:x".to_string(), "http://example.com/x".to_string());
+        e.text = Some("Hello".to_string());
+
+        let mut cfg = ConversionConfig::default();
+        assert_eq!(
+            Value::Object(node2object_with_config(&e, &cfg).unwrap()),
+            json!({
+                "title": {
+                    "@xmlns": "http://example.com/ns",
+                    "@xmlns:x": "http://example.com/x",
+                    "#text": "Hello"
+                }
+            })
+        );
+
+        cfg.strip_xmlns_attributes = true;
+        assert_eq!(
+            Value::Object(node2object_with_config(&e, &cfg).unwrap()),
+            json!({ "title": "Hello" })
+        );
+    }
+
+    #[test]
+    fn deserialize_into_struct() {
+        #[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+        struct Book {
+            #[serde(rename = "@id")]
+            id: f64,
+            title: String,
+            tags: Vec<String>,
+        }
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Document {
+            book: Book,
+        }
+
+        let dom_root = treexml::Document::parse(
+            r#"<book id="3"><title>Dune</title><tags>a</tags><tags>b</tags></book>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let book = Book {
+            id: 3.0,
+            title: "Dune".to_string(),
+            tags: vec!["a".to_string(), "b".to_string()],
+        };
+        assert_eq!(
+            node_to::<Document>(&dom_root).unwrap(),
+            Document { book: book.clone() }
+        );
+
+        let mut cfg = ConversionConfig {
+            include_root: false,
+            ..Default::default()
+        };
+        assert_eq!(node_to_with_config::<Book>(&dom_root, &cfg).unwrap(), book);
+
+        cfg.expand_entities = false;
+        assert!(node_to_with_config::<Book>(&dom_root, &cfg).is_err());
+    }
+
+    #[test]
+    fn merge_siblings() {
+        let dom_root = treexml::Document::parse(
+            r#"<config><settings><a>1</a><nested><x>1</x></nested><list>p</list></settings><settings><b>2</b><nested><y>2</y></nested><list>q</list><list>r</list></settings><item>1</item><item>2</item></config>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let mut cfg = ConversionConfig {
+            merge_siblings: vec!["settings".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "config": {
+                    "settings": {
+                        "a": 1.0,
+                        "b": 2.0,
+                        "nested": { "x": 1.0, "y": 2.0 },
+                        "list": ["q", "r"]
+                    },
+                    "item": [1.0, 2.0]
+                }
+            })
+        );
+
+        let dom_root = treexml::Document::parse(
+            r#"<config><settings><a>1</a><tags>x</tags><tags>y</tags><n><m>1</m></n></settings><settings><a>2</a><tags>z</tags><tags>w</tags><n><m>3</m></n></settings></config>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        cfg.merge_siblings = vec!["config/settings".to_string()].into_iter().collect();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "config": {
+                    "settings": { "a": 2.0, "tags": ["x", "y", "z", "w"], "n": { "m": 3.0 } }
+                }
+            })
+        );
+
+        cfg.strict = true;
+        let err = node2object_with_config(&dom_root, &cfg).unwrap_err();
+        assert_eq!(err.path().as_str(), "/config/settings/a");
+    }
+
+    #[test]
+    fn element_from_struct() {
+        #[derive(serde::Serialize)]
+        struct Book {
+            #[serde(rename = "@id")]
+            id: u32,
+            title: String,
+            tags: Vec<String>,
+        }
+
+        let e = element_from(
+            &Book {
+                id: 3,
+                title: "Dune".to_string(),
+                tags: vec!["a".to_string(), "b".to_string()],
+            },
+            "book",
+        )
+        .unwrap();
+        assert_eq!(
+            Value::Object(node2object(&e)),
+            json!({ "book": { "@id": 3.0, "title": "Dune", "tags": ["a", "b"] } })
+        );
+
+        let mut bad = std::collections::HashMap::new();
+        bad.insert((1, 2), "x");
+        assert!(matches!(
+            element_from(&bad, "map"),
+            Err(ReverseError::Serialize { .. })
+        ));
+        assert!(matches!(
+            element_from(&vec![vec![1]], "nested"),
+            Err(ReverseError::NestedArray { .. })
+        ));
+    }
+
+    #[test]
+    fn array_key_rename() {
+        let dom_root = treexml::Document::parse(
+            r#"<feed><entry>1</entry><entry>2</entry><author>a</author><link>x</link></feed>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let mut cfg = ConversionConfig {
+            array_key_rename: vec![
+                ("entry".to_string(), "entries".to_string()),
+                ("author".to_string(), "authors".to_string()),
+                ("link".to_string(), "links".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            force_array: vec!["link".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        // `author` occurs once and isn't forced into an array, so it keeps its name.
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "feed": {
+                    "entries": [1.0, 2.0],
+                    "author": "a",
+                    "links": ["x"]
+                }
+            })
+        );
+
+        cfg.force_array.insert("feed/author".to_string());
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap())["feed"]["authors"],
+            json!(["a"])
+        );
+    }
+
+    #[test]
+    fn attributes_as_children() {
+        let dom_root = treexml::Document::parse(
+            r#"<elem attr="x" n="2"><child>c</child><leaf flag="true">t</leaf></elem>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            attribute_as_child: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "elem": {
+                    "attr": { "#text": "x" },
+                    "n": { "#text": 2.0 },
+                    "child": "c",
+                    "leaf": { "flag": { "#text": true }, "#text": "t" }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn max_depth() {
+        let dom_root = treexml::Document::parse(
+            r#"<a><b><c><d>1</d></c></b><e>2</e><f><g/><g><x>1</x></g></f></a>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let mut cfg = ConversionConfig {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let err = node2object_with_config(&dom_root, &cfg).unwrap_err();
+        assert_eq!(err.path().as_str(), "/a/b/c");
+
+        cfg.truncate_at_max_depth = true;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "a": {
+                    "b": { "c": "#truncated" },
+                    "e": 2.0,
+                    "f": { "g": ["#truncated", "#truncated"] }
+                }
+            })
+        );
+
+        cfg.truncation_placeholder = json!({ "#truncated": true });
+        cfg.max_depth = Some(3);
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "a": {
+                    "b": { "c": { "d": { "#truncated": true } } },
+                    "e": 2.0,
+                    "f": { "g": { "x": { "#truncated": true } } }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn children_as_attributes() {
+        let dom_root =
+            treexml::Document::parse(r#"<elem><id>42</id><name>foo</name></elem>"#.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+
+        let cfg = ConversionConfig {
+            child_as_attribute: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "elem": { "@id": "42", "@name": "foo" } })
+        );
+
+        let dom_root = treexml::Document::parse(
+            r#"<elem name="x"><id>42</id><name>foo</name><tag>a</tag><tag>b</tag><meta><v>1</v></meta><unit kind="k">kg</unit></elem>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "elem": {
+                    "@name": "x",
+                    "@id": "42",
+                    "name": "foo",
+                    "tag": ["a", "b"],
+                    "meta": { "@v": "1" },
+                    "unit": { "@kind": "k", "#text": "kg" }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn convert_subtree_at_path() {
+        let dom_root = treexml::Document::parse(
+            r#"<report><head>h</head><body><rows><row>1</row><row>2</row></rows><note>a</note><note>b</note></body></report>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig::default();
+        assert_eq!(
+            node2object_at(&dom_root, "report/body/rows", &cfg).unwrap(),
+            json!({ "row": [1.0, 2.0] })
+        );
+        assert_eq!(
+            node2object_at(&dom_root, "report/body/note[2]", &cfg).unwrap(),
+            json!("b")
+        );
+
+        let err = node2object_at(&dom_root, "report/body/table/row", &cfg).unwrap_err();
+        assert_eq!(
+            err,
+            ConversionError::NotFound {
+                segment: "table".into(),
+                path: {
+                    let mut path = JsonPointer::default();
+                    path.push("report");
+                    path.push("body");
+                    path
+                },
+                xml_path: {
+                    let mut xml_path = XmlPath::default();
+                    xml_path.push_element("report", None);
+                    xml_path.push_element("body", None);
+                    xml_path
+                },
+            }
+        );
+        assert!(node2object_at(&dom_root, "report/body/note[3]", &cfg).is_err());
+    }
+
+    #[test]
+    fn attribute_parser_hook() {
+        let dom_root = treexml::Document::parse(
+            r#"<log><event epoch="1614851200" level="3">1614851200</event><other epoch="7"/></log>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            attribute_parser: Some(AttributeParser::new(|name, value, node| {
+                if name == "epoch" && node.matches("log/event") {
+                    Some(json!(format!("epoch:{}", value)))
+                } else {
+                    None
+                }
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "log": {
+                    "event": { "@epoch": "epoch:1614851200", "@level": 3.0, "#text": 1614851200.0 },
+                    "other": { "@epoch": 7.0 }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn reverse_mixed_arrays() {
+        let value = json!([1, "a", null, true, { "k": "v" }, [2, 3]]);
+        let cfg = ReverseConfig::default();
+
+        let e = value_to_element_with_config("list", &value, &cfg).unwrap();
+        let texts = e
+            .children
+            .iter()
+            .map(|c| (c.name.as_str(), c.text.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            vec![
+                ("item", Some("1")),
+                ("item", Some("a")),
+                ("item", None),
+                ("item", Some("true")),
+                ("item", None),
+                ("item", None),
+            ]
+        );
+        assert_eq!(
+            Value::Object(node2object(&e.children[4])),
+            json!({ "item": { "k": "v" } })
+        );
+        assert_eq!(
+            Value::Object(node2object(&e.children[5])),
+            json!({ "item": { "item": [2.0, 3.0] } })
+        );
+
+        let e = value_to_element_with_config(
+            "doc",
+            &json!({ "row": [[1, 2], "x"] }),
+            &ReverseConfig {
+                mixed_array_item_tag: "cell".into(),
+                ..ReverseConfig::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            Value::Object(node2object(&e)),
+            json!({ "doc": { "row": [{ "cell": [1.0, 2.0] }, "x"] } })
+        );
+        assert!(value_to_element("list", &value).is_err());
+    }
+
+    #[test]
+    fn sorted_arrays() {
+        let dom_root = treexml::Document::parse(
+            r#"<r><e><id>2</id><n>b</n></e><e><id>1</id><n>z</n></e><e><id>1</id><n>a</n></e><v>c</v><v>a</v><v>b</v></r>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let mut cfg = ConversionConfig {
+            sort_arrays_by: Some(ArraySortKey::FirstKey),
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "r": {
+                    "e": [
+                        { "id": 1.0, "n": "z" },
+                        { "id": 1.0, "n": "a" },
+                        { "id": 2.0, "n": "b" }
+                    ],
+                    "v": ["a", "b", "c"]
+                }
+            })
+        );
+
+        cfg.sort_arrays_by = Some(ArraySortKey::AllKeys);
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap())["r"]["e"],
+            json!([
+                { "id": 1.0, "n": "a" },
+                { "id": 1.0, "n": "z" },
+                { "id": 2.0, "n": "b" }
+            ])
+        );
+
+        cfg.sort_arrays_by = Some(ArraySortKey::Custom(std::sync::Arc::new(|a, b| {
+            b["n"].as_str().cmp(&a["n"].as_str())
+        })));
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap())["r"]["e"],
+            json!([
+                { "id": 1.0, "n": "z" },
+                { "id": 2.0, "n": "b" },
+                { "id": 1.0, "n": "a" }
+            ])
+        );
+    }
+
+    #[test]
+    fn reserved_key_collisions() {
+        let dom_root = treexml::Document::parse(
+            r#"<r><item unit="kg">5</item><v><value>1</value><other>2</other></v></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let mut cfg = ConversionConfig {
+            text_key: Some("value".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "r": {
+                    "item": { "@unit": "kg", "value": 5.0 },
+                    "v": { "_value": 1.0, "other": 2.0 }
+                }
+            })
+        );
+
+        cfg.reserved_key_escape = "\\".into();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap())["r"]["v"],
+            json!({ "\\value": 1.0, "other": 2.0 })
+        );
+
+        cfg.strict = true;
+        assert_eq!(
+            node2object_with_config(&dom_root, &cfg).unwrap_err(),
+            ConversionError::ReservedKey {
+                key: "value".into(),
+                path: {
+                    let mut path = JsonPointer::default();
+                    path.push("r");
+                    path.push("v");
+                    path
+                },
+                xml_path: {
+                    let mut xml_path = XmlPath::default();
+                    xml_path.push_element("r", None);
+                    xml_path.push_element("v", None);
+                    xml_path.push_element("value", None);
+                    xml_path
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn single_element_array_threshold() {
+        let dom_root = treexml::Document::parse(
+            r#"<r><a>1</a><b>2</b><b>3</b><c><d>x</d></c></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let mut cfg = ConversionConfig::default();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": { "a": 1.0, "b": [2.0, 3.0], "c": { "d": "x" } } })
+        );
+
+        for threshold in 0..=1 {
+            cfg.single_element_array_threshold = threshold;
+            assert_eq!(
+                Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+                json!({ "r": { "a": [1.0], "b": [2.0, 3.0], "c": [{ "d": ["x"] }] } })
+            );
+        }
+    }
+
+    /// Small deterministic generator for `random_trees_convert_consistently`.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, n: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % n as u64) as usize
+        }
+
+        fn pick<'s>(&mut self, items: &[&'s str]) -> &'s str {
+            items[self.next(items.len())]
+        }
+    }
+
+    fn random_tree(rng: &mut Lcg, depth: usize) -> treexml::Element {
+        const NAMES: &[&str] = &["a", "b", "value", "item", "_value", "#text", "x:y", "$t"];
+        const ATTRIBUTES: &[&str] = &["id", "xml:lang", "value", "xmlns", "xmlns:p", "a", "b"];
+        const VALUES: &[&str] = &[
+            "1", "true", "", "en", "de", "0x1f", " 2 ", "QUJD", "1,5", "urn:p",
+        ];
+
+        let mut e = treexml::Element::new(rng.pick(NAMES));
+        if rng.next(4) == 0 {
+            e.prefix = Some("p".into());
+        }
+        for _ in 0..rng.next(3) {
+            e.attributes
+                .insert(rng.pick(ATTRIBUTES).into(), rng.pick(VALUES).into());
+        }
+        if rng.next(3) == 0 {
+            e.text = Some(rng.pick(VALUES).into());
+        }
+        if rng.next(6) == 0 {
+            e.cdata = Some(rng.pick(VALUES).into());
+        }
+        if depth > 0 {
+            for _ in 0..rng.next(5) {
+                e.children.push(random_tree(rng, depth - 1));
+            }
+        }
+        e
+    }
+
+    fn random_config(rng: &mut Lcg) -> ConversionConfig {
+        let names = |rng: &mut Lcg| {
+            vec!["a".to_string(), "value".to_string(), "item".to_string()]
+                .into_iter()
+                .filter(|_| rng.next(2) == 0)
+                .collect::<Vec<_>>()
+        };
+
+        let mut cfg = ConversionConfig::default();
+        let toggles: &[fn(&mut ConversionConfig)] = &[
+            |cfg| cfg.mode = ConversionMode::GData,
+            |cfg| cfg.include_root = false,
+            |cfg| cfg.root_element_name_key = Some("a".into()),
+            |cfg| cfg.group_by_xml_lang = true,
+            |cfg| cfg.strict = true,
+            |cfg| cfg.text_key = Some("value".into()),
+            |cfg| cfg.empty_element_as = EmptyElement::EmptyObject,
+            |cfg| cfg.defaults_for_attribute_only = true,
+            |cfg| cfg.attribute_as_child = true,
+            |cfg| cfg.child_as_attribute = true,
+            |cfg| cfg.drop_attributes = true,
+            |cfg| cfg.strip_xmlns_attributes = true,
+            |cfg| cfg.collapsed_wrapper_key = WrapperKey::Child,
+            |cfg| cfg.single_element_array_threshold = 0,
+            |cfg| cfg.trim_text = true,
+            |cfg| cfg.preserve_original_string = true,
+            |cfg| cfg.parse_radix_literals = true,
+            |cfg| cfg.sort_arrays_by = Some(ArraySortKey::AllKeys),
+            |cfg| cfg.max_depth = Some(3),
+            |cfg| cfg.truncate_at_max_depth = true,
+            |cfg| cfg.namespace_prefixes = PrefixHandling::Keep,
+            |cfg| cfg.namespace_prefixes = PrefixHandling::Strip,
+            |cfg| cfg.default_namespace = DefaultNsHandling::Expand,
+            |cfg| cfg.default_namespace = DefaultNsHandling::ApplyPrefix("d".into()),
+            |cfg| cfg.namespace_key = Some("#ns".into()),
+            |cfg| cfg.harmonize_sibling_types = true,
+            |cfg| cfg.heterogeneous_arrays = HeteroPolicy::Stringify,
+            |cfg| cfg.heterogeneous_arrays = HeteroPolicy::Error,
+            |cfg| cfg.merge_attributes_into_root = true,
+            |cfg| cfg.attributes_key = Some("@".into()),
+            |cfg| cfg.attribute_position = AttrPosition::Last,
+            |cfg| cfg.separate_cdata = true,
+            |cfg| cfg.inject_source_tag = true,
+            |cfg| cfg.arrays_as_indexed_objects = true,
+            |cfg| cfg.truncated_count_key = Some("#truncated".into()),
+        ];
+        for toggle in toggles {
+            if rng.next(3) == 0 {
+                toggle(&mut cfg);
+            }
+        }
+        for name in names(rng) {
+            cfg.defaults.insert(name, json!(0));
+        }
+        cfg.collapse_wrappers = names(rng);
+        cfg.merge_siblings = names(rng).into_iter().collect();
+        cfg.force_array = names(rng).into_iter().collect();
+        cfg.skip_attributes = names(rng);
+        cfg.array_key_rename = names(rng)
+            .into_iter()
+            .map(|name| (name, "item".to_string()))
+            .collect();
+        for name in names(rng) {
+            cfg.base64_paths.insert(name, Base64Handling::Tagged);
+        }
+        for name in names(rng) {
+            cfg.max_occurrences.insert(name, rng.next(3));
+        }
+        for name in names(rng) {
+            cfg.ensure_array_keys.insert(name, names(rng));
+        }
+        cfg.prefix_map = names(rng)
+            .into_iter()
+            .map(|name| ("urn:p".to_string(), name))
+            .collect();
+        if rng.next(4) == 0 {
+            cfg.strip_namespaces.insert("urn:p".into());
+        }
+        cfg
+    }
+
+    #[test]
+    fn random_trees_convert_consistently() {
+        let mut rng = Lcg(0x5eed);
+        for _ in 0..2000 {
+            let e = random_tree(&mut rng, 4);
+            let cfg = random_config(&mut rng);
+
+            // Every entry point gives the same output: the event path behind
+            // `node2object_with_config`, the builders and the direct conversion.
+            let converted = node2object_with_config(&e, &cfg);
+            let with_stats = node2object_with_stats(&e, &cfg);
+            let built = node2object_with_builder(&e, &cfg, &SerdeJsonBuilder);
+            let mut sink = ValueSink::new();
+            let driven = drive(&e, &mut sink, &cfg).map(|_| sink.finish());
+            let direct = namespaces::resolve_namespaces(&e, &cfg)
+                .and_then(|resolved| convert_root_with_stats(&resolved, None, &cfg));
+            let context = || format!("{:?}\n{:?}", e, cfg);
+            match (&converted, &with_stats, &direct) {
+                (Ok(data), Ok((with_stats, stats)), Ok((direct, direct_stats))) => {
+                    assert_eq!(data, with_stats, "{}", context());
+                    assert_eq!(data, direct, "{}", context());
+                    assert_eq!(stats, direct_stats, "{}", context());
+                    assert_eq!(
+                        built.as_ref().ok(),
+                        Some(&Value::Object(data.clone())),
+                        "{}",
+                        context()
+                    );
+                    assert_eq!(
+                        driven.as_ref().ok(),
+                        Some(&Some(Value::Object(data.clone()))),
+                        "{}",
+                        context()
+                    );
+                }
+                _ => {
+                    for ok in [
+                        with_stats.is_ok(),
+                        direct.is_ok(),
+                        built.is_ok(),
+                        driven.is_ok(),
+                    ] {
+                        assert_eq!(ok, converted.is_ok(), "{}", context());
+                    }
+                }
+            }
+
+            if let Ok(data) = converted {
+                let _ = value_to_element_with_config(
+                    "root",
+                    &Value::Object(data),
+                    &ReverseConfig::default(),
+                );
+            }
+            let _ = node2object_lazy(&e, &cfg).collect::<Vec<_>>();
+            let _ = node2object_at(&e, &format!("{}/a[2]/value", e.name), &cfg);
+            let _ = convert_children_of_type(&e, "a", &cfg);
+            #[cfg(feature = "rayon")]
+            let _ = node2object_parallel_children(&e, &cfg);
+        }
+    }
+
+    #[test]
+    fn entity_handling() {
+        let dom_root =
+            treexml::Document::parse(r#"<r a="&lt;b&gt;">Tom &amp; Jerry &#169;</r>"#.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+
+        for handling in [
+            EntityHandling::Default,
+            EntityHandling::ExpandBuiltin,
+            EntityHandling::Error,
+        ] {
+            let cfg = ConversionConfig {
+                entity_handling: handling,
+                ..Default::default()
+            };
+            assert_eq!(
+                Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+                json!({ "r": { "@a": "<b>", "#text": "Tom & Jerry \u{a9}" } })
+            );
+        }
+
+        let cfg = ConversionConfig {
+            entity_handling: EntityHandling::PreserveAsString,
+            ..Default::default()
+        };
+        assert!(matches!(
+            node2object_with_config(&dom_root, &cfg),
+            Err(ConversionError::UnsupportedOption {
+                option: "entity_handling",
+                ..
+            })
+        ));
+
+        // Undeclared entities never reach the converter.
+        assert!(treexml::Document::parse("<r>&copy;</r>".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn xml_paths_in_errors() {
+        let dom_root = treexml::Document::parse(
+            r#"<population><entry id="1"/><entry id="2"><data>QUJD</data></entry><entry id="3" value="x"><data>!!</data><deep><deeper/></deep></entry></population>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            max_depth: Some(3),
+            ..Default::default()
+        };
+        let err = node2object_with_config(&dom_root, &cfg).unwrap_err();
+        assert_eq!(err.xml_path().as_str(), "population/entry[3]/deep/deeper");
+        assert_eq!(
+            err.to_string(),
+            "element at population/entry[3]/deep/deeper is nested too deeply"
+        );
+
+        let cfg = ConversionConfig {
+            strict: true,
+            base64_paths: vec![("data".to_string(), Base64Handling::Tagged)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let err = node2object_with_config(&dom_root, &cfg).unwrap_err();
+        assert_eq!(err.xml_path().as_str(), "population/entry[3]/data");
+        assert_eq!(err.path().as_str(), "/population/entry/2/data");
+
+        let cfg = ConversionConfig {
+            strict: true,
+            text_key: Some("@value".into()),
+            ..Default::default()
+        };
+        let err = node2object_with_config(&dom_root, &cfg).unwrap_err();
+        assert_eq!(err.xml_path().as_str(), "population/entry[3]/@value");
+    }
+
+    #[test]
+    fn element_with_ancestors() {
+        let dom_root = treexml::Document::parse(
+            r#"<library id="1"><shelf n="2"><book>Dune</book><book>Emma</book></shelf></library>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let shelf = &dom_root.children[0];
+        let book = &shelf.children[1];
+        let cfg = ConversionConfig::default();
+        assert_eq!(
+            Value::Object(
+                element_with_ancestors_to_object(&[&dom_root, shelf], book, &cfg).unwrap()
+            ),
+            json!({ "library": { "shelf": { "book": "Emma" } } })
+        );
+        assert_eq!(
+            Value::Object(element_with_ancestors_to_object(&[], &dom_root, &cfg).unwrap()),
+            Value::Object(node2object(&dom_root))
+        );
+
+        let cfg = ConversionConfig {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let err = element_with_ancestors_to_object(&[&dom_root, shelf], book, &cfg).unwrap_err();
+        assert_eq!(err.xml_path().as_str(), "library/shelf/book[2]");
+    }
+
+    #[test]
+    fn positions_unsupported() {
+        let dom_root = treexml::Document::parse("<r>\n  <a>1</a>\n</r>".as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+
+        let cfg = ConversionConfig {
+            include_positions: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            node2object_with_config(&dom_root, &cfg),
+            Err(ConversionError::UnsupportedOption {
+                option: "include_positions",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn converted_children_iterator() {
+        let dom_root = treexml::Document::parse(
+            r#"<r a="1"><x>1</x><empty/><y><z>2</z></y><x>3</x></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig::default();
+        assert_eq!(
+            iter_converted_children(&dom_root, &cfg)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            vec![
+                ("x", json!(1.0)),
+                ("y", json!({ "z": 2.0 })),
+                ("x", json!(3.0))
+            ]
+        );
+        assert_eq!(
+            iter_converted_children(&dom_root, &cfg)
+                .filter_map(Result::ok)
+                .find(|(name, _)| *name == "y")
+                .map(|(_, v)| v),
+            Some(json!({ "z": 2.0 }))
+        );
+
+        let cfg = ConversionConfig {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let mut iter = iter_converted_children(&dom_root, &cfg);
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    /// Minimal validator for the subset of JSON Schema produced by `infer_schema`.
+    fn schema_accepts(schema: &Value, v: &Value) -> bool {
+        if let Some(variants) = schema.get("anyOf").and_then(Value::as_array) {
+            return variants.iter().any(|s| schema_accepts(s, v));
+        }
+        let type_matches = |t: &Value| match t.as_str() {
+            Some("null") => v.is_null(),
+            Some("boolean") => v.is_boolean(),
+            Some("number") => v.is_number(),
+            Some("string") => v.is_string(),
+            Some("array") => v.is_array(),
+            Some("object") => v.is_object(),
+            _ => false,
+        };
+        match schema.get("type") {
+            Some(Value::Array(types)) if !types.iter().any(type_matches) => return false,
+            Some(t @ Value::String(_)) if !type_matches(t) => return false,
+            _ => {}
+        }
+        match v {
+            Value::Array(items) => schema
+                .get("items")
+                .is_none_or(|s| items.iter().all(|item| schema_accepts(s, item))),
+            Value::Object(data) => {
+                let required = schema
+                    .get("required")
+                    .and_then(Value::as_array)
+                    .map_or(&[][..], Vec::as_slice);
+                let properties = &schema["properties"];
+                required
+                    .iter()
+                    .all(|k| data.contains_key(k.as_str().unwrap()))
+                    && data
+                        .iter()
+                        .all(|(k, item)| properties.get(k).is_some_and(|s| schema_accepts(s, item)))
+            }
+            _ => true,
+        }
+    }
+
+    #[test]
+    fn inferred_schema_validates_output() {
+        let dom_root = treexml::Document::parse(
+            r#"
+            <population>
+              <entry>
+                <name>Alex</name>
+                <height>173.5</height>
+              </entry>
+              <entry>
+                <name>Mel</name>
+                <height>180.4</height>
+              </entry>
+            </population>
+            "#
+            .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig::default();
+        let schema = infer_schema(&[&dom_root], &cfg).unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "properties": {
+                    "population": {
+                        "type": "object",
+                        "properties": {
+                            "entry": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "height": { "type": "number" },
+                                        "name": { "type": "string" }
+                                    },
+                                    "required": ["height", "name"]
+                                }
+                            }
+                        },
+                        "required": ["entry"]
+                    }
+                },
+                "required": ["population"]
+            })
+        );
+        let output = Value::Object(node2object_with_config(&dom_root, &cfg).unwrap());
+        assert!(schema_accepts(&schema, &output));
+        assert!(!schema_accepts(
+            &schema,
+            &json!({ "population": { "entry": 1.0 } })
+        ));
+    }
+
+    #[test]
+    fn inferred_schema_merges_documents() {
+        let parse = |src: &str| {
+            treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap()
+        };
+        let a = parse("<r><id>1</id><note>x</note><tag>a</tag></r>");
+        let b = parse("<r><id>two</id><tag>a</tag><tag>b</tag></r>");
+
+        let cfg = ConversionConfig::default();
+        let schema = infer_schema(&[&a, &b], &cfg).unwrap();
+        let r = &schema["properties"]["r"];
+        assert_eq!(r["required"], json!(["id", "tag"]));
+        assert_eq!(
+            r["properties"]["id"],
+            json!({ "type": ["number", "string"] })
+        );
+        assert_eq!(
+            r["properties"]["tag"],
+            json!({ "anyOf": [{ "type": "string" }, { "type": "array", "items": { "type": "string" } }] })
+        );
+        for e in &[&a, &b] {
+            let output = Value::Object(node2object_with_config(e, &cfg).unwrap());
+            assert!(schema_accepts(&schema, &output));
+        }
+
+        let cfg = ConversionConfig {
+            force_array: vec!["tag".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        let schema = infer_schema(&[&a, &b], &cfg).unwrap();
+        assert_eq!(
+            schema["properties"]["r"]["properties"]["tag"],
+            json!({ "type": "array", "items": { "type": "string" } })
+        );
+    }
+
+    #[test]
+    fn text_key_per_node_type() {
+        let dom_root = treexml::Document::parse(
+            r#"<r><a id="1">x</a><p>mixed <b>bold</b></p><n lang="en"/></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            text_key: Some("value".into()),
+            text_key_for_type: vec![
+                (XMLNodeType::TextAndAttributes, "_text".to_string()),
+                (XMLNodeType::SemiStructured, "content".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": {
+                "a": { "@id": 1.0, "_text": "x" },
+                "p": { "b": "bold", "content": "mixed " },
+                "n": { "@lang": "en" }
+            } })
+        );
+
+        let dom_root = treexml::Document::parse(r#"<r><content>1</content></r>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": { "_content": 1.0 } })
+        );
+    }
+
+    #[test]
+    fn harmonized_sibling_types() {
+        let dom_root = treexml::Document::parse(
+            r#"<r>
+                <i>1</i><i>2.5</i><i>0x10</i>
+                <m>1</m><m>abc</m><m>true</m>
+                <h>1</h><h>2</h>
+                <g><m>1</m><m>2</m></g>
+            </r>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            harmonize_sibling_types: true,
+            strict_number_roundtrip: true,
+            parse_radix_literals: true,
+            boolean_in_text: true,
+            ..Default::default()
+        };
+        let output = Value::Object(node2object_with_config(&dom_root, &cfg).unwrap());
+        assert_eq!(
+            output,
+            json!({ "r": {
+                "i": [1.0, 2.5, 16.0],
+                "m": ["1", "abc", "true"],
+                "h": [1, 2],
+                "g": { "m": [1, 2] }
+            } })
+        );
+        assert!(output["r"]["i"][0].is_f64());
+        assert!(output["r"]["h"][0].is_i64());
+
+        let cfg = ConversionConfig {
+            harmonize_sibling_types: false,
+            ..cfg
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap())["r"]["m"],
+            json!([1, "abc", true])
+        );
+
+        // Types are taken from the converted values, here decoded payloads.
+        let dom_root = treexml::Document::parse("<r><b>1234</b><b>abcd</b></r>".as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let cfg = ConversionConfig {
+            harmonize_sibling_types: true,
+            base64_paths: vec![("b".to_string(), Base64Handling::Tagged)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": { "b": [{ "#base64": "1234" }, { "#base64": "abcd" }] } })
+        );
+    }
+
+    #[test]
+    fn separate_cdata_key() {
+        let dom_root = treexml::Document::parse(
+            r#"<r><a><![CDATA[1 < 2]]></a><b id="1">x<![CDATA[y]]></b><c>12</c></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        assert_eq!(
+            Value::Object(node2object(&dom_root)),
+            json!({ "r": { "a": "1 < 2", "b": { "@id": 1.0, "#text": "xy" }, "c": 12.0 } })
+        );
+
+        let cfg = ConversionConfig {
+            separate_cdata: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": {
+                "a": { "#cdata": "1 < 2" },
+                "b": { "@id": 1.0, "#text": "x", "#cdata": "y" },
+                "c": 12.0
+            } })
+        );
+
+        let cfg = ConversionConfig {
+            separate_cdata: true,
+            cdata_key: "_cdata".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap())["r"]["a"],
+            json!({ "_cdata": "1 < 2" })
+        );
+
+        let dom_root = treexml::Document::parse(r#"<r><_cdata>1</_cdata></r>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": { "__cdata": 1.0 } })
+        );
+
+        for cfg in [
+            ConversionConfig {
+                comment_key: Some("#comment".into()),
+                ..Default::default()
+            },
+            ConversionConfig {
+                pi_key: Some("#pi".into()),
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                node2object_with_config(&dom_root, &cfg),
+                Err(ConversionError::UnsupportedOption { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn heterogeneous_array_policies() {
+        let dom_root = treexml::Document::parse(
+            r#"<r><v>1</v><v>abc</v><v/><w>1</w><w>2</w><w/><n>x</n><n/></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let convert =
+            |cfg: &ConversionConfig| node2object_with_config(&dom_root, cfg).map(Value::Object);
+
+        let cfg = ConversionConfig {
+            empty_element_as: EmptyElement::Null,
+            ..Default::default()
+        };
+        assert_eq!(
+            convert(&cfg).unwrap(),
+            json!({ "r": { "v": [1.0, "abc", null], "w": [1.0, 2.0, null], "n": ["x", null] } })
+        );
+
+        let cfg = ConversionConfig {
+            heterogeneous_arrays: HeteroPolicy::Stringify,
+            ..cfg
+        };
+        assert_eq!(
+            convert(&cfg).unwrap(),
+            json!({ "r": { "v": ["1", "abc", null], "w": [1.0, 2.0, null], "n": ["x", null] } })
+        );
+
+        let cfg = ConversionConfig {
+            heterogeneous_arrays_allow_null: false,
+            ..cfg
+        };
+        assert_eq!(
+            convert(&cfg).unwrap(),
+            json!({ "r": { "v": ["1", "abc", ""], "w": ["1", "2", ""], "n": ["x", ""] } })
+        );
+
+        let cfg = ConversionConfig {
+            heterogeneous_arrays: HeteroPolicy::Error,
+            heterogeneous_arrays_allow_null: true,
+            ..cfg
+        };
+        let err = convert(&cfg).unwrap_err();
+        assert_eq!(
+            err,
+            ConversionError::InconsistentSiblingTypes {
+                first: "number",
+                second: "string",
+                path: {
+                    let mut path = JsonPointer::default();
+                    path.push("r");
+                    path.push("v");
+                    path
+                },
+                xml_path: {
+                    let mut path = XmlPath::default();
+                    path.push_element("r", None);
+                    path.push_element("v", None);
+                    path
+                },
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "siblings at r/v mix values of type number and string"
+        );
+
+        let dom_root = treexml::Document::parse(r#"<r><w>1</w><w/></r>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        assert!(node2object_with_config(&dom_root, &cfg).is_ok());
+        let cfg = ConversionConfig {
+            heterogeneous_arrays_allow_null: false,
+            ..cfg
+        };
+        assert!(matches!(
+            node2object_with_config(&dom_root, &cfg),
+            Err(ConversionError::InconsistentSiblingTypes {
+                first: "number",
+                second: "null",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn attributes_merged_into_root() {
+        let dom_root = treexml::Document::parse(
+            r#"<e id="1" name="attr"><name>foo</name><n>2</n></e>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        let cfg = ConversionConfig {
+            merge_attributes_into_root: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "id": 1.0, "name": "foo", "n": 2.0 } })
+        );
+
+        let cfg = ConversionConfig {
+            attribute_child_collision: CollisionStrategy::AttributeWins,
+            ..cfg
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "id": 1.0, "name": "attr", "n": 2.0 } })
+        );
+
+        let cfg = ConversionConfig {
+            attribute_child_collision: CollisionStrategy::Error,
+            ..cfg
+        };
+        let err = node2object_with_config(&dom_root, &cfg).unwrap_err();
+        assert!(matches!(
+            &err,
+            ConversionError::AttributeChildCollision { key, .. } if key == "name"
+        ));
+        assert_eq!(err.path().as_str(), "/e/name");
+
+        let cfg = ConversionConfig {
+            attribute_child_collision: CollisionStrategy::Error,
+            ..Default::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "@id": 1.0, "@name": "attr", "name": "foo", "n": 2.0 } })
+        );
+    }
+
+    #[test]
+    fn xml2js_preset() {
+        let dom_root =
+            treexml::Document::parse(include_str!("../tests/fixtures/xml2js.xml").as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+        let expected: Value =
+            serde_json::from_str(include_str!("../tests/fixtures/xml2js.json")).unwrap();
+
+        let cfg = ConversionConfig::preset(Preset::Xml2js {
+            explicit_array: true,
+            attr_key: "$".into(),
+            char_key: "_".into(),
+        });
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            expected
+        );
+
+        let cfg = ConversionConfig::preset(Preset::Xml2js {
+            explicit_array: false,
+            attr_key: "@".into(),
+            char_key: "#".into(),
+        });
+        let output = Value::Object(node2object_with_config(&dom_root, &cfg).unwrap());
+        assert_eq!(output["catalog"]["count"], json!("2"));
+        assert_eq!(
+            output["catalog"]["book"][0]["price"],
+            json!({ "#": "44.95", "@": { "currency": "USD" } })
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_spans() {
+        use std::sync::{
+            atomic::{AtomicU64, Ordering},
+            Mutex,
+        };
+
+        /// Records the names of the elements in `convert_node` spans.
+        #[derive(Default)]
+        struct Recorder {
+            next_id: AtomicU64,
+            elements: Mutex<Vec<String>>,
+        }
+
+        struct ElementVisitor<'a>(&'a mut Option<String>);
+
+        impl tracing::field::Visit for ElementVisitor<'_> {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "element" {
+                    *self.0 = Some(format!("{:?}", value));
+                }
+            }
+        }
+
+        impl tracing::Subscriber for Recorder {
+            fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+                let mut element = None;
+                span.record(&mut ElementVisitor(&mut element));
+                if span.metadata().name() == "convert_node" {
+                    self.elements.lock().unwrap().extend(element);
+                }
+                tracing::span::Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+            }
+            fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+            fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+            fn event(&self, _: &tracing::Event<'_>) {}
+            fn enter(&self, _: &tracing::span::Id) {}
+            fn exit(&self, _: &tracing::span::Id) {}
+        }
+
+        let dom_root = treexml::Document::parse(r#"<r><a>1</a><b><c/></b></r>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+
+        let recorder = std::sync::Arc::new(Recorder::default());
+        tracing::subscriber::with_default(recorder.clone(), || node2object(&dom_root));
+        assert_eq!(*recorder.elements.lock().unwrap(), ["r", "a", "b", "c"]);
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn attribute_positions() {
+        let dom_root = treexml::Document::parse(
+            r#"<e z="1" b="2"><c>3</c><a>4</a><t id="5">x</t></e>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+
+        for (position, expected) in [
+            (
+                AttrPosition::First,
+                r##"{"e":{"@b":2.0,"@z":1.0,"c":3.0,"a":4.0,"t":{"@id":5.0,"#text":"x"}}}"##,
+            ),
+            (
+                AttrPosition::Last,
+                r##"{"e":{"c":3.0,"a":4.0,"t":{"#text":"x","@id":5.0},"@b":2.0,"@z":1.0}}"##,
+            ),
+            (
+                AttrPosition::Sorted,
+                r##"{"e":{"a":4.0,"@b":2.0,"c":3.0,"t":{"#text":"x","@id":5.0},"@z":1.0}}"##,
+            ),
+        ] {
+            let cfg = ConversionConfig {
+                attribute_position: position,
+                ..Default::default()
+            };
+            let output = Value::Object(node2object_with_config(&dom_root, &cfg).unwrap());
+            assert_eq!(serde_json::to_string(&output).unwrap(), expected);
+        }
+    }
+
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    mod wasm {
+        use super::*;
+        use serde::Serialize;
+        use wasm_bindgen_test::wasm_bindgen_test;
+
+        #[wasm_bindgen_test]
+        fn js_round_trip() {
+            let value = node2json_js(r#"<e id="1"><a>x</a></e>"#).unwrap();
+            let converted: Value = serde_wasm_bindgen::from_value(value).unwrap();
+            assert_eq!(converted, json!({ "e": { "@id": 1.0, "a": "x" } }));
+            assert!(node2json_js("<e>").is_err());
+
+            let js = converted["e"]
+                .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+                .unwrap();
+            let xml = json2xml_js(js, "e").unwrap();
+            let dom_root = treexml::Document::parse(xml.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+            assert_eq!(Value::Object(node2object(&dom_root)), converted);
+        }
+    }
+
+    #[test]
+    fn empty_attributes() {
+        let dom_root = treexml::Document::parse(
+            r#"<r><a note="" id="1">x</a><b note=""/><c note="" id="2"/></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let convert = |cfg: &ConversionConfig| {
+            Value::Object(node2object_with_config(&dom_root, cfg).unwrap())
+        };
+
+        assert_eq!(
+            convert(&ConversionConfig::default()),
+            json!({ "r": {
+                "a": { "@note": "", "@id": 1.0, "#text": "x" },
+                "b": { "@note": "" },
+                "c": { "@note": "", "@id": 2.0 }
+            } })
+        );
+
+        let cfg = ConversionConfig {
+            empty_attribute_as: EmptyAttribute::Null,
+            ..Default::default()
+        };
+        assert_eq!(
+            convert(&cfg),
+            json!({ "r": {
+                "a": { "@note": null, "@id": 1.0, "#text": "x" },
+                "b": { "@note": null },
+                "c": { "@note": null, "@id": 2.0 }
+            } })
+        );
+
+        // Skipping the only attribute of `b` leaves an empty element, which is omitted by
+        // default and follows `empty_element_as` otherwise.
+        let cfg = ConversionConfig {
+            empty_attribute_as: EmptyAttribute::Skip,
+            ..Default::default()
+        };
+        assert_eq!(
+            convert(&cfg),
+            json!({ "r": { "a": { "@id": 1.0, "#text": "x" }, "c": { "@id": 2.0 } } })
+        );
+        let cfg = ConversionConfig {
+            empty_element_as: EmptyElement::EmptyString,
+            ..cfg
+        };
+        assert_eq!(
+            convert(&cfg),
+            json!({ "r": { "a": { "@id": 1.0, "#text": "x" }, "b": "", "c": { "@id": 2.0 } } })
+        );
+        assert_eq!(
+            scan_xml_node(&dom_root.children[1], &cfg),
+            XMLNodeType::Empty
+        );
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn python_bindings() {
+        use pyo3::{prelude::*, types::PyDict};
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "node2object").unwrap();
+            python::python_module(&module).unwrap();
+            let locals = PyDict::new(py);
+            locals.set_item("n2o", module).unwrap();
+            py.run(
+                pyo3::ffi::c_str!(
+                    "
+xml = '<e id=\"1\"><a>x</a><b/></e>'
+assert n2o.node2object(xml) == {'e': {'@id': 1.0, 'a': 'x'}}
+data = n2o.node2object(xml, {'include_root': False, 'empty_element_as': 'null'})
+assert data == {'@id': 1.0, 'a': 'x', 'b': None}, data
+assert n2o.node2object(n2o.object2node(data, 'e'), {'empty_element_as': 'null'}) == {'e': data}
+data = n2o.node2object('<e><a>x</a></e>', {
+    'include_root': False,
+    'ensure_array_keys': {'e': ['a']},
+    'attribute_position': 'last',
+    'default_namespace': {'apply_prefix': 'd'},
+    'split_attribute_values': {'id': ','},
+    'text_key_for_type': {'text_and_attributes': '#value'},
+    'base64_paths': {'e/a': 'hex'},
+    'sort_arrays_by': None,
+})
+assert data == {'a': ['x']}, data
+for bad in [
+    {'no_such_option': 1},
+    {'mode': 'nope'},
+    {'default_namespace': {'prefix': 'd'}},
+    {'split_attribute_values': {'id': 'pipe'}},
+    {'text_key_for_type': {'node': '#text'}},
+    {'id_references': {}},
+]:
+    try:
+        n2o.node2object(xml, bad)
+    except (KeyError, ValueError):
+        pass
+    else:
+        raise AssertionError(bad)
+"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+        });
+    }
+
+    #[test]
+    fn struct2node_roundtrip() {
+        #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        enum Format {
+            Hardcover,
+            Ebook(String),
+        }
+
+        #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Publisher {
+            #[serde(rename = "@country")]
+            country: String,
+            name: String,
+        }
+
+        #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Book {
+            #[serde(rename = "@id")]
+            id: u32,
+            title: String,
+            tags: Vec<String>,
+            price: f64,
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            note: Option<String>,
+            binding: Format,
+            format: Format,
+            publisher: Publisher,
+        }
+
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Doc {
+            book: Book,
+        }
+
+        let book = Book {
+            id: 7,
+            title: "Dune".to_string(),
+            tags: vec!["sf".to_string(), "classic".to_string()],
+            price: 9.5,
+            note: None,
+            binding: Format::Hardcover,
+            format: Format::Ebook("epub".to_string()),
+            publisher: Publisher {
+                country: "US".to_string(),
+                name: "Chilton".to_string(),
+            },
+        };
+        let e = struct2node("book", &book, &ReverseConfig::default()).unwrap();
+        assert_eq!(e.attributes.get("id").map(String::as_str), Some("7"));
+        assert!(e.children.iter().all(|c| c.name != "note"));
+
+        let cfg = ConversionConfig {
+            strict_number_roundtrip: true,
+            ..ConversionConfig::default()
+        };
+        let doc: Doc = node_to_with_config(&e, &cfg).unwrap();
+        assert_eq!(doc, Doc { book: book.clone() });
+
+        let mut bad = std::collections::HashMap::new();
+        bad.insert((1, 2), "x");
+        assert!(matches!(
+            struct2node("map", &bad, &ReverseConfig::default()),
+            Err(ReverseError::Serialize { .. })
+        ));
+
+        let mut attr = std::collections::BTreeMap::new();
+        attr.insert("@publisher", book.publisher);
+        assert!(matches!(
+            struct2node("book", &attr, &ReverseConfig::default()),
+            Err(ReverseError::NonScalarAttribute { .. })
+        ));
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn c_bindings() {
+        use std::os::raw::c_char;
+
+        let xml = "<a><b>1</b></a>";
+        let mut out = vec![0 as c_char; 64];
+        let len = unsafe {
+            node2object_c(
+                xml.as_ptr() as *const c_char,
+                xml.len(),
+                out.as_mut_ptr(),
+                out.len(),
+            )
+        };
+        let json = unsafe { std::ffi::CStr::from_ptr(out.as_ptr()) };
+        assert_eq!(len as usize, json.to_bytes().len());
+        assert_eq!(
+            serde_json::from_slice::<Value>(json.to_bytes()).unwrap(),
+            json!({ "a": { "b": 1.0 } })
+        );
+
+        let convert = |xml: &str, cap: usize| {
+            let mut out = vec![0 as c_char; cap];
+            unsafe {
+                node2object_c(
+                    xml.as_ptr() as *const c_char,
+                    xml.len(),
+                    out.as_mut_ptr(),
+                    cap,
+                )
+            }
+        };
+        assert_eq!(convert(xml, len as usize), NODE2OBJECT_ERR_BUFFER_TOO_SMALL);
+        assert_eq!(convert("<a><b></a>", 64), NODE2OBJECT_ERR_PARSE);
+        assert_eq!(
+            unsafe { node2object_c(std::ptr::null(), 0, out.as_mut_ptr(), out.len()) },
+            NODE2OBJECT_ERR_NULL_POINTER
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_reader() {
+        use std::{
+            pin::Pin,
+            task::{Context, Poll},
+        };
+        use tokio::io::{AsyncRead, ReadBuf};
+
+        /// Yields its input three bytes at a time.
+        struct Chunked(&'static [u8]);
+
+        impl AsyncRead for Chunked {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                let n = self.0.len().min(3).min(buf.remaining());
+                buf.put_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        let src = r#"<?xml version="1.0"?>
+<!-- catalog -->
+<catalog xmlns:x="urn:x" x:lang="en">
+    <book id="1"><title>Dune &amp; more</title><price>9.5</price></book>
+    <book id="2"><title><![CDATA[<Emma>]]></title><x:price>7</x:price></book>
+    <empty/>
+</catalog>"#;
+        let cfg = ConversionConfig::default();
+        let expected = Value::Object(
+            node2object_with_config(
+                &treexml::Document::parse(src.as_bytes())
+                    .unwrap()
+                    .root
+                    .unwrap(),
+                &cfg,
+            )
+            .unwrap(),
+        );
+
+        assert_eq!(
+            convert_async_reader(src.as_bytes(), &cfg).await.unwrap(),
+            expected
+        );
+        assert_eq!(
+            convert_async_reader(Chunked(src.as_bytes()), &cfg)
+                .await
+                .unwrap(),
+            expected
+        );
+
+        assert!(matches!(
+            convert_async_reader(Chunked(b"<a><b></a>"), &cfg).await,
+            Err(ReadError::Parse { .. })
+        ));
+        assert!(matches!(
+            convert_async_reader(Chunked(b"<a><b>"), &cfg).await,
+            Err(ReadError::Parse { .. })
+        ));
+        assert!(matches!(
+            convert_async_reader(Chunked(b"<!-- none -->"), &cfg).await,
+            Err(ReadError::NoRoot)
+        ));
+
+        // Children are converted one at a time as with `stream_convert`, and those left out by
+        // `max_occurrences` are read past, chunks split tokens or not.
+        let cfg = ConversionConfig {
+            max_occurrences: vec![("entry".to_string(), 2)].into_iter().collect(),
+            truncated_count_key: Some("#truncated".into()),
+            ..Default::default()
+        };
+        let src = r#"<log id="1"><entry><msg>a</msg></entry>note<entry><msg>b</msg></entry><entry><msg><i>c</i></msg></entry><end/></log>"#;
+        let expected = json!({
+            "log": {
+                "@id": 1.0,
+                "entry": [{ "msg": "a" }, { "msg": "b" }],
+                "#truncated": { "entry": 1 },
+                "#text": "note"
+            }
+        });
+        assert_eq!(
+            convert_async_reader(src.as_bytes(), &cfg).await.unwrap(),
+            expected
+        );
+        assert_eq!(
+            convert_async_reader(Chunked(src.as_bytes()), &cfg)
+                .await
+                .unwrap(),
+            expected
+        );
+        assert!(matches!(
+            convert_async_reader(Chunked(b"<log><entry/><entry/><entry><msg>"), &cfg).await,
+            Err(ReadError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn element_extension_methods() {
+        let dom_root = treexml::Document::parse(r#"<a x="1"><b>t</b></a>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+
+        assert_eq!(dom_root.to_json_map(), node2object(&dom_root));
+        assert_eq!(
+            dom_root.to_json_value(),
+            json!({ "a": { "@x": 1.0, "b": "t" } })
+        );
+        assert_eq!(
+            serde_json::from_str::<Value>(&dom_root.to_json_string()).unwrap(),
+            dom_root.to_json_value()
+        );
+
+        let cfg = ConversionConfig {
+            include_root: false,
+            ..ConversionConfig::default()
+        };
+        assert_eq!(
+            Value::Object(dom_root.to_json_map_with_config(&cfg).unwrap()),
+            json!({ "@x": 1.0, "b": "t" })
+        );
+    }
+
+    #[test]
+    fn json_event_sink() {
+        #[derive(Debug, PartialEq)]
+        enum Event {
+            StartObject(Option<String>),
+            EndObject,
+            StartArray(Option<String>),
+            EndArray,
+            Scalar(Option<String>, Value),
+            Value(Option<String>, Value),
+        }
+
+        #[derive(Default)]
+        struct Recorder(Vec<Event>);
+
+        impl JsonEventSink for Recorder {
+            fn start_object(&mut self, key: Option<&str>) {
+                self.0.push(Event::StartObject(key.map(str::to_string)));
+            }
+            fn end_object(&mut self) {
+                self.0.push(Event::EndObject);
+            }
+            fn start_array(&mut self, key: Option<&str>) {
+                self.0.push(Event::StartArray(key.map(str::to_string)));
+            }
+            fn end_array(&mut self) {
+                self.0.push(Event::EndArray);
+            }
+            fn scalar(&mut self, key: Option<&str>, value: &Value) {
+                self.0
+                    .push(Event::Scalar(key.map(str::to_string), value.clone()));
+            }
+            fn value(&mut self, key: Option<&str>, value: Value) {
+                self.0.push(Event::Value(key.map(str::to_string), value));
+            }
+        }
+
+        let configs = [
+            ConversionConfig::default(),
+            ConversionConfig {
+                include_root: false,
+                root_element_name_key: Some("#name".into()),
+                inject_source_tag: true,
+                ..Default::default()
+            },
+            ConversionConfig {
+                attribute_position: AttrPosition::Last,
+                ensure_array_keys: vec![
+                    ("a".into(), vec!["c".into(), "z".into()]),
+                    ("b".into(), vec!["d".into()]),
+                ]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            },
+            ConversionConfig {
+                merge_siblings: vec!["b".into()].into_iter().collect(),
+                heterogeneous_arrays: HeteroPolicy::Stringify,
+                ..Default::default()
+            },
+            ConversionConfig {
+                force_array: vec!["b".into(), "c".into()].into_iter().collect(),
+                ..Default::default()
+            },
+            ConversionConfig {
+                single_element_array_threshold: 0,
+                collapse_wrappers: vec!["c".into()].into_iter().collect(),
+                ..Default::default()
+            },
+        ];
+        for (src, cfg) in [
+            include_str!("../tests/fixtures/xml2js.xml"),
+            r#"<a x="1"><b>t</b><b>2</b><c/></a>"#,
+            r#"<a x="1"><b y="1"><c>1</c></b>text<b><c>2</c><d/></b><c/></a>"#,
+            r#"<a><c><d/></c><b><c/></b><b y="1"><c><d/></c></b></a>"#,
+        ]
+        .iter()
+        .flat_map(|src| configs.iter().map(move |cfg| (src, cfg)))
+        {
+            let dom_root = treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+            let mut recorder = Recorder::default();
+            drive(&dom_root, &mut recorder, cfg).unwrap();
+
+            let mut sink = ValueSink::new();
+            for event in &recorder.0 {
+                match event {
+                    Event::StartObject(key) => sink.start_object(key.as_deref()),
+                    Event::EndObject => sink.end_object(),
+                    Event::StartArray(key) => sink.start_array(key.as_deref()),
+                    Event::EndArray => sink.end_array(),
+                    Event::Scalar(key, value) => sink.scalar(key.as_deref(), value),
+                    Event::Value(key, value) => sink.value(key.as_deref(), value.clone()),
+                }
+            }
+            // Same output and counters as converting the whole tree at once.
+            let resolved = namespaces::resolve_namespaces(&dom_root, cfg).unwrap();
+            let (data, stats) = convert_root_with_stats(&resolved, None, cfg).unwrap();
+            assert_eq!(sink.finish(), Some(Value::Object(data)));
+            assert_eq!(node2object_with_stats(&dom_root, cfg).unwrap().1, stats);
+        }
+
+        let cfg = ConversionConfig::default();
+
+        let dom_root = treexml::Document::parse(r#"<a><b>1</b><b>2</b></a>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let mut recorder = Recorder::default();
+        drive(&dom_root, &mut recorder, &cfg).unwrap();
+        assert_eq!(
+            recorder.0,
+            vec![
+                Event::StartObject(None),
+                Event::StartObject(Some("a".into())),
+                Event::StartArray(Some("b".into())),
+                Event::Value(None, json!(1.0)),
+                Event::Value(None, json!(2.0)),
+                Event::EndArray,
+                Event::EndObject,
+                Event::EndObject,
+            ]
+        );
+
+        // Repeated parents are reported item by item.
+        let dom_root = treexml::Document::parse(
+            r#"<r><item id="1"><n>a</n></item><item id="2"><n>b</n></item></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let mut recorder = Recorder::default();
+        drive(&dom_root, &mut recorder, &cfg).unwrap();
+        let item = |id: f64, n: &str| {
+            vec![
+                Event::StartObject(None),
+                Event::Value(Some("@id".into()), json!(id)),
+                Event::Value(Some("n".into()), json!(n)),
+                Event::EndObject,
+            ]
+        };
+        let mut expected = vec![
+            Event::StartObject(None),
+            Event::StartObject(Some("r".into())),
+            Event::StartArray(Some("item".into())),
+        ];
+        expected.extend(item(1.0, "a"));
+        expected.extend(item(2.0, "b"));
+        expected.extend([Event::EndArray, Event::EndObject, Event::EndObject]);
+        assert_eq!(recorder.0, expected);
+    }
+
+    #[test]
+    fn forced_arrays_of_omitted_elements() {
+        let dom_root = treexml::Document::parse(r#"<r><x/><y>1</y></r>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let forced = ConversionConfig {
+            force_array: vec!["x".into()].into_iter().collect(),
+            ..Default::default()
+        };
+        let threshold = ConversionConfig {
+            single_element_array_threshold: 0,
+            ..Default::default()
+        };
+        for cfg in [&forced, &threshold] {
+            // Omitted empty elements leave no empty array behind.
+            let data = node2object_with_config(&dom_root, cfg).unwrap();
+            let expected = match cfg.single_element_array_threshold {
+                0 => json!({ "r": { "y": [1.0] } }),
+                _ => json!({ "r": { "y": 1.0 } }),
+            };
+            assert_eq!(Value::Object(data.clone()), expected);
+            let (with_stats, stats) = node2object_with_stats(&dom_root, cfg).unwrap();
+            assert_eq!(with_stats, data);
+            assert_eq!(stats.element_count, 3);
+        }
+    }
+
+    #[test]
+    fn default_text_value() {
+        let dom_root =
+            treexml::Document::parse(r#"<a><b/><c></c><d>1</d><e x="1"/></a>"#.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+        let mut cfg = ConversionConfig {
+            empty_element_as: EmptyElement::Omit,
+            default_text_value: Some(json!(0)),
+            ..ConversionConfig::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "a": { "b": 0, "c": 0, "d": 1.0, "e": { "@x": 1.0 } } })
+        );
+
+        cfg.defaults.insert("c".into(), json!("none"));
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "a": { "b": 0, "c": "none", "d": 1.0, "e": { "@x": 1.0 } } })
+        );
+    }
+
+    #[test]
+    fn flattened_pairs() {
+        let dom_root = treexml::Document::parse(
+            r#"
+            <population>
+              <entry id="1">
+                <name>Alex</name>
+                <height>173.5</height>
+              </entry>
+              <entry id="2">
+                <name>Mel</name>
+                <height>180.4</height>
+              </entry>
+            </population>
+            "#
+            .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let pairs = node2flat(&dom_root, &FlattenConfig::default()).unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                ("population.entry[0].@id".to_string(), json!(1.0)),
+                ("population.entry[0].name".to_string(), json!("Alex")),
+                ("population.entry[0].height".to_string(), json!(173.5)),
+                ("population.entry[1].@id".to_string(), json!(2.0)),
+                ("population.entry[1].name".to_string(), json!("Mel")),
+                ("population.entry[1].height".to_string(), json!(180.4)),
+            ]
+        );
+
+        let dom_root = treexml::Document::parse(r#"<a><b/><c><d>1</d></c></a>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let cfg = FlattenConfig {
+            conversion: ConversionConfig {
+                include_root: false,
+                empty_element_as: EmptyElement::Null,
+                ..ConversionConfig::default()
+            },
+            separator: "/".into(),
+        };
+        assert_eq!(
+            node2flat(&dom_root, &cfg).unwrap(),
+            vec![
+                ("b".to_string(), Value::Null),
+                ("c/d".to_string(), json!(1.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn merged_documents() {
+        let parse = |src: &str| {
+            treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap()
+        };
+        let monday = parse(
+            r#"<export><date>mon</date><entry>1</entry><entry>2</entry><meta><v>1</v></meta></export>"#,
+        );
+        let tuesday = parse(
+            r#"<export><date>tue</date><entry>3</entry><entry>4</entry><meta><w>2</w></meta></export>"#,
+        );
+
+        let mut options = MergeOptions::default();
+        assert_eq!(
+            merge_documents(&[&monday, &tuesday], &options).unwrap(),
+            json!({ "export": {
+                "date": "tue",
+                "entry": [1.0, 2.0, 3.0, 4.0],
+                "meta": { "v": 1.0, "w": 2.0 }
+            } })
+        );
+
+        options.scalar_conflict = ScalarConflict::First;
+        assert_eq!(
+            merge_documents(&[&monday, &tuesday], &options).unwrap()["export"]["date"],
+            json!("mon")
+        );
+
+        options.scalar_conflict = ScalarConflict::Error;
+        let err = merge_documents(&[&monday, &tuesday], &options).unwrap_err();
+        assert!(matches!(err, ConversionError::MergeConflict { .. }));
+        assert_eq!(err.path().as_str(), "/export/date");
+
+        let wednesday = parse(r#"<export><entry>5</entry></export>"#);
+        options.conversion.force_array.insert("entry".into());
+        assert_eq!(
+            merge_documents(&[&wednesday, &wednesday], &options).unwrap(),
+            json!({ "export": { "entry": [5.0, 5.0] } })
+        );
+
+        let other = parse(r#"<report><n>1</n></report>"#);
+        assert!(matches!(
+            merge_documents(&[&wednesday, &other], &options),
+            Err(ConversionError::RootMismatch { .. })
+        ));
+        options.root_names = RootNames::KeyByName;
+        options.conversion.include_root = false;
+        assert_eq!(
+            merge_documents(&[&wednesday, &other], &options).unwrap(),
+            json!({ "export": { "entry": [5.0] }, "report": { "n": 1.0 } })
+        );
+    }
+
+    #[test]
+    fn split_attribute_values() {
+        let dom_root = treexml::Document::parse(
+            r#"<a class="a  b c" tags="x, y,,z" path="1:2" id="7"/>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let cfg = ConversionConfig {
+            split_attribute_values: vec![
+                ("class".to_string(), SplitStrategy::Whitespace),
+                ("tags".to_string(), SplitStrategy::Comma),
+                ("path".to_string(), SplitStrategy::Char(':')),
+            ]
+            .into_iter()
+            .collect(),
+            ..ConversionConfig::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "a": {
+                "@class": ["a", "b", "c"],
+                "@tags": ["x", "y", "z"],
+                "@path": ["1", "2"],
+                "@id": 7.0
+            } })
+        );
+    }
+
+    #[test]
+    fn value_builders() {
+        let cfg = ConversionConfig::default();
+        for src in [
+            include_str!("../tests/fixtures/xml2js.xml"),
+            r#"<r><item id="1"><n>a</n><n/></item><item id="2"><n>b</n>x</item></r>"#,
+        ] {
+            let dom_root = treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+            let (data, _) = node2object_with_stats(&dom_root, &cfg).unwrap();
+            let expected = Value::Object(data);
+            assert_eq!(
+                node2object_with_builder(&dom_root, &cfg, &SerdeJsonBuilder).unwrap(),
+                expected
+            );
+
+            #[cfg(feature = "simd-json")]
+            {
+                let simd = node2object_with_builder(&dom_root, &cfg, &SimdJsonBuilder).unwrap();
+                assert_eq!(serde_json::to_value(&simd).unwrap(), expected);
+            }
+        }
+
+        let dom_root = treexml::Document::parse(r#"<r><a>1</a><a><b/></a></r>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let cfg = ConversionConfig {
+            max_depth: Some(2),
+            ..ConversionConfig::default()
+        };
+        assert!(matches!(
+            node2object_with_builder(&dom_root, &cfg, &SerdeJsonBuilder),
+            Err(ConversionError::DepthExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn siblings_as_array() {
+        let parse = |src: &str| {
+            treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap()
+        };
+        let a = parse(r#"<item id="1"><name>x</name></item>"#);
+        let b = parse(r#"<entry>2</entry>"#);
+        let c = parse(r#"<empty/>"#);
+        assert_eq!(
+            convert_siblings_as_array(&[&a, &b, &c], &ConversionConfig::default()).unwrap(),
+            json!([{ "@id": 1.0, "name": "x" }, 2.0])
+        );
+
+        let cfg = ConversionConfig {
+            empty_element_as: EmptyElement::Null,
+            coerce_numbers: false,
+            ..ConversionConfig::default()
+        };
+        assert_eq!(
+            convert_siblings_as_array(&[&b, &c], &cfg).unwrap(),
+            json!(["2", null])
+        );
+    }
+
+    #[test]
+    fn inject_source_tag() {
+        let dom_root = treexml::Document::parse(
+            r#"<shapes><circle r="1"/><square><side>2</side></square><n>3</n><_tag>x</_tag></shapes>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let cfg = ConversionConfig {
+            include_root: false,
+            inject_source_tag: true,
+            ..ConversionConfig::default()
+        };
+        let expected = json!({
+            "_tag": "shapes",
+            "circle": { "@r": 1.0, "_tag": "circle" },
+            "square": { "side": 2.0, "_tag": "square" },
+            "n": 3.0,
+            "__tag": "x"
+        });
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            expected
+        );
+        assert_eq!(
+            Value::Object(
+                node2object_lazy(&dom_root, &cfg)
+                    .collect::<Result<Map<_, _>, _>>()
+                    .unwrap()
+            ),
+            expected
+        );
+        #[cfg(feature = "rayon")]
+        assert_eq!(
+            Value::Object(node2object_parallel_children(&dom_root, &cfg).unwrap()),
+            expected
+        );
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn encoded_bytes() {
+        let cfg = ConversionConfig::default();
+        let expected = json!({ "city": { "name": "Zürich", "note": "café · ñ" } });
+        for bytes in [
+            &include_bytes!("../tests/fixtures/utf8-bom.xml")[..],
+            &include_bytes!("../tests/fixtures/utf16le.xml")[..],
+            &include_bytes!("../tests/fixtures/latin1.xml")[..],
+        ] {
+            assert_eq!(xml_bytes_to_json(bytes, &cfg).unwrap(), expected);
+        }
+
+        let utf16: Vec<u8> = r#"<?xml version="1.0" encoding="UTF-16"?><a>ß</a>"#
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(
+            xml_bytes_to_json(&utf16, &cfg).unwrap(),
+            json!({ "a": "ß" })
+        );
+        assert!(matches!(
+            xml_bytes_to_json(br#"<?xml version="1.0" encoding="x-klingon"?><a/>"#, &cfg),
+            Err(ReadError::UnknownEncoding(encoding)) if encoding == "x-klingon"
+        ));
+        assert!(matches!(
+            xml_bytes_to_json(b"<a>\xff</a>", &cfg),
+            Err(ReadError::Malformed { .. })
+        ));
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn simd_api() {
+        let dom_root = treexml::Document::parse(r#"<a x="1"><b>t</b><b>2</b></a>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let expected = Value::Object(node2object(&dom_root));
+        let data = simd::node2object(&dom_root);
+        assert_eq!(serde_json::to_value(&data).unwrap(), expected);
+        assert_eq!(
+            serde_json::from_str::<Value>(&simd_json::to_string(&data).unwrap()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn id_references() {
+        let parse = |src: &str| {
+            treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap()
+        };
+        let dom_root = parse(
+            r#"<library><author id="a1"><name>Le Guin</name></author><book author-ref="a1"><title>Lathe</title></book><book author-ref="a9"><title>Dune</title></book></library>"#,
+        );
+        let mut cfg = ConversionConfig {
+            id_references: Some(IdReferences {
+                reference_attributes: vec!["author-ref".into()],
+                ..IdReferences::default()
+            }),
+            ..ConversionConfig::default()
+        };
+        let author = json!({ "@id": "a1", "name": "Le Guin" });
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "library": {
+                "author": author,
+                "book": [
+                    { "@author-ref": author, "title": "Lathe" },
+                    { "@author-ref": "a9", "title": "Dune" }
+                ]
+            } })
+        );
+
+        cfg.strict = true;
+        let err = node2object_with_config(&dom_root, &cfg).unwrap_err();
+        assert!(matches!(err, ConversionError::DanglingReference { ref id, .. } if id == "a9"));
+        assert_eq!(err.path().as_str(), "/library/book/1/@author-ref");
+        assert_eq!(err.xml_path().as_str(), "library/book[2]/@author-ref");
+        #[cfg(feature = "xml-rs")]
+        {
+            let src = r#"<library><author id="a1"/><book author-ref="a9"/></library>"#;
+            match xmlrs_document_to_value(src.as_bytes(), &cfg).unwrap_err() {
+                XmlRsConversionError::Conversion(err) => {
+                    assert_eq!(err.xml_path().as_str(), "library/book/@author-ref")
+                }
+                err => panic!("unexpected error {:?}", err),
+            }
+        }
+
+        cfg.strict = false;
+        if let Some(refs) = &mut cfg.id_references {
+            refs.mode = ReferenceMode::Pointer;
+        }
+        assert_eq!(
+            node2object_with_config(&dom_root, &cfg).unwrap()["library"]["book"][0]["@author-ref"],
+            json!("/library/author")
+        );
+
+        let dom_root =
+            parse(r#"<r><n id="1" next="2"><v>x</v></n><n id="2" next="1"><v>y</v></n></r>"#);
+        let mut cfg = ConversionConfig {
+            id_references: Some(IdReferences {
+                reference_attributes: vec!["next".into()],
+                ..IdReferences::default()
+            }),
+            ..ConversionConfig::default()
+        };
+        let err = node2object_with_config(&dom_root, &cfg).unwrap_err();
+        assert!(matches!(err, ConversionError::ReferenceCycle { .. }));
+        // The reference closing the cycle is inlined, but located where it is written.
+        assert_eq!(err.xml_path().as_str(), "r/n[1]/@next");
+        if let Some(refs) = &mut cfg.id_references {
+            refs.mode = ReferenceMode::Pointer;
+        }
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": { "n": [
+                { "@id": 1.0, "@next": "/r/n/1", "v": "x" },
+                { "@id": 2.0, "@next": "/r/n/0", "v": "y" }
+            ] } })
+        );
+
+        // References fanning out at every level are inlined up to a limit.
+        let dom_root = parse(
+            r#"<r><c id="c"/><b id="b" left="c" right="c"/><a id="a" left="b" right="b"/><top left="a" right="a"/></r>"#,
+        );
+        let mut cfg = ConversionConfig {
+            id_references: Some(IdReferences {
+                reference_attributes: vec!["left".into(), "right".into()],
+                ..IdReferences::default()
+            }),
+            ..ConversionConfig::default()
+        };
+        let data = node2object_with_config(&dom_root, &cfg).unwrap();
+        assert_eq!(
+            data["r"]["top"]["@left"]["@right"]["@left"],
+            json!({ "@id": "c" })
+        );
+        if let Some(refs) = &mut cfg.id_references {
+            refs.max_inlined = 10;
+        }
+        let err = node2object_with_config(&dom_root, &cfg).unwrap_err();
+        assert!(matches!(
+            err,
+            ConversionError::InliningLimitExceeded { limit: 10, .. }
+        ));
+    }
+
+    #[test]
+    fn tree_stats() {
+        let dom_root = treexml::Document::parse(
+            r#"<a x="1"><b y="2" z="3">t</b><c><b><![CDATA[d]]></b><e/></c><e/></a>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        assert_eq!(
+            element_tree_stats(&dom_root),
+            TreeStats {
+                element_count: 6,
+                max_depth: 3,
+                unique_tags: vec!["a", "b", "c", "e"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                total_attributes: 3,
+                text_node_count: 2,
+                empty_node_count: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn ensure_array_keys() {
+        let cfg = ConversionConfig {
+            ensure_array_keys: vec![("items".to_string(), vec!["item".to_string()])]
+                .into_iter()
+                .collect(),
+            ..ConversionConfig::default()
+        };
+        for (src, expected) in [
+            ("<items/>", json!({ "items": { "item": [] } })),
+            (
+                "<items><n>1</n></items>",
+                json!({ "items": { "n": 1.0, "item": [] } }),
+            ),
+            (
+                "<items><item>a</item></items>",
+                json!({ "items": { "item": ["a"] } }),
+            ),
+            (
+                "<items><item>a</item><item>b</item><item>c</item></items>",
+                json!({ "items": { "item": ["a", "b", "c"] } }),
+            ),
+            (
+                "<r><items/><other/></r>",
+                json!({ "r": { "items": { "item": [] } } }),
+            ),
+        ] {
+            let dom_root = treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+            assert_eq!(
+                Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+                expected
+            );
+            assert_eq!(
+                Value::Object(
+                    node2object_lazy(&dom_root, &cfg)
+                        .collect::<Result<Map<_, _>, _>>()
+                        .unwrap()
+                ),
+                expected[&dom_root.name]
+            );
+            #[cfg(feature = "rayon")]
+            assert_eq!(
+                Value::Object(node2object_parallel_children(&dom_root, &cfg).unwrap()),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn text_merge() {
+        let dom_root = treexml::Document::parse(r#"<a>text<![CDATA[cdata]]></a>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let mut cfg = ConversionConfig::default();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "a": "textcdata" })
+        );
+
+        cfg.text_merge_separator = "\n".into();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "a": "text\ncdata" })
+        );
+
+        cfg.text_merge_order = TextMergeOrder::CdataFirst;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "a": "cdata\ntext" })
+        );
+
+        let dom_root = treexml::Document::parse(r#"<a><![CDATA[only]]></a>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "a": "only" })
+        );
+    }
+
+    #[test]
+    fn arrays_as_indexed_objects_pads_indices() {
+        let cfg = ConversionConfig {
+            arrays_as_indexed_objects: true,
+            ..ConversionConfig::default()
+        };
+
+        let dom_root = treexml::Document::parse("<l><i>a</i><i>b</i><i>c</i></l>".as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "l": { "i": { "0": "a", "1": "b", "2": "c" } } })
+        );
+
+        let xml = format!(
+            "<l>{}</l>",
+            (0..12)
+                .map(|i| format!("<i>v{}</i>", i))
+                .collect::<String>()
+        );
+        let dom_root = treexml::Document::parse(xml.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let data = node2object_with_config(&dom_root, &cfg).unwrap();
+        let items = data["l"]["i"].as_object().unwrap();
+        let keys = items.keys().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(keys.len(), 12);
+        assert!(keys.iter().all(|k| k.len() == 2));
+        assert_eq!(items["00"], json!("v0"));
+        assert_eq!(items["09"], json!("v9"));
+        assert_eq!(items["11"], json!("v11"));
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(
+            sorted.iter().map(|k| &items[*k]).collect::<Vec<_>>(),
+            (0..12)
+                .map(|i| &items[&format!("{:02}", i)])
+                .collect::<Vec<_>>()
+        );
+
+        let one_based = ConversionConfig {
+            indexed_object_base: 1,
+            ..cfg.clone()
+        };
+        let data = node2object_with_config(&dom_root, &one_based).unwrap();
+        let items = data["l"]["i"].as_object().unwrap();
+        assert_eq!(items["01"], json!("v0"));
+        assert_eq!(items["12"], json!("v11"));
+        assert!(!items.contains_key("00"));
+    }
+
+    #[test]
+    fn arrays_as_indexed_objects_with_force_array() {
+        let mut cfg = ConversionConfig {
+            arrays_as_indexed_objects: true,
+            ..ConversionConfig::default()
+        };
+        cfg.force_array.insert("i".to_string());
+
+        let dom_root = treexml::Document::parse("<l><i>a</i></l>".as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "l": { "i": { "0": "a" } } })
+        );
+        assert_eq!(
+            node2object_lazy(&dom_root, &cfg)
+                .collect::<Result<Map<_, _>, _>>()
+                .unwrap(),
+            json!({ "i": { "0": "a" } }).as_object().unwrap().clone()
+        );
+    }
+
+    #[test]
+    fn text_parsers() {
+        use std::sync::Arc;
+
+        let dom_root = treexml::Document::parse(
+            r#"<r n="7"><a>42</a><b>1.5</b><c>true</c><d>+3</d><e>text</e></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let convert = |parser: Arc<dyn TextParser>| {
+            let cfg = ConversionConfig {
+                text_parser: Some(parser),
+                ..ConversionConfig::default()
+            };
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap())
+        };
+
+        assert_eq!(
+            convert(Arc::new(StandardTextParser)),
+            Value::Object(node2object(&dom_root))
+        );
+        assert_eq!(
+            convert(Arc::new(StrictStringParser)),
+            json!({ "r": { "@n": "7", "a": "42", "b": "1.5", "c": "true", "d": "+3", "e": "text" } })
+        );
+        assert_eq!(
+            convert(Arc::new(IntegerPreferringTextParser)),
+            json!({ "r": { "@n": 7, "a": 42, "b": 1.5, "c": true, "d": "+3", "e": "text" } })
+        );
+
+        struct Upper;
+        impl TextParser for Upper {
+            fn parse(&self, text: &str) -> Value {
+                Value::String(text.to_uppercase())
+            }
+        }
+        assert_eq!(convert(Arc::new(Upper))["r"]["e"], json!("TEXT"));
+    }
+
+    #[test]
+    fn prefix_map() {
+        // xml-rs consumes namespace declarations while parsing, so add them back by hand.
+        let parse = |src: &str, declarations: &[(&str, &str)]| {
+            let mut e = treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+            for (name, uri) in declarations {
+                e.attributes.insert(name.to_string(), uri.to_string());
+            }
+            e
+        };
+        let first = parse(
+            r#"<ns1:order xmlns:ns1="urn:acme" ns1:id="7"><ns1:item>a</ns1:item><note>n</note></ns1:order>"#,
+            &[("xmlns:ns1", "urn:acme")],
+        );
+        let second = parse(
+            r#"<order xmlns="urn:acme" xmlns:m="urn:acme" m:id="7"><m:item>a</m:item><note>n</note></order>"#,
+            &[("xmlns", "urn:acme"), ("xmlns:m", "urn:acme")],
+        );
+
+        let mut cfg = ConversionConfig {
+            strip_xmlns_attributes: true,
+            ..ConversionConfig::default()
+        };
+        assert_ne!(
+            node2object_with_config(&first, &cfg).unwrap(),
+            node2object_with_config(&second, &cfg).unwrap()
+        );
+
+        cfg.prefix_map
+            .insert("urn:acme".to_string(), "acme".to_string());
+        let expected = json!({
+            "acme:order": {
+                "@acme:id": 7.0,
+                "acme:item": "a",
+                "acme:note": "n"
+            }
+        });
+        // Unprefixed children of the first document are in no namespace.
+        assert_eq!(
+            Value::Object(node2object_with_config(&first, &cfg).unwrap()),
+            json!({ "acme:order": { "@acme:id": 7.0, "acme:item": "a", "note": "n" } })
+        );
+        assert_eq!(
+            Value::Object(node2object_with_config(&second, &cfg).unwrap()),
+            expected
+        );
+
+        let third = parse(
+            r#"<x:order xmlns:x="urn:acme" x:id="7"><x:item>a</x:item><x:note>n</x:note></x:order>"#,
+            &[("xmlns:x", "urn:acme")],
+        );
+        assert_eq!(
+            node2object_with_config(&third, &cfg).unwrap(),
+            node2object_with_config(&second, &cfg).unwrap()
+        );
+
+        cfg.strip_xmlns_attributes = false;
+        assert_eq!(
+            Value::Object(node2object_with_config(&third, &cfg).unwrap())["acme:order"]
+                ["@xmlns:acme"],
+            json!("urn:acme")
+        );
+    }
+
+    #[test]
+    fn node_with_metadata() {
+        let dom_root =
+            treexml::Document::parse(r#"<r><a>1</a><b id="x">t</b><c/><d><e/></d></r>"#.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+        let cfg = ConversionConfig::default();
+        let convert = |i: usize| convert_node_with_metadata(&dom_root.children[i], &cfg).unwrap();
+
+        assert_eq!(convert(0), (Some(json!(1.0)), XMLNodeType::Text));
+        assert_eq!(
+            convert(1),
+            (
+                Some(json!({ "@id": "x", "#text": "t" })),
+                XMLNodeType::TextAndAttributes
+            )
+        );
+        assert_eq!(convert(2), (None, XMLNodeType::Empty));
+        assert_eq!(convert(3), (Some(json!({})), XMLNodeType::Parent));
+        assert_eq!(
+            convert_node_with_metadata(&dom_root, &cfg).unwrap(),
+            (
+                Some(node2object(&dom_root)["r"].clone()),
+                XMLNodeType::Parent
+            )
+        );
+
+        // The node type is the one the conversion followed.
+        let cfg = ConversionConfig {
+            collapse_wrappers: vec!["d".to_string()],
+            skip_attributes: vec!["id".to_string()],
+            ..ConversionConfig::default()
+        };
+        let convert = |i: usize| convert_node_with_metadata(&dom_root.children[i], &cfg).unwrap();
+        assert_eq!(convert(1), (Some(json!("t")), XMLNodeType::Text));
+        assert_eq!(convert(3), (None, XMLNodeType::Empty));
+    }
+
+    #[test]
+    fn strip_selected_namespaces() {
+        const SOAP: &str = "http://www.w3.org/2003/05/soap-envelope";
+        let mut dom_root =
+            treexml::Document::parse(include_str!("../tests/fixtures/soap.xml").as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+        // xml-rs consumes namespace declarations while parsing, so add them back by hand.
+        dom_root
+            .attributes
+            .insert("xmlns:soap".to_string(), SOAP.to_string());
+        dom_root
+            .attributes
+            .insert("xmlns:acme".to_string(), "urn:acme".to_string());
+
+        let mut cfg = ConversionConfig {
+            namespace_prefixes: PrefixHandling::Keep,
+            ..ConversionConfig::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "soap:Envelope": {
+                    "@xmlns:soap": SOAP,
+                    "@xmlns:acme": "urn:acme",
+                    "soap:Header": {
+                        "acme:trace": { "@soap:mustUnderstand": true, "#text": "abc" }
+                    },
+                    "soap:Body": {
+                        "acme:order": {
+                            "@acme:id": 7.0,
+                            "acme:item": "widget",
+                            "note": "fragile"
+                        }
+                    }
+                }
+            })
+        );
+
+        cfg.strip_namespaces.insert(SOAP.to_string());
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "Envelope": {
+                    "@xmlns:acme": "urn:acme",
+                    "Header": {
+                        "acme:trace": { "@mustUnderstand": true, "#text": "abc" }
+                    },
+                    "Body": {
+                        "acme:order": {
+                            "@acme:id": 7.0,
+                            "acme:item": "widget",
+                            "note": "fragile"
+                        }
+                    }
+                }
+            })
+        );
+
+        cfg.namespace_prefixes = PrefixHandling::Strip;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap())["Envelope"]["Body"],
+            json!({ "order": { "@id": 7.0, "item": "widget", "note": "fragile" } })
+        );
+
+        // Stripping can make an element collide with an unprefixed sibling.
+        let mut body = treexml::Element::new("Body");
+        body.prefix = Some("soap".to_string());
+        body.attributes
+            .insert("xmlns:soap".to_string(), SOAP.to_string());
+        body.attributes
+            .insert("soap:id".to_string(), "1".to_string());
+        body.attributes.insert("id".to_string(), "2".to_string());
+        let mut first = treexml::Element::new("x");
+        first.prefix = Some("soap".to_string());
+        first.text = Some("a".to_string());
+        let mut second = treexml::Element::new("x");
+        second.text = Some("b".to_string());
+        body.children = vec![first, second];
+        assert_eq!(
+            Value::Object(node2object_with_config(&body, &cfg).unwrap()),
+            json!({ "Body": { "@id": 2.0, "x": ["a", "b"] } })
+        );
+
+        // Attributes ending up with the same name go through the collision policy too.
+        cfg.attribute_child_collision = CollisionStrategy::Error;
+        let mut envelope = treexml::Element::new("Envelope");
+        envelope.children.push(body);
+        match node2object_with_config(&envelope, &cfg).unwrap_err() {
+            ConversionError::AttributeChildCollision {
+                key,
+                path,
+                xml_path,
+            } => {
+                assert_eq!(key, "@id");
+                assert_eq!(path.to_string(), "/Envelope/Body/@id");
+                assert_eq!(xml_path.as_str(), "Envelope/Body");
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn attribute_namespace_separator() {
+        let dom_root = treexml::Document::parse(
+            r#"<r xmlns:x="urn:x" xml:lang="en" x:a="1"><x:c>t</x:c></r>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let mut cfg = ConversionConfig {
+            attribute_namespace_separator: '_',
+            ..ConversionConfig::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": { "@xml_lang": "en", "@x_a": 1.0, "c": "t" } })
+        );
+
+        cfg.attribute_namespace_separator = '/';
+        cfg.namespace_prefixes = PrefixHandling::Keep;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": { "@xml/lang": "en", "@x/a": 1.0, "x/c": "t" } })
+        );
+
+        cfg.namespace_prefixes = PrefixHandling::Strip;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "r": { "@lang": "en", "@a": 1.0, "c": "t" } })
+        );
+    }
+
+    #[test]
+    fn default_namespace() {
+        const ATOM: &str = "http://www.w3.org/2005/Atom";
+        let mut dom_root = treexml::Document::parse(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>t</title><ext xmlns=""><item>i</item></ext></feed>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        // xml-rs consumes namespace declarations while parsing, so add them back by hand.
+        dom_root
+            .attributes
+            .insert("xmlns".to_string(), ATOM.to_string());
+        dom_root.children[1]
+            .attributes
+            .insert("xmlns".to_string(), String::new());
+
+        let mut cfg = ConversionConfig {
+            strip_xmlns_attributes: true,
+            ..ConversionConfig::default()
+        };
+        let ignored = json!({ "feed": { "title": "t", "ext": { "item": "i" } } });
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            ignored
+        );
+        cfg.default_namespace = DefaultNsHandling::Ignore;
+        cfg.prefix_map
+            .insert("urn:unused".to_string(), "u".to_string());
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            ignored
+        );
+
+        cfg.default_namespace = DefaultNsHandling::ApplyPrefix("atom".to_string());
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "atom:feed": { "atom:title": "t", "ext": { "item": "i" } } })
+        );
+
+        cfg.default_namespace = DefaultNsHandling::Expand;
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "{http://www.w3.org/2005/Atom}feed": {
+                    "{http://www.w3.org/2005/Atom}title": "t",
+                    "ext": { "item": "i" }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn fragment_array() {
+        let cfg = ConversionConfig::default();
+        assert_eq!(
+            fragment_to_array(r#"<msg id="1">hi</msg><msg id="2"/><ping/>"#, &cfg).unwrap(),
+            json!([{ "@id": 1.0, "#text": "hi" }, { "@id": 2.0 }])
+        );
+        assert_eq!(fragment_to_array("", &cfg).unwrap(), json!([]));
+        assert_eq!(
+            fragment_to_array(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<msg>hi</msg><msg>there</msg>",
+                &cfg
+            )
+            .unwrap(),
+            json!(["hi", "there"])
+        );
+        // Processing instructions other than the declaration are kept.
+        assert_eq!(
+            fragment_to_array("<?xml-stylesheet href=\"a.xsl\"?><msg>hi</msg>", &cfg).unwrap(),
+            json!(["hi"])
+        );
+        assert!(matches!(
+            fragment_to_array("<msg>hi</msg></_root><msg>there</msg>", &cfg),
+            Err(FragmentError::Parse(_))
+        ));
+        assert!(matches!(
+            fragment_to_array("<a><b></a>", &cfg),
+            Err(FragmentError::Parse(_))
+        ));
+
+        let shallow = ConversionConfig {
+            max_depth: Some(1),
+            ..ConversionConfig::default()
+        };
+        assert!(matches!(
+            fragment_to_array("<a><b/></a>", &shallow),
+            Err(FragmentError::Conversion(
+                ConversionError::DepthExceeded { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn namespace_metadata() {
+        const ATOM: &str = "http://www.w3.org/2005/Atom";
+        let mut dom_root = treexml::Document::parse(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:x="urn:x"><title>t</title><x:count>3</x:count><link x:rel="self"/><plain xmlns="">p</plain></feed>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        // xml-rs consumes namespace declarations while parsing, so add them back by hand.
+        dom_root
+            .attributes
+            .insert("xmlns".to_string(), ATOM.to_string());
+        dom_root
+            .attributes
+            .insert("xmlns:x".to_string(), "urn:x".to_string());
+        dom_root.children[3]
+            .attributes
+            .insert("xmlns".to_string(), String::new());
+
+        let mut cfg = ConversionConfig {
+            strip_xmlns_attributes: true,
+            ..ConversionConfig::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "feed": { "title": "t", "count": 3.0, "link": { "@x:rel": "self" }, "plain": "p" }
+            })
+        );
+
+        cfg.namespace_key = Some("#ns".to_string());
+        let expected = json!({
+            "feed": {
+                "#ns": ATOM,
+                "title": { "#text": "t", "#ns": ATOM },
+                "count": { "#text": 3.0, "#ns": "urn:x" },
+                "link": {
+                    "@x:rel": "self",
+                    "#ns": ATOM,
+                    "#attr_ns": { "@x:rel": "urn:x" }
+                },
+                "plain": "p"
+            }
+        });
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            expected
+        );
+        #[cfg(feature = "rayon")]
+        assert_eq!(
+            Value::Object(node2object_parallel_children(&dom_root, &cfg).unwrap()),
+            expected
+        );
+        for pointer in ["/feed/count", "/feed/link", "/feed/link/#attr_ns"] {
+            assert_eq!(
+                extract(&dom_root, pointer, &cfg).unwrap().as_ref(),
+                expected.pointer(pointer)
+            );
+        }
+    }
+
+    #[test]
+    fn omit_empty_attributes() {
+        let dom_root = treexml::Document::parse(r#"<e attr=""/>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let cfg = ConversionConfig {
+            empty_attribute_as: EmptyAttribute::Skip,
+            ..ConversionConfig::default()
+        };
+        assert_eq!(
+            Value::Object(node2object(&dom_root)),
+            json!({ "e": { "@attr": "" } })
+        );
+        // The attribute is left out entirely, leaving an empty root element.
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": null })
+        );
+        let cfg = ConversionConfig {
+            include_root: false,
+            ..cfg
+        };
+        assert_eq!(
+            node2object_with_config(&dom_root, &cfg).unwrap(),
+            Map::new()
+        );
+
+        // `omit_empty_attributes` drops the entry, but the element keeps its object.
+        let cfg = ConversionConfig {
+            omit_empty_attributes: true,
+            ..ConversionConfig::default()
+        };
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": {} })
+        );
+        let dom_root = treexml::Document::parse(r#"<e attr="" id="1"><a>x</a></e>"#.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({ "e": { "@id": 1.0, "a": "x" } })
+        );
+    }
+
+    #[test]
+    fn coerce_scalar_matches_conversion() {
+        const CORPUS: &[&str] = &[
+            "42",
+            "-0",
+            "+1",
+            "007",
+            " 12 ",
+            "1.5e3",
+            "1E400",
+            "-1e-400",
+            "18446744073709551616",
+            "9007199254740993",
+            "0x1F",
+            "1,000",
+            "1.000,5",
+            ".5",
+            "5.",
+            "NaN",
+            "inf",
+            "-infinity",
+            "true",
+            "True",
+            "false ",
+            "null",
+            "",
+            "text",
+            "2021-03-04",
+        ];
+        let configs = vec![
+            ConversionConfig::default(),
+            ConversionConfig {
+                trim_text: true,
+                coerce_padded_numbers: true,
+                parse_radix_literals: true,
+                allow_thousands_separators: true,
+                allow_leading_plus: true,
+                ..ConversionConfig::default()
+            },
+            ConversionConfig {
+                strict_number_roundtrip: true,
+                coerce_scientific_notation: false,
+                boolean_in_text: false,
+                ..ConversionConfig::default()
+            },
+            ConversionConfig {
+                decimal_separator: DecimalSeparator::Comma,
+                allow_thousands_separators: true,
+                preserve_original_string: true,
+                ..ConversionConfig::default()
+            },
+            ConversionConfig {
+                coerce_numbers: false,
+                ..ConversionConfig::default()
+            },
+        ];
+
+        for cfg in &configs {
+            for text in CORPUS {
+                let mut e = treexml::Element::new("e");
+                e.text = Some(text.to_string()).filter(|t| !t.is_empty());
+                let cfg = ConversionConfig {
+                    empty_element_as: EmptyElement::EmptyString,
+                    ..cfg.clone()
+                };
+                assert_eq!(
+                    node2object_with_config(&e, &cfg).unwrap()["e"],
+                    coerce_scalar(text, &cfg),
+                    "{:?}",
+                    text
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "streaming")]
+    #[test]
+    fn streaming() {
+        #[derive(Default)]
+        struct Recorder(Vec<String>, Vec<(String, Value)>);
+        impl StreamHandler for Recorder {
+            fn on_start(&mut self, name: &str) {
+                self.0.push(format!("start {}", name));
+            }
+            fn on_end(&mut self, name: &str) {
+                self.0.push(format!("end {}", name));
+            }
+            fn on_text(&mut self, text: &str) {
+                self.0.push(format!("text {}", text));
+            }
+            fn on_object(&mut self, key: &str, value: Value) {
+                self.0.push(format!("object {}", key));
+                self.1.push((key.to_string(), value));
+            }
+        }
+
+        let src = r#"<?xml version="1.0"?>
+<log id="1">
+    <entry level="info"><msg>started</msg></entry>
+    note &amp; more
+    <entry level="warn"><msg>slow</msg><ms>250</ms></entry>
+    <empty/>
+    <![CDATA[<raw>]]>
+</log>"#;
+        let cfg = ConversionConfig::default();
+        let mut recorder = Recorder::default();
+        stream_convert(src.as_bytes(), &mut recorder, &cfg).unwrap();
+        assert_eq!(
+            recorder.0,
+            vec![
+                "start log",
+                "object @id",
+                "object entry",
+                "text \n    note & more\n    ",
+                "object entry",
+                "text <raw>",
+                "end log"
+            ]
+        );
+        assert_eq!(
+            recorder.1,
+            vec![
+                ("@id".to_string(), json!(1.0)),
+                (
+                    "entry".to_string(),
+                    json!({ "@level": "info", "msg": "started" })
+                ),
+                (
+                    "entry".to_string(),
+                    json!({ "@level": "warn", "msg": "slow", "ms": 250.0 })
+                ),
+            ]
+        );
+
+        let mut recorder = Recorder::default();
+        stream_convert(&b"<a x=\"y\"/>"[..], &mut recorder, &cfg).unwrap();
+        assert_eq!(recorder.0, vec!["start a", "object @x", "end a"]);
+
+        for src in ["<a><b></a>", "<a><b>", "<a/><b/>", "text", "<!-- none -->"] {
+            let result = stream_convert(src.as_bytes(), &mut Recorder::default(), &cfg);
+            match src {
+                "<!-- none -->" => assert!(matches!(result, Err(StreamError::NoRoot))),
+                _ => assert!(matches!(result, Err(StreamError::Parse { .. })), "{}", src),
+            }
+        }
+    }
+
+    #[cfg(feature = "quick-xml")]
+    #[test]
+    fn quick_xml_backend() {
+        use quick_xml::events::Event;
+
+        let src = r#"<feed xmlns:x="urn:x">
+    <entry id="1"><title>Dune &amp; more</title><x:price>9.5</x:price></entry>
+    <skip><entry id="nested"/></skip>
+    <entry id="2"><title><![CDATA[<Emma>]]></title><tag/><tag/></entry>
+</feed>"#;
+        let dom_root = treexml::Document::parse(src.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let cfg = ConversionConfig::default();
+
+        let mut reader = quick_xml::Reader::from_str(src);
+        reader.config_mut().expand_empty_elements = true;
+        let mut converted = Vec::new();
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(start) if start.name().as_ref() == b"entry" => {
+                    let start = start.into_owned();
+                    converted.push(qxml_node2object(&start, &mut reader, &cfg).unwrap());
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            converted,
+            vec![
+                node2object_with_config(&dom_root.children[0], &cfg).unwrap(),
+                node2object_with_config(&dom_root.children[1].children[0], &cfg).unwrap(),
+                node2object_with_config(&dom_root.children[2], &cfg).unwrap(),
+            ]
+        );
+
+        let mut reader = quick_xml::Reader::from_str("<a><b><c></b></a>");
+        let start = match reader.read_event().unwrap() {
+            Event::Start(start) => start.into_owned(),
+            _ => unreachable!(),
+        };
+        match qxml_node2object(&start, &mut reader, &cfg) {
+            Err(ConversionError::Malformed { xml_path, .. }) => {
+                assert_eq!(xml_path.as_str(), "a/b/c")
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // Namespace declarations are read for the namespace options but not converted.
+        let mut reader = quick_xml::Reader::from_str(src);
+        let start = match reader.read_event().unwrap() {
+            Event::Start(start) => start.into_owned(),
+            _ => unreachable!(),
+        };
+        let cfg = ConversionConfig {
+            namespace_key: Some("#ns".into()),
+            prefix_map: vec![("urn:x".into(), "ext".into())].into_iter().collect(),
+            ..Default::default()
+        };
+        let result = qxml_node2object(&start, &mut reader, &cfg).unwrap();
+        assert_eq!(
+            result["feed"]["entry"][0]["ext:price"],
+            json!({"#text": 9.5, "#ns": "urn:x"})
+        );
+        assert!(result["feed"].get("@xmlns:x").is_none());
+        assert!(result["feed"].get("@xmlns:ext").is_none());
+    }
+
+    #[cfg(feature = "quick-xml")]
+    #[test]
+    fn quick_xml_unexpanded_entities() {
+        use quick_xml::events::Event;
+
+        let convert = |src: &str, cfg: &ConversionConfig| {
+            let mut reader = quick_xml::Reader::from_str(src);
+            let start = match reader.read_event().unwrap() {
+                Event::Start(start) => start.into_owned(),
+                _ => unreachable!(),
+            };
+            qxml_node2object(&start, &mut reader, cfg).map(Value::Object)
+        };
+
+        let src = r#"<e a="&lt;&#169;">&#169; 2024 &amp; co</e>"#;
+        assert_eq!(
+            convert(src, &ConversionConfig::default()).unwrap(),
+            json!({ "e": { "@a": "<\u{a9}", "#text": "\u{a9} 2024 & co" } })
+        );
+        let cfg = ConversionConfig {
+            expand_entities: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            convert(src, &cfg).unwrap(),
+            json!({ "e": { "@a": "&lt;&#169;", "#text": "&#169; 2024 &amp; co" } })
+        );
+        // Undeclared entities are kept too, as nothing is expanded.
+        assert_eq!(
+            convert("<e>&copy;</e>", &cfg).unwrap(),
+            json!({ "e": "&copy;" })
+        );
+    }
+
+    #[cfg(feature = "quick-xml")]
+    #[test]
+    fn quick_xml_entity_handling() {
+        use quick_xml::events::Event;
+
+        let convert = |src: &str, handling: EntityHandling| {
+            let mut reader = quick_xml::Reader::from_str(src);
+            let start = match reader.read_event().unwrap() {
+                Event::Start(start) => start.into_owned(),
+                _ => unreachable!(),
+            };
+            let cfg = ConversionConfig {
+                entity_handling: handling,
+                ..Default::default()
+            };
+            qxml_node2object(&start, &mut reader, &cfg).map(Value::Object)
+        };
+
+        let src = r#"<r a="&lt;b&gt;"><t>Tom &amp; Jerry &#169;</t><c>&copy; 2024</c></r>"#;
+        assert!(matches!(
+            convert(src, EntityHandling::Default),
+            Err(ConversionError::Malformed { .. })
+        ));
+        assert_eq!(
+            convert(src, EntityHandling::ExpandBuiltin).unwrap(),
+            json!({ "r": { "@a": "<b>", "t": "Tom & Jerry \u{a9}", "c": "&copy; 2024" } })
+        );
+        assert_eq!(
+            convert(src, EntityHandling::PreserveAsString).unwrap(),
+            json!({ "r": { "@a": "&lt;b&gt;", "t": "Tom &amp; Jerry \u{a9}", "c": "&copy; 2024" } })
+        );
+        match convert(src, EntityHandling::Error) {
+            Err(ConversionError::UnresolvableEntity {
+                entity, xml_path, ..
+            }) => {
+                assert_eq!(entity, "copy");
+                assert_eq!(xml_path.to_string(), "r/c");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // Documents without undeclared entities convert the same with every variant but one.
+        let src = r#"<r a="&lt;b&gt;">Tom &amp; Jerry &#169;</r>"#;
+        for handling in [
+            EntityHandling::Default,
+            EntityHandling::ExpandBuiltin,
+            EntityHandling::Error,
+        ] {
+            assert_eq!(
+                convert(src, handling).unwrap(),
+                json!({ "r": { "@a": "<b>", "#text": "Tom & Jerry \u{a9}" } })
+            );
+        }
+    }
+
+    #[cfg(feature = "quick-xml")]
+    #[test]
+    fn quick_xml_comments() {
+        use quick_xml::events::Event;
+
+        let src =
+            r#"<r><!-- first --><?render fast?><a>1<!--note--></a><b>x</b><!-- second --></r>"#;
+        let convert = |cfg: &ConversionConfig| {
+            let mut reader = quick_xml::Reader::from_str(src);
+            let start = match reader.read_event().unwrap() {
+                Event::Start(start) => start.into_owned(),
+                _ => unreachable!(),
+            };
+            qxml_node2object(&start, &mut reader, cfg).map(Value::Object)
+        };
+
+        let cfg = ConversionConfig {
+            comment_key: Some("#comment".into()),
+            pi_key: Some("#pi".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            convert(&cfg).unwrap(),
+            json!({
+                "r": {
+                    "a": { "#text": 1.0, "#comment": "note" },
+                    "b": "x",
+                    "#comment": [" first ", " second "],
+                    "#pi": "render fast",
+                }
+            })
+        );
+        assert_eq!(
+            convert(&ConversionConfig::default()).unwrap(),
+            json!({ "r": { "a": 1.0, "b": "x" } })
+        );
+    }
+
+    #[test]
+    fn analyze_findings() {
+        let findings = |xml: &str, cfg: &ConversionConfig| {
+            let e = treexml::Document::parse(xml.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+            analyze(&e, cfg)
+                .into_iter()
+                .map(|f| (f.path.to_string(), f.category))
+                .collect::<Vec<_>>()
+        };
+        let cfg = ConversionConfig::default();
+
+        assert_eq!(
+            findings("<p>Hello <b>world</b>!</p>", &cfg),
+            vec![("p".into(), FindingCategory::MixedContent)]
+        );
+        assert_eq!(
+            findings(
+                "<lib><shelf><book>a</book></shelf><shelf><book>b</book><book>c</book></shelf></lib>",
+                &cfg
+            ),
+            vec![("lib/shelf[1]/book".into(), FindingCategory::ArrayShape)]
+        );
+        let forced = ConversionConfig {
+            force_array: vec!["book".into()].into_iter().collect(),
+            ..Default::default()
+        };
+        assert!(findings(
+            "<lib><shelf><book>a</book></shelf><shelf><book>b</book><book>c</book></shelf></lib>",
+            &forced
+        )
+        .is_empty());
+        assert_eq!(
+            findings(
+                r#"<e big="1e400"><huge>-1e999</huge><small>1e300</small></e>"#,
+                &cfg
+            ),
+            vec![
+                ("e/@big".into(), FindingCategory::NumberOverflow),
+                ("e/huge".into(), FindingCategory::NumberOverflow),
+            ]
+        );
+        // Padded text is only coerced, and so only overflows, once trimmed.
+        let padded = r#"<e big=" 1e400 "><huge> 1e999 </huge></e>"#;
+        assert!(findings(padded, &cfg).is_empty());
+        let trimmed = ConversionConfig {
+            trim_text: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            findings(padded, &trimmed),
+            vec![
+                ("e/@big".into(), FindingCategory::NumberOverflow),
+                ("e/huge".into(), FindingCategory::NumberOverflow),
+            ]
+        );
+        assert_eq!(
+            findings(
+                r#"<e id="1" xmlns:a="urn:a" xmlns:b="urn:b"><a:item/><b:item/><id>2</id></e>"#,
+                &cfg
+            ),
+            vec![("e/b:item".into(), FindingCategory::KeyCollision)]
+        );
+        let merged = ConversionConfig {
+            merge_attributes_into_root: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            findings(r#"<e id="1"><id>2</id></e>"#, &merged),
+            vec![("e/id".into(), FindingCategory::KeyCollision)]
+        );
+
+        let fixture = include_str!("../tests/fixtures/ambiguous.xml");
+        assert_eq!(
+            findings(fixture, &cfg),
+            vec![
+                ("report/@total".into(), FindingCategory::NumberOverflow),
+                ("report/note".into(), FindingCategory::MixedContent),
+                ("report/b:ref".into(), FindingCategory::KeyCollision),
+                (
+                    "report/section[1]/entry".into(),
+                    FindingCategory::ArrayShape
+                ),
+            ]
+        );
+
+        let clean = r#"<catalog><book id="1"><title>A</title><price>9.5</price></book><book id="2"><title>B</title><price>12</price></book></catalog>"#;
+        assert!(findings(clean, &cfg).is_empty());
+    }
+
+    #[cfg(feature = "roxmltree")]
+    #[test]
+    fn roxmltree_backend() {
+        let src = r#"<feed xmlns="urn:feed" xmlns:x="urn:x">
+    <entry id="1" x:lang="en"><title>Dune &amp; more</title><x:price>9.5</x:price></entry>
+    <entry id="2"><title><![CDATA[<Emma>]]></title><tag/><tag/></entry>
+</feed>"#;
+        let doc = roxmltree::Document::parse(src).unwrap();
+        let mut dom_root = treexml::Document::parse(src.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        // roxmltree reads CDATA sections as text.
+        let title = &mut dom_root.children[1].children[0];
+        title.text = title.cdata.take();
+        let cfg = ConversionConfig::default();
+
+        let expected = node2object_with_config(&dom_root, &cfg).unwrap();
+        assert_eq!(roxmltree_node2object(doc.root(), &cfg).unwrap(), expected);
+        assert_eq!(
+            roxmltree_node2object(doc.root_element(), &cfg).unwrap(),
+            expected
+        );
+
+        // treexml drops the declarations, roxmltree keeps the namespaces.
+        dom_root
+            .attributes
+            .insert("xmlns".into(), "urn:feed".into());
+        dom_root.attributes.insert("xmlns:x".into(), "urn:x".into());
+        let cfg = ConversionConfig {
+            namespace_key: Some("#ns".into()),
+            prefix_map: vec![("urn:x".into(), "ext".into())].into_iter().collect(),
+            strip_xmlns_attributes: true,
+            ..Default::default()
+        };
+        let result = roxmltree_node2object(doc.root(), &cfg).unwrap();
+        assert_eq!(result["feed"]["#ns"], json!("urn:feed"));
+        assert_eq!(result["feed"]["entry"][0]["@ext:lang"], json!("en"));
+        assert_eq!(
+            result["feed"]["entry"][0]["ext:price"],
+            json!({"#text": 9.5, "#ns": "urn:x"})
+        );
+        assert_eq!(result, node2object_with_config(&dom_root, &cfg).unwrap());
+
+        let text = doc.root_element().first_child().unwrap();
+        assert!(matches!(
+            roxmltree_node2object(text, &cfg),
+            Err(ConversionError::Malformed { .. })
+        ));
+
+        // Namespaces declared above an inner element still resolve.
+        let entry = doc.root_element().first_element_child().unwrap();
+        let result = roxmltree_node2object(entry, &cfg).unwrap();
+        assert_eq!(
+            result,
+            json!({
+                "entry": {
+                    "#ns": "urn:feed",
+                    "#attr_ns": { "@ext:lang": "urn:x" },
+                    "@id": 1.0,
+                    "@ext:lang": "en",
+                    "title": { "#text": "Dune & more", "#ns": "urn:feed" },
+                    "ext:price": { "#text": 9.5, "#ns": "urn:x" }
+                }
+            })
+            .as_object()
+            .unwrap()
+            .clone()
+        );
+    }
+
+    #[cfg(feature = "roxmltree")]
+    #[test]
+    fn roxmltree_positions() {
+        let src = include_str!("../tests/fixtures/positions.xml");
+        let doc = roxmltree::Document::parse(src).unwrap();
+        let cfg = ConversionConfig {
+            include_positions: true,
+            pi_key: Some("#pi".into()),
+            ..Default::default()
+        };
+        let pos = |line: u32, col: u32| json!({ "line": line, "col": col });
+        assert_eq!(
+            Value::Object(roxmltree_node2object(doc.root(), &cfg).unwrap()),
+            json!({
+                "inventory": {
+                    "item": [
+                        {
+                            "@sku": "A-1",
+                            "name": { "#text": "Bolt", "#pos": pos(5, 5) },
+                            "qty": { "#text": 40.0, "#pos": pos(6, 5) },
+                            "#pos": pos(4, 3),
+                        },
+                        {
+                            "@sku": "B-2",
+                            "name": { "#text": "Nut", "#pos": pos(9, 5) },
+                            "qty": { "#text": 7.0, "#pos": pos(10, 7) },
+                            "#pi": "restock weekly",
+                            "#pos": pos(8, 3),
+                        },
+                    ],
+                    "#pos": pos(3, 1),
+                }
+            })
+        );
+
+        // Comments outside the root element belong to no element.
+        let cfg = ConversionConfig {
+            comment_key: Some("#comment".into()),
+            ..Default::default()
+        };
+        let dom_root = treexml::Document::parse(src.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        assert_eq!(
+            roxmltree_node2object(doc.root(), &cfg).unwrap(),
+            node2object_with_config(&dom_root, &ConversionConfig::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn conversion_stats() {
+        // The document of the README example.
+        let src = r#"
+        <population>
+          <entry>
+            <name>Alex</name>
+            <height>173.5</height>
+          </entry>
+          <entry>
+            <name>Mel</name>
+            <height>180.4</height>
+          </entry>
+        </population>"#;
+        let dom_root = treexml::Document::parse(src.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let (data, stats) =
+            node2object_with_stats(&dom_root, &ConversionConfig::default()).unwrap();
+        assert_eq!(data, node2object(&dom_root));
+        let expected = ConversionStats {
+            element_count: 7,
+            attribute_count: 0,
+            max_depth: 3,
+            text_bytes: 17,
+            array_count: 1,
+            number_coercions: 2,
+            boolean_coercions: 0,
+        };
+        assert_eq!(stats, expected);
+        #[cfg(feature = "streaming")]
+        {
+            struct Discard;
+            impl StreamHandler for Discard {
+                fn on_object(&mut self, _key: &str, _value: Value) {}
+            }
+            let cfg = ConversionConfig::default();
+            let stats = stream_convert_with_stats(src.as_bytes(), &mut Discard, &cfg).unwrap();
+            // Repeated children are streamed one by one instead of grouped into an array.
+            assert_eq!(
+                stats,
+                ConversionStats {
+                    array_count: 0,
+                    ..expected
+                }
+            );
+        }
+
+        let dom_root = treexml::Document::parse(
+            r#"<e id="1" on="true" tags="a 2"><x>yes</x><y/><z>false</z></e>"#.as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let cfg = ConversionConfig {
+            boolean_in_text: true,
+            boolean_in_attributes: true,
+            force_array: vec!["x".to_string()].into_iter().collect(),
+            ensure_array_keys: vec![("e".to_string(), vec!["w".to_string()])]
+                .into_iter()
+                .collect(),
+            split_attribute_values: vec![("tags".to_string(), SplitStrategy::Whitespace)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let (_, stats) = node2object_with_stats(&dom_root, &cfg).unwrap();
+        assert_eq!(
+            stats,
+            ConversionStats {
+                element_count: 4,
+                attribute_count: 3,
+                max_depth: 2,
+                text_bytes: 8,
+                array_count: 2,
+                number_coercions: 1,
+                boolean_coercions: 2,
+            }
+        );
+    }
+
+    #[cfg(feature = "xml-rs")]
+    #[test]
+    fn xml_rs_backend() {
+        let docs = [
+            r#"<feed id="7"><entry><title>Dune</title><price>9.5</price></entry><entry><title><![CDATA[<Emma>]]></title></entry><note/></feed>"#,
+            r#"<p lang="en">Hello <b>world</b> and <b>more</b><!-- skipped -->!</p>"#,
+            r#"<a:root xmlns:a="urn:a" a:x="1"><a:item>1</a:item></a:root>"#,
+            r#"<leaf id="1">text</leaf>"#,
+            "<empty/>",
+            r#"<r v="0"><v>1</v><v>x</v><v>2.5</v><n>1</n><n>2</n><g><v>1</v><v>true</v></g></r>"#,
+        ];
+        let configs = [
+            ConversionConfig::default(),
+            ConversionConfig {
+                heterogeneous_arrays: HeteroPolicy::Stringify,
+                merge_siblings: vec!["entry".to_string()].into_iter().collect(),
+                max_occurrences: vec![("n".to_string(), 1)].into_iter().collect(),
+                truncated_count_key: Some("#truncated".into()),
+                namespace_prefixes: PrefixHandling::Strip,
+                ..Default::default()
+            },
+            ConversionConfig {
+                harmonize_sibling_types: true,
+                child_as_attribute: true,
+                array_key_rename: vec![("v".to_string(), "values".to_string())]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            ConversionConfig {
+                force_array: vec!["entry".to_string(), "item".to_string()]
+                    .into_iter()
+                    .collect(),
+                include_root: false,
+                ..Default::default()
+            },
+            ConversionConfig {
+                ensure_array_keys: vec![("feed".to_string(), vec!["tag".to_string()])]
+                    .into_iter()
+                    .collect(),
+                inject_source_tag: true,
+                ..Default::default()
+            },
+        ];
+        for src in docs {
+            let dom_root = treexml::Document::parse(src.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+            for cfg in &configs {
+                assert_eq!(
+                    xmlrs_document_to_value(src.as_bytes(), cfg).unwrap(),
+                    Value::Object(node2object_with_config(&dom_root, cfg).unwrap()),
+                    "{}",
+                    src
+                );
+            }
+        }
+
+        for src in ["<a><b></a>", "<a/><b/>", "", "<!-- none -->"] {
+            let result = xmlrs_document_to_value(src.as_bytes(), &ConversionConfig::default());
+            assert!(
+                matches!(result, Err(XmlRsConversionError::Xml(_))),
+                "{}",
+                src
+            );
+        }
+        let cfg = ConversionConfig {
+            expand_entities: false,
+            ..Default::default()
+        };
+        assert!(matches!(
+            xmlrs_document_to_value(docs[0].as_bytes(), &cfg),
+            Err(XmlRsConversionError::Conversion(
+                ConversionError::UnsupportedOption { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn all_scalars_as_strings() {
+        let cfg = ConversionConfig {
+            all_scalars_as_strings: true,
+            ..Default::default()
+        };
+        let dom_root = treexml::Document::parse(
+            r#"
+            <population>
+              <entry>
+                <name>Alex</name>
+                <height>173.5</height>
+              </entry>
+              <entry>
+                <name>Mel</name>
+                <height>180.4</height>
+              </entry>
+            </population>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            json!({
+                "population": {
+                    "entry": [
+                        { "name": "Alex", "height": "173.5" },
+                        { "name": "Mel", "height": "180.4" }
+                    ]
+                }
+            })
+        );
+
+        let dom_root = treexml::Document::parse(
+            r#"<e id="7" on="true" note=""><flag>false</flag><raw><![CDATA[0x1F]]></raw><n>+1e3</n><none/></e>"#
+                .as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let expected = |none: Option<Value>| {
+            let mut e = json!({
+                "@id": "7",
+                "@on": "true",
+                "@note": "",
+                "flag": "false",
+                "raw": "0x1F",
+                "n": "+1e3"
+            });
+            if let Some(none) = none {
+                e["none"] = none;
+            }
+            json!({ "e": e })
+        };
+        let convert = |cfg: &ConversionConfig| {
+            Value::Object(node2object_with_config(&dom_root, cfg).unwrap())
+        };
+        assert_eq!(convert(&cfg), expected(None));
+        let cfg = ConversionConfig {
+            empty_attribute_as: EmptyAttribute::Null,
+            empty_element_as: EmptyElement::EmptyObject,
+            parse_radix_literals: true,
+            text_parser: Some(std::sync::Arc::new(IntegerPreferringTextParser)),
+            ..cfg
+        };
+        assert_eq!(convert(&cfg), expected(Some(json!({}))));
+    }
+
+    #[cfg(feature = "minidom")]
+    #[test]
+    fn minidom_backend() {
+        let src = r#"<feed xmlns="urn:feed" xmlns:x="urn:x" id="7">
+    <entry x:lang="en" xml:lang="de"><title>Dune &amp; more</title><x:price>9.5</x:price></entry>
+    <entry><title><![CDATA[<Emma>]]></title><tag/><tag/></entry>
+</feed>"#;
+        let element: minidom::Element = src.parse().unwrap();
+        let mut dom_root = treexml::Document::parse(src.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        // minidom reads CDATA sections as text.
+        let title = &mut dom_root.children[1].children[0];
+        title.text = title.cdata.take();
+        let cfg = ConversionConfig::default();
+        assert_eq!(
+            minidom_node2object(&element, &cfg).unwrap(),
+            node2object_with_config(&dom_root, &cfg).unwrap()
+        );
+
+        // treexml drops the declarations, minidom keeps the namespaces.
+        dom_root
+            .attributes
+            .insert("xmlns".into(), "urn:feed".into());
+        dom_root.attributes.insert("xmlns:x".into(), "urn:x".into());
+        let cfg = ConversionConfig {
+            namespace_key: Some("#ns".into()),
+            prefix_map: vec![("urn:x".into(), "ext".into())].into_iter().collect(),
+            strip_xmlns_attributes: true,
+            ..Default::default()
+        };
+        let result = minidom_node2object(&element, &cfg).unwrap();
+        assert_eq!(result["feed"]["#ns"], json!("urn:feed"));
+        assert_eq!(result["feed"]["entry"][0]["@ext:lang"], json!("en"));
+        assert_eq!(result["feed"]["entry"][0]["@xml:lang"], json!("de"));
+        assert_eq!(
+            result["feed"]["entry"][0]["ext:price"],
+            json!({"#text": 9.5, "#ns": "urn:x"})
+        );
+        assert_eq!(result, node2object_with_config(&dom_root, &cfg).unwrap());
+    }
+
+    #[test]
+    fn max_occurrences() {
+        let src = r#"
+        <log>
+          <record>1</record>
+          <record>2</record>
+          <note>kept</note>
+          <record>3</record>
+          <record><deep><deeper/></deep></record>
+          <record/>
+        </log>"#;
+        let dom_root = treexml::Document::parse(src.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let cfg = ConversionConfig {
+            max_occurrences: vec![("record".to_string(), 2)].into_iter().collect(),
+            truncated_count_key: Some("#truncated_count".to_string()),
+            ..ConversionConfig::default()
+        };
+        let expected = json!({
+            "log": {
+                "record": [1.0, 2.0],
+                "note": "kept",
+                "#truncated_count": {"record": 3}
+            }
+        });
+        assert_eq!(
+            Value::Object(node2object_with_config(&dom_root, &cfg).unwrap()),
+            expected
+        );
+
+        // Without a key the skipped children are dropped silently.
+        let untracked = ConversionConfig {
+            truncated_count_key: None,
+            ..cfg.clone()
+        };
+        let data = node2object_with_config(&dom_root, &untracked).unwrap();
+        assert_eq!(
+            Value::Object(data),
+            json!({"log": {"record": [1.0, 2.0], "note": "kept"}})
+        );
+
+        // Patterns match paths too, and the smallest matching limit wins.
+        let by_path = ConversionConfig {
+            max_occurrences: vec![("log/record".to_string(), 1), ("record".to_string(), 3)]
+                .into_iter()
+                .collect(),
+            ..cfg.clone()
+        };
+        let data = node2object_with_config(&dom_root, &by_path).unwrap();
+        // A truncated group stays an array when a single child is left.
+        assert_eq!(data["log"]["record"], json!([1.0]));
+        assert_eq!(data["log"]["#truncated_count"], json!({"record": 4}));
+        #[cfg(feature = "xml-rs")]
+        assert_eq!(
+            xmlrs_document_to_value(src.as_bytes(), &by_path).unwrap(),
+            Value::Object(data)
+        );
+
+        #[cfg(feature = "streaming")]
+        {
+            #[derive(Default)]
+            struct Collect(Vec<(String, Value)>);
+            impl StreamHandler for Collect {
+                fn on_object(&mut self, key: &str, value: Value) {
+                    self.0.push((key.to_string(), value));
+                }
+            }
+            let mut collect = Collect::default();
+            let stats = stream_convert_with_stats(src.as_bytes(), &mut collect, &cfg).unwrap();
+            assert_eq!(
+                collect.0,
+                vec![
+                    ("record".to_string(), json!(1.0)),
+                    ("record".to_string(), json!(2.0)),
+                    ("note".to_string(), json!("kept")),
+                    ("#truncated_count".to_string(), json!({"record": 3})),
+                ]
+            );
+            // The skipped subtrees are never converted.
+            assert_eq!(stats.element_count, 4);
+        }
+
+        #[cfg(feature = "xml-rs")]
+        assert_eq!(
+            xmlrs_document_to_value(src.as_bytes(), &cfg).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn extract_pointer() {
+        let src = r#"
+        <report id="r1">
+          <summary><total>42</total><currency>EUR</currency></summary>
+          <line sku="a"><qty>1</qty></line>
+          <line sku="b"><qty>2</qty></line>
+          <line sku="c"><qty>3</qty></line>
+        </report>"#;
+        let dom_root = treexml::Document::parse(src.as_bytes())
+            .unwrap()
+            .root
+            .unwrap();
+        let cfg = ConversionConfig::default();
+        let extracted = |pointer| extract(&dom_root, pointer, &cfg).unwrap();
+        assert_eq!(extracted("/report/summary/total"), Some(json!(42.0)));
+        assert_eq!(extracted("/report/@id"), Some(json!("r1")));
+        assert_eq!(extracted("/report/line/1/@sku"), Some(json!("b")));
+        assert_eq!(
+            extracted("/report/summary"),
+            Some(json!({"total": 42.0, "currency": "EUR"}))
+        );
+        assert_eq!(
+            extracted("/report/line/2"),
+            Some(json!({"@sku": "c", "qty": 3.0}))
+        );
+        assert_eq!(extracted("/report/line/3"), None);
+        assert_eq!(extracted("/report/line/01"), None);
+        assert_eq!(extracted("/report/summary/missing"), None);
+        assert_eq!(extracted("/other"), None);
+        assert_eq!(extracted("report"), None);
+        assert_eq!(extracted(""), Some(Value::Object(node2object(&dom_root))));
+
+        // Only the addressed subtree is converted, so errors elsewhere don't surface.
+        let dom_root = treexml::Document::parse(
+            "<report><mixed>1</mixed><mixed>x</mixed><total>2</total></report>".as_bytes(),
+        )
+        .unwrap()
+        .root
+        .unwrap();
+        let strict = ConversionConfig {
+            heterogeneous_arrays: HeteroPolicy::Error,
+            ..ConversionConfig::default()
+        };
+        assert!(node2object_with_config(&dom_root, &strict).is_err());
+        assert_eq!(
+            extract(&dom_root, "/report/total", &strict).unwrap(),
+            Some(json!(2.0))
+        );
+
+        // Every value of the full conversion is found at its pointer, whatever the options.
+        fn pointers(v: &Value, prefix: String, out: &mut Vec<String>) {
+            let children: Vec<(String, &Value)> = match v {
+                Value::Object(data) => data.iter().map(|(k, v)| (k.clone(), v)).collect(),
+                Value::Array(items) => items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (i.to_string(), v))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            for (token, v) in children {
+                let pointer = format!("{}/{}", prefix, token.replace('~', "~0").replace('/', "~1"));
+                pointers(v, pointer.clone(), out);
+                out.push(pointer);
+            }
+        }
+        let configs = vec![
+            ConversionConfig::default(),
+            ConversionConfig {
+                include_root: false,
+                ..ConversionConfig::default()
+            },
+            ConversionConfig {
+                child_as_attribute: true,
+                force_array: vec!["tag".to_string()].into_iter().collect(),
+                ..ConversionConfig::default()
+            },
+            ConversionConfig {
+                max_occurrences: vec![("book".to_string(), 1)].into_iter().collect(),
+                truncated_count_key: Some("#truncated_count".to_string()),
+                ..ConversionConfig::default()
+            },
+            ConversionConfig {
+                arrays_as_indexed_objects: true,
+                ..ConversionConfig::default()
+            },
+        ];
+        let fixtures = [
+            include_str!("../tests/fixtures/xml2js.xml"),
+            include_str!("../tests/fixtures/soap.xml"),
+            include_str!("../tests/fixtures/ambiguous.xml"),
+            src,
+        ];
+        for fixture in fixtures {
+            let dom_root = treexml::Document::parse(fixture.as_bytes())
+                .unwrap()
+                .root
+                .unwrap();
+            for cfg in &configs {
+                let full = Value::Object(node2object_with_config(&dom_root, cfg).unwrap());
+                let mut all = vec!["/missing".to_string(), "/report/line/-".to_string()];
+                pointers(&full, String::new(), &mut all);
+                for pointer in all {
+                    let missing = format!("{}/missing", pointer);
+                    for pointer in [pointer, missing] {
+                        assert_eq!(
+                            extract(&dom_root, &pointer, cfg).unwrap().as_ref(),
+                            full.pointer(&pointer),
+                            "{}",
+                            pointer
+                        );
+                    }
+                }
+            }
+        }
     }
 }
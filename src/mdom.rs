@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use minidom::{rxml::Namespace, Element, Node};
+use serde_json::{Map, Value};
+
+use crate::{
+    annotations::Recorded,
+    namespaces::{convert_declared, declaration_attribute},
+    ConversionConfig, ConversionError,
+};
+
+/// Namespace declarations in scope as `(prefix, URI)` pairs, innermost last. The default
+/// namespace has no prefix.
+type Bindings<'a> = Vec<(Option<&'a str>, &'a str)>;
+
+/// Innermost prefix bound to `uri`, or `None` for the default namespace.
+fn prefix_of<'a>(bindings: &Bindings<'a>, uri: &str) -> Option<&'a str> {
+    bindings
+        .iter()
+        .rev()
+        .find(|(_, bound)| *bound == uri)
+        .and_then(|(prefix, _)| *prefix)
+}
+
+/// Copies `e` and its descendants into a treexml element tree, mirroring what
+/// `treexml::Document::parse` produces for the same document. The namespaces `e` declares
+/// are kept among its attributes so that the namespace options can resolve them.
+fn element<'a>(e: &'a Element, bindings: &mut Bindings<'a>) -> treexml::Element {
+    let scope = bindings.len();
+    let mut attributes = HashMap::new();
+    for (prefix, uri) in e.prefixes.declared_prefixes() {
+        bindings.push((prefix.as_deref(), uri));
+        attributes.insert(declaration_attribute(prefix.as_deref()), uri.clone());
+    }
+    for ((ns, name), value) in e.attrs().iter() {
+        let name = if ns.is_none() {
+            name.to_string()
+        } else if *ns == Namespace::XML {
+            format!("xml:{}", name)
+        } else {
+            match prefix_of(bindings, ns) {
+                Some(prefix) => format!("{}:{}", prefix, name),
+                None => name.to_string(),
+            }
+        };
+        attributes.insert(name, value.clone());
+    }
+
+    let ns = e.ns();
+    let mut converted = treexml::Element {
+        prefix: prefix_of(bindings, &ns).map(str::to_string),
+        attributes,
+        ..treexml::Element::new(e.name())
+    };
+    for node in e.nodes() {
+        match node {
+            Node::Element(c) => converted.children.push(element(c, bindings)),
+            Node::Text(text) if !text.chars().all(char::is_whitespace) => {
+                converted
+                    .text
+                    .get_or_insert_with(String::new)
+                    .push_str(text);
+            }
+            Node::Text(_) => {}
+        }
+    }
+    bindings.truncate(scope);
+    converted
+}
+
+/// Converts a minidom element like `node2object_with_config`.
+///
+/// minidom gives every element its namespace URI and keeps the prefixes each element
+/// declares, so the namespace options apply to the whole tree. Namespace declarations never
+/// become attributes, as with treexml, whatever `strip_xmlns_attributes` says. An element
+/// doesn't know the prefixes declared by its ancestors: converting an inner element, names in
+/// namespaces declared above it are converted as unprefixed names in no namespace. Of two
+/// prefixes bound to the same namespace the innermost is used. minidom keeps neither comments,
+/// processing instructions nor source positions, and reads CDATA sections as text, so they
+/// are always converted as text.
+pub fn minidom_node2object(
+    element: &Element,
+    cfg: &ConversionConfig,
+) -> Result<Map<String, Value>, ConversionError> {
+    let e = self::element(element, &mut Vec::new());
+    convert_declared(&e, None, Recorded::default(), cfg)
+}
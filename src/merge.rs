@@ -0,0 +1,121 @@
+use serde_json::{Map, Value};
+
+use crate::{
+    element_key, node2object_with_config, ConversionConfig, ConversionError, JsonPointer, XmlPath,
+};
+
+/// Resolution of documents holding different scalar values at the same place.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScalarConflict {
+    /// The value from the earliest document is kept.
+    First,
+    /// The value from the latest document is kept.
+    #[default]
+    Last,
+    /// Merging fails with `ConversionError::MergeConflict`.
+    Error,
+}
+
+/// Treatment of documents whose root elements have different names.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RootNames {
+    /// Merging fails with `ConversionError::RootMismatch`.
+    #[default]
+    RequireEqual,
+    /// Each document is stored under its root name, so only same-named roots are merged.
+    KeyByName,
+}
+
+/// Options for `merge_documents`.
+#[derive(Clone, Debug, Default)]
+pub struct MergeOptions {
+    /// Conversion options applied to each document.
+    pub conversion: ConversionConfig,
+    pub scalar_conflict: ScalarConflict,
+    pub root_names: RootNames,
+}
+
+/// Deep-merges `v` into `target`. On a conflict with `ScalarConflict::Error`, returns the keys
+/// leading to the conflicting value.
+fn merge(target: &mut Value, v: Value, policy: ScalarConflict) -> Result<(), Vec<String>> {
+    match (target, v) {
+        (Value::Object(target), Value::Object(other)) => {
+            for (k, v) in other {
+                match target.get_mut(&k) {
+                    Some(existing) => merge(existing, v, policy).map_err(|mut keys| {
+                        keys.insert(0, k);
+                        keys
+                    })?,
+                    None => {
+                        target.insert(k, v);
+                    }
+                }
+            }
+        }
+        (Value::Array(target), Value::Array(other)) => target.extend(other),
+        (Value::Array(target), v) => target.push(v),
+        (target, Value::Array(mut items)) => {
+            items.insert(0, target.take());
+            *target = Value::Array(items);
+        }
+        (target, v) => {
+            if *target != v {
+                match policy {
+                    ScalarConflict::First => {}
+                    ScalarConflict::Last => *target = v,
+                    ScalarConflict::Error => return Err(Vec::new()),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Converts every element in `roots` and deep-merges the results in order. Objects are merged
+/// recursively and arrays are concatenated; a single value meeting an array is added to it,
+/// so names listed in `force_array` always accumulate. Other differing values are resolved
+/// according to `options.scalar_conflict`.
+pub fn merge_documents(
+    roots: &[&treexml::Element],
+    options: &MergeOptions,
+) -> Result<Value, ConversionError> {
+    let cfg = &options.conversion;
+    let mut merged = Value::Object(Map::new());
+    let mut expected_root = None;
+    for root in roots {
+        let name = element_key(root, cfg);
+        let mut xml_path = XmlPath::default();
+        xml_path.push_element(&root.name, None);
+
+        let data = node2object_with_config(root, cfg)?;
+        let data = match options.root_names {
+            RootNames::RequireEqual => {
+                let expected = expected_root.get_or_insert_with(|| name.clone());
+                if *expected != name {
+                    return Err(ConversionError::RootMismatch {
+                        expected: expected.to_string(),
+                        found: name.into_owned(),
+                        path: JsonPointer::default(),
+                        xml_path,
+                    });
+                }
+                data
+            }
+            RootNames::KeyByName if cfg.include_root => data,
+            RootNames::KeyByName => {
+                let mut keyed = Map::new();
+                keyed.insert(name.into_owned(), Value::Object(data));
+                keyed
+            }
+        };
+
+        merge(&mut merged, Value::Object(data), options.scalar_conflict).map_err(|keys| {
+            let mut path = JsonPointer::default();
+            for k in &keys {
+                path.push(k);
+            }
+            ConversionError::MergeConflict { path, xml_path }
+        })?;
+    }
+    Ok(merged)
+}
@@ -0,0 +1,334 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{
+    attribute_key, CollisionStrategy, Context, ConversionConfig, ConversionError, ConversionMode,
+    DefaultNsHandling, PrefixHandling,
+};
+
+/// Namespace bound to the `xml` prefix by the XML specification.
+pub(crate) const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Namespace declarations in scope as `(prefix, URI)` pairs, innermost last. The default
+/// namespace has an empty prefix.
+type Bindings<'a> = Vec<(&'a str, &'a str)>;
+
+/// Checks whether any option needs the namespace URIs of elements and attributes.
+fn resolves_namespaces(cfg: &ConversionConfig) -> bool {
+    !cfg.prefix_map.is_empty()
+        || !cfg.strip_namespaces.is_empty()
+        || cfg.namespace_prefixes == PrefixHandling::Strip
+        || cfg.default_namespace != DefaultNsHandling::Ignore
+        || cfg.namespace_key.is_some()
+}
+
+/// Prefix given to names in some namespace.
+enum Prefix<'c> {
+    /// Listed in `prefix_map`.
+    Canonical(&'c str),
+    /// Listed in `strip_namespaces`.
+    Stripped,
+    /// Any other namespace, or no namespace at all.
+    Unchanged,
+}
+
+fn target<'c>(uri: Option<&str>, cfg: &'c ConversionConfig) -> Prefix<'c> {
+    let uri = match uri {
+        Some(uri) => uri,
+        None => return Prefix::Unchanged,
+    };
+    match cfg.prefix_map.get(uri) {
+        Some(canonical) => Prefix::Canonical(canonical),
+        None if cfg.strip_namespaces.contains(uri) => Prefix::Stripped,
+        None => Prefix::Unchanged,
+    }
+}
+
+/// Namespace declarations made by the attributes of `e`.
+fn declarations(e: &treexml::Element) -> impl Iterator<Item = (&str, &str)> {
+    e.attributes
+        .iter()
+        .filter_map(|(name, uri)| match name.as_str() {
+            "xmlns" => Some(("", uri.as_str())),
+            name => Some((name.strip_prefix("xmlns:")?, uri.as_str())),
+        })
+}
+
+/// URI bound to `prefix`, if any.
+fn lookup<'a>(bindings: &Bindings<'a>, prefix: &str) -> Option<&'a str> {
+    if prefix == "xml" {
+        return Some(XML_NAMESPACE);
+    }
+    bindings
+        .iter()
+        .rev()
+        .find(|(p, _)| *p == prefix)
+        .map(|(_, uri)| *uri)
+        .filter(|uri| !uri.is_empty())
+}
+
+/// Joins a prefix and a local name, leaving out empty prefixes.
+fn qualify(prefix: &str, local: &str) -> String {
+    if prefix.is_empty() {
+        local.to_string()
+    } else {
+        format!("{}:{}", prefix, local)
+    }
+}
+
+/// New name of the attribute `name` with value `value` in the scope of `bindings`, or `None`
+/// if it is left out.
+fn attribute_name(
+    name: &str,
+    value: &str,
+    bindings: &Bindings,
+    cfg: &ConversionConfig,
+) -> Option<String> {
+    let (prefix, local) = match name.split_once(':') {
+        _ if name == "xmlns" => return declaration_name(name, value, cfg),
+        Some(("xmlns", _)) => return declaration_name(name, value, cfg),
+        Some(parts) => parts,
+        None => return Some(name.to_string()),
+    };
+    Some(match target(lookup(bindings, prefix), cfg) {
+        Prefix::Canonical(canonical) => qualify(canonical, local),
+        Prefix::Stripped => local.to_string(),
+        Prefix::Unchanged if cfg.namespace_prefixes == PrefixHandling::Strip => local.to_string(),
+        Prefix::Unchanged => name.to_string(),
+    })
+}
+
+/// New name of the namespace declaration `name` of `uri`, or `None` if it is left out.
+fn declaration_name(name: &str, uri: &str, cfg: &ConversionConfig) -> Option<String> {
+    match target(Some(uri).filter(|uri| !uri.is_empty()), cfg) {
+        Prefix::Canonical(canonical) => Some(qualify("xmlns", canonical)),
+        Prefix::Stripped => None,
+        Prefix::Unchanged => Some(name.to_string()),
+    }
+}
+
+/// URI of the namespace of the attribute `name`, if it has a prefix bound in `bindings`.
+fn attribute_uri<'a>(name: &str, bindings: &Bindings<'a>) -> Option<&'a str> {
+    match name.split_once(':') {
+        Some(("xmlns", _)) | None => None,
+        Some((prefix, _)) => lookup(bindings, prefix),
+    }
+}
+
+/// Namespaces of an element and of its attributes, keyed by their new names. The tree mirrors
+/// the element tree, `children` holding the namespaces of the child elements by index.
+#[derive(Default)]
+pub(crate) struct ElementUris {
+    pub(crate) element: Option<String>,
+    pub(crate) attributes: Vec<(String, String)>,
+    pub(crate) children: Vec<ElementUris>,
+}
+
+/// Element tree with the namespace options applied.
+pub(crate) struct Resolved<'a> {
+    pub(crate) root: Cow<'a, treexml::Element>,
+    /// Namespaces of the root and its descendants. Only recorded when
+    /// `ConversionConfig::namespace_key` is set.
+    pub(crate) uris: ElementUris,
+}
+
+/// Two attributes of an element ending up with the same name, reported when
+/// `ConversionConfig::attribute_child_collision` is `CollisionStrategy::Error`.
+struct Collision {
+    /// Indices of the children leading from the root down to the element.
+    position: Vec<usize>,
+    /// New name of the attributes.
+    name: String,
+}
+
+impl Collision {
+    fn error(
+        self,
+        ancestors: &[&treexml::Element],
+        root: &treexml::Element,
+        cfg: &ConversionConfig,
+    ) -> ConversionError {
+        let mut ctx = Context::new(cfg);
+        ctx.path.extend(ancestors);
+        ctx.path.push(root);
+        for i in self.position {
+            let parent = *ctx.path.last().expect("the root is on the path");
+            ctx.path.push(&parent.children[i]);
+        }
+        let key = attribute_key(&self.name, cfg);
+        let mut path = ctx.pointer();
+        path.push(&key);
+        ConversionError::AttributeChildCollision {
+            key,
+            path,
+            xml_path: ctx.xml_path(),
+        }
+    }
+}
+
+/// Rewrites `e`, found at `position` below the root, recording in `collision` the first
+/// attributes that end up with the same name if they must be reported.
+fn rewrite<'a>(
+    e: &'a treexml::Element,
+    bindings: &mut Bindings<'a>,
+    position: &mut Vec<usize>,
+    collision: &mut Option<Collision>,
+    cfg: &ConversionConfig,
+) -> (treexml::Element, ElementUris) {
+    let scope = bindings.len();
+    bindings.extend(declarations(e));
+
+    // Attributes written without a prefix win over prefixed ones ending up with the same name.
+    let mut renamed = Vec::new();
+    let mut attributes = HashMap::with_capacity(e.attributes.len());
+    for (name, value) in &e.attributes {
+        match attribute_name(name, value, bindings, cfg) {
+            Some(new) if new == *name && !name.contains(':') => {
+                attributes.insert(new, value.clone());
+            }
+            Some(new) => renamed.push((name, new, value)),
+            None => {}
+        }
+    }
+    renamed.sort();
+    let mut attribute_uris = Vec::new();
+    for (name, new, value) in renamed {
+        if attributes.contains_key(&new) {
+            if cfg.attribute_child_collision == CollisionStrategy::Error && collision.is_none() {
+                *collision = Some(Collision {
+                    position: position.clone(),
+                    name: new,
+                });
+            }
+            continue;
+        }
+        if let (true, Some(uri)) = (cfg.namespace_key.is_some(), attribute_uri(name, bindings)) {
+            attribute_uris.push((new.clone(), uri.to_string()));
+        }
+        attributes.insert(new, value.clone());
+    }
+
+    let uri = lookup(bindings, e.prefix.as_deref().unwrap_or(""));
+    let (prefix, name) = match target(uri, cfg) {
+        Prefix::Canonical(canonical) if cfg.mode == ConversionMode::Standard => {
+            (None, qualify(canonical, &e.name))
+        }
+        Prefix::Canonical(canonical) => (
+            Some(canonical.to_string()).filter(|p| !p.is_empty()),
+            e.name.clone(),
+        ),
+        Prefix::Stripped => (None, e.name.clone()),
+        Prefix::Unchanged => match (&e.prefix, uri, &cfg.default_namespace) {
+            (None, Some(_), DefaultNsHandling::ApplyPrefix(prefix))
+                if cfg.mode == ConversionMode::Standard =>
+            {
+                (None, qualify(prefix, &e.name))
+            }
+            (None, Some(_), DefaultNsHandling::ApplyPrefix(prefix)) => (
+                Some(prefix.clone()).filter(|p| !p.is_empty()),
+                e.name.clone(),
+            ),
+            (None, Some(uri), DefaultNsHandling::Expand) => {
+                (None, format!("{{{}}}{}", uri, e.name))
+            }
+            _ => (e.prefix.clone(), e.name.clone()),
+        },
+    };
+    let element = uri
+        .filter(|_| cfg.namespace_key.is_some())
+        .map(str::to_string);
+
+    let (children, mut children_uris): (Vec<_>, Vec<_>) = e
+        .children
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            position.push(i);
+            let rewritten = rewrite(c, bindings, position, collision, cfg);
+            position.pop();
+            rewritten
+        })
+        .unzip();
+    if cfg.namespace_key.is_none() {
+        children_uris.clear();
+    }
+    let element_uris = ElementUris {
+        element,
+        attributes: attribute_uris,
+        children: children_uris,
+    };
+
+    bindings.truncate(scope);
+    let e = treexml::Element {
+        prefix,
+        name,
+        attributes,
+        children,
+        text: e.text.clone(),
+        cdata: e.cdata.clone(),
+    };
+    (e, element_uris)
+}
+
+/// Name of the attribute declaring `prefix`, e.g. `xmlns:acme`, or `xmlns` for the default
+/// namespace.
+#[cfg(any(feature = "minidom", feature = "roxmltree"))]
+pub(crate) fn declaration_attribute(prefix: Option<&str>) -> String {
+    match prefix {
+        Some(prefix) => qualify("xmlns", prefix),
+        None => "xmlns".to_string(),
+    }
+}
+
+/// Converts a tree copied from a parser that resolves namespaces itself, see
+/// `convert_recorded`. Such backends copy the namespace declarations in scope among the
+/// attributes, named by `declaration_attribute`, only for the namespace options to resolve
+/// them: the declarations are always left out of the output, as treexml leaves them out when
+/// parsing, whatever `ConversionConfig::strip_xmlns_attributes` says.
+#[cfg(any(feature = "minidom", feature = "quick-xml", feature = "roxmltree"))]
+pub(crate) fn convert_declared(
+    e: &treexml::Element,
+    notes: Option<&crate::annotations::Annotations>,
+    recorded: crate::annotations::Recorded,
+    cfg: &ConversionConfig,
+) -> Result<serde_json::Map<String, serde_json::Value>, crate::ConversionError> {
+    let cfg = ConversionConfig {
+        strip_xmlns_attributes: true,
+        ..cfg.clone()
+    };
+    crate::convert_recorded(e, notes, recorded, &cfg).map(|(data, _)| data)
+}
+
+/// Applies the namespace options of `cfg` to the tree below `e`, returning it unchanged if none
+/// is set. Namespaces are resolved from the `xmlns` declarations among the attributes of `e`
+/// and its descendants. Fails with `ConversionError::AttributeChildCollision` if two attributes
+/// of an element end up with the same name and `CollisionStrategy::Error` is set.
+pub(crate) fn resolve_namespaces<'a>(
+    e: &'a treexml::Element,
+    cfg: &ConversionConfig,
+) -> Result<Resolved<'a>, ConversionError> {
+    resolve_namespaces_below(&[], e, cfg)
+}
+
+/// Same as `resolve_namespaces` for `e` converted below `ancestors`, which errors are located
+/// under. Declarations made by `ancestors` are not in scope.
+pub(crate) fn resolve_namespaces_below<'a>(
+    ancestors: &[&treexml::Element],
+    e: &'a treexml::Element,
+    cfg: &ConversionConfig,
+) -> Result<Resolved<'a>, ConversionError> {
+    if !resolves_namespaces(cfg) {
+        return Ok(Resolved {
+            root: Cow::Borrowed(e),
+            uris: ElementUris::default(),
+        });
+    }
+    let mut collision = None;
+    let (root, uris) = rewrite(e, &mut Vec::new(), &mut Vec::new(), &mut collision, cfg);
+    if let Some(collision) = collision {
+        return Err(collision.error(ancestors, &root, cfg));
+    }
+    Ok(Resolved {
+        root: Cow::Owned(root),
+        uris,
+    })
+}
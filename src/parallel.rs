@@ -0,0 +1,66 @@
+use rayon::prelude::*;
+use serde_json::{Map, Value};
+
+use crate::{
+    annotations::{check_options, Recorded},
+    attribute_entries, check_reserved_keys, convert_child, insert_ensured_arrays,
+    is_collapsed_wrapper,
+    namespaces::resolve_namespaces,
+    node2object_with_config, node_attributes,
+    references::resolve_references,
+    scan_xml_node, tag_namespace, tag_source, wrap_root, Context, ConversionConfig,
+    ConversionError, NodeContext, SiblingGroups, XMLNodeType,
+};
+
+/// Same as `node2object_with_config`, but the immediate children of the root are converted in
+/// parallel on the rayon thread pool. Their subtrees are converted sequentially.
+///
+/// The output is identical to the sequential conversion: converted children are grouped in
+/// document order after the parallel pass, so repeated elements keep their original order
+/// within arrays. If several children fail to convert, the error of the first one in document
+/// order is returned.
+pub fn node2object_parallel_children(
+    e: &treexml::Element,
+    cfg: &ConversionConfig,
+) -> Result<Map<String, Value>, ConversionError> {
+    check_options(cfg, Recorded::default())?;
+    if scan_xml_node(e, cfg) != XMLNodeType::Parent || is_collapsed_wrapper(e, cfg) {
+        return node2object_with_config(e, cfg);
+    }
+    let resolved = resolve_namespaces(e, cfg)?;
+    let e = &*resolved.root;
+    let ctx = Context::with_parent(cfg, e);
+    check_reserved_keys(&node_attributes(e, None, cfg), &ctx)?;
+    let mut groups = SiblingGroups::new(&ctx)?;
+    let children = e
+        .children
+        .par_iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let mut ctx = Context::with_parent(cfg, e);
+            ctx.namespaces = resolved.uris.children.get(i);
+            convert_child(i, c, &groups, &mut ctx)
+        })
+        .collect::<Vec<_>>();
+
+    let mut data = attribute_entries(
+        node_attributes(e, None, cfg),
+        &NodeContext::new(&[], e),
+        cfg,
+    );
+
+    for (c, v) in e.children.iter().zip(children) {
+        if let Some(v) = v? {
+            groups.insert(&mut data, c, v, &ctx)?;
+        }
+    }
+
+    groups.finish(&mut data, &ctx)?;
+
+    let value = insert_ensured_arrays(Some(Value::Object(data)), e, XMLNodeType::Parent, &ctx);
+    let value = tag_namespace(value, e, Some(&resolved.uris), cfg);
+    let value = tag_source(value, e, cfg);
+    let mut data = wrap_root(e, value, cfg);
+    resolve_references(&mut data, e, cfg)?;
+    Ok(data)
+}
@@ -0,0 +1,262 @@
+#[cfg(feature = "streaming")]
+use std::{io, sync::Arc};
+
+use std::{borrow::Cow, fmt};
+
+use quick_xml::{
+    encoding::EncodingError,
+    escape::{resolve_predefined_entity, unescape, EscapeError},
+    events::{BytesStart, Event},
+};
+
+use crate::{annotations::Annotations, ConversionConfig, EntityHandling};
+
+/// Failure to build a tree from pull parser events.
+#[derive(Debug)]
+pub(crate) enum BuildError {
+    /// The events don't form a well-formed document.
+    Malformed(String),
+    /// The entity named here isn't predefined and `EntityHandling::Error` is set.
+    UnresolvableEntity(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::Malformed(message) => f.write_str(message),
+            BuildError::UnresolvableEntity(entity) => {
+                write!(f, "entity &{}; can't be resolved", entity)
+            }
+        }
+    }
+}
+
+impl From<&str> for BuildError {
+    fn from(message: &str) -> Self {
+        BuildError::Malformed(message.to_string())
+    }
+}
+
+impl From<String> for BuildError {
+    fn from(message: String) -> Self {
+        BuildError::Malformed(message)
+    }
+}
+
+impl From<quick_xml::Error> for BuildError {
+    fn from(e: quick_xml::Error) -> Self {
+        BuildError::Malformed(e.to_string())
+    }
+}
+
+impl From<EscapeError> for BuildError {
+    fn from(e: EscapeError) -> Self {
+        BuildError::Malformed(e.to_string())
+    }
+}
+
+/// Treatment of the references in text and attribute values, see
+/// `ConversionConfig::expand_entities` and `ConversionConfig::entity_handling`.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct References {
+    /// Keep references as written instead of expanding them.
+    keep: bool,
+    handling: EntityHandling,
+}
+
+impl References {
+    pub(crate) fn new(cfg: &ConversionConfig) -> Self {
+        Self {
+            keep: !cfg.expand_entities,
+            handling: cfg.entity_handling,
+        }
+    }
+
+    /// Value of the raw text `raw`. Character references and the predefined entities are
+    /// expanded, other entities are kept as written or rejected depending on `handling`.
+    fn expand(self, raw: &str) -> Result<Cow<'_, str>, BuildError> {
+        if self.keep || !raw.contains('&') {
+            return Ok(Cow::Borrowed(raw));
+        }
+        let mut value = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(amp) = rest.find('&') {
+            value.push_str(&rest[..amp]);
+            let start = raw.len() - rest.len() + amp;
+            let end = match rest[amp..].find(';') {
+                Some(semicolon) => amp + semicolon + 1,
+                None => return Err(EscapeError::UnterminatedEntity(start..raw.len()).into()),
+            };
+            let reference = &rest[amp..end];
+            let name = &reference[1..reference.len() - 1];
+            let predefined = resolve_predefined_entity(name);
+            match (self.handling, predefined) {
+                _ if name.starts_with('#') => value.push_str(&unescape(reference)?),
+                (EntityHandling::PreserveAsString, _) => value.push_str(reference),
+                (_, Some(c)) => value.push_str(c),
+                (EntityHandling::ExpandBuiltin, None) => value.push_str(reference),
+                (EntityHandling::Error, None) => {
+                    return Err(BuildError::UnresolvableEntity(name.to_string()))
+                }
+                (EntityHandling::Default, None) => {
+                    let range = start..start + reference.len();
+                    return Err(EscapeError::UnrecognizedEntity(range, name.to_string()).into());
+                }
+            }
+            rest = &rest[end..];
+        }
+        value.push_str(rest);
+        Ok(Cow::Owned(value))
+    }
+}
+
+/// Builds a treexml element tree from pull parser events, mirroring what
+/// `treexml::Document::parse` produces for the same document.
+#[derive(Default)]
+pub(crate) struct TreeBuilder {
+    pub(crate) open: Vec<treexml::Element>,
+    pub(crate) root: Option<treexml::Element>,
+    pub(crate) references: References,
+    /// Keep namespace declarations among the attributes, which treexml leaves out.
+    pub(crate) declarations: bool,
+    /// Record comments and processing instructions, which treexml drops, in `notes`.
+    pub(crate) annotate: bool,
+    /// Annotations of the open elements, while `annotate` is set.
+    pub(crate) open_notes: Vec<Annotations>,
+    /// Annotations of the root, once it is closed.
+    pub(crate) notes: Option<Annotations>,
+}
+
+fn utf8(bytes: &[u8]) -> Result<&str, quick_xml::Error> {
+    std::str::from_utf8(bytes).map_err(|e| EncodingError::from(e).into())
+}
+
+/// Creates an element from its start tag. Namespace declarations are left out of the
+/// attributes, as with treexml.
+#[cfg(feature = "streaming")]
+pub(crate) fn start_element(start: &BytesStart) -> Result<treexml::Element, BuildError> {
+    start_element_with(start, References::default(), false)
+}
+
+/// Same as `start_element`, with the references in attribute values treated as `references`
+/// asks, and namespace declarations kept if `declarations` is set.
+fn start_element_with(
+    start: &BytesStart,
+    references: References,
+    declarations: bool,
+) -> Result<treexml::Element, BuildError> {
+    let name = utf8(start.name().as_ref())?.to_string();
+    let mut e = match name.split_once(':') {
+        Some((prefix, local)) => treexml::Element {
+            prefix: Some(prefix.to_string()),
+            ..treexml::Element::new(local)
+        },
+        None => treexml::Element::new(name),
+    };
+    for attr in start.attributes() {
+        let attr = attr.map_err(quick_xml::Error::from)?;
+        if !declarations && attr.key.as_namespace_binding().is_some() {
+            continue;
+        }
+        let key = utf8(attr.key.as_ref())?.to_string();
+        let value = references.expand(utf8(&attr.value)?)?.into_owned();
+        e.attributes.insert(key, value);
+    }
+    Ok(e)
+}
+
+impl TreeBuilder {
+    /// Creates an element from its start tag, as the options of the builder ask.
+    pub(crate) fn element(&self, start: &BytesStart) -> Result<treexml::Element, BuildError> {
+        start_element_with(start, self.references, self.declarations)
+    }
+
+    /// Makes `e` the innermost open element.
+    pub(crate) fn open(&mut self, e: treexml::Element) {
+        self.open.push(e);
+        if self.annotate {
+            self.open_notes.push(Annotations::default());
+        }
+    }
+
+    /// Attaches a finished element to its parent, or makes it the root.
+    fn close(&mut self, e: treexml::Element, notes: Annotations) -> Result<(), String> {
+        match self.open.last_mut() {
+            Some(parent) => parent.children.push(e),
+            None if self.root.is_some() => return Err("multiple root elements".to_string()),
+            None => self.root = Some(e),
+        }
+        if self.annotate {
+            match self.open_notes.last_mut() {
+                Some(parent) => parent.children.push(notes),
+                None => self.notes = Some(notes),
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles one event. Returns `true` once the end of the document is reached.
+    pub(crate) fn event(&mut self, event: Event) -> Result<bool, BuildError> {
+        match event {
+            Event::Start(start) => {
+                let e = self.element(&start)?;
+                if self.open.is_empty() && self.root.is_some() {
+                    return Err("multiple root elements".into());
+                }
+                self.open(e);
+            }
+            Event::Empty(start) => {
+                let e = self.element(&start)?;
+                self.close(e, Annotations::default())?;
+            }
+            Event::End(_) => {
+                let e = self.open.pop().ok_or("unexpected end tag")?;
+                let notes = self.open_notes.pop().unwrap_or_default();
+                self.close(e, notes)?;
+            }
+            Event::Text(text) => {
+                let text = self.references.expand(utf8(&text)?)?;
+                if text.chars().all(char::is_whitespace) {
+                    return Ok(false);
+                }
+                let e = self
+                    .open
+                    .last_mut()
+                    .ok_or("text outside the root element")?;
+                e.text.get_or_insert_with(String::new).push_str(&text);
+            }
+            Event::CData(cdata) => {
+                let cdata = cdata.decode().map_err(quick_xml::Error::from)?;
+                let e = self
+                    .open
+                    .last_mut()
+                    .ok_or("CDATA outside the root element")?;
+                e.cdata.get_or_insert_with(String::new).push_str(&cdata);
+            }
+            Event::Eof => {
+                if let Some(e) = self.open.last() {
+                    return Err(format!("element {} is not closed", e.name).into());
+                }
+                return Ok(true);
+            }
+            Event::Comment(text) => {
+                if let Some(notes) = self.open_notes.last_mut() {
+                    notes.comments.push(utf8(&text)?.to_string());
+                }
+            }
+            Event::PI(pi) => {
+                if let Some(notes) = self.open_notes.last_mut() {
+                    notes.pis.push(utf8(&pi)?.to_string());
+                }
+            }
+            Event::Decl(_) | Event::DocType(_) => {}
+        }
+        Ok(false)
+    }
+}
+
+/// Converts an I/O error reported by the parser back into an owned one.
+#[cfg(feature = "streaming")]
+pub(crate) fn io_error(e: Arc<io::Error>) -> io::Error {
+    Arc::try_unwrap(e).unwrap_or_else(|e| io::Error::new(e.kind(), e.to_string()))
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+
+use pyo3::{
+    exceptions::{PyKeyError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+use serde_json::Value;
+
+use crate::{
+    node2object_with_config, value_to_element, ArraySortKey, AttrPosition, Base64Handling,
+    BinaryEncoding, CollisionStrategy, ContextConflict, ConversionConfig, ConversionMode,
+    DecimalSeparator, DefaultNsHandling, EmptyAttribute, EmptyElement, EntityHandling,
+    HeteroPolicy, PrefixHandling, SplitStrategy, TextMergeOrder, WrapperKey, XMLNodeType,
+};
+
+/// Converts a JSON value into the equivalent Python object through the `json` module.
+fn to_python<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    py.import("json")?
+        .call_method1("loads", (value.to_string(),))
+}
+
+/// Converts a JSON-compatible Python object into a JSON value through the `json` module.
+fn from_python(obj: &Bound<'_, PyAny>) -> PyResult<Value> {
+    let json: String = obj
+        .py()
+        .import("json")?
+        .call_method1("dumps", (obj,))?
+        .extract()?;
+    serde_json::from_str(&json).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Error for an option value that names no variant.
+fn invalid(key: &str, name: &str) -> PyErr {
+    PyValueError::new_err(format!("invalid value {:?} for {}", name, key))
+}
+
+/// Finds the variant of an enum option named `name` in snake case.
+fn variant<T: Copy>(key: &str, name: &str, variants: &[(&str, T)]) -> PyResult<T> {
+    variants
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, v)| *v)
+        .ok_or_else(|| invalid(key, name))
+}
+
+/// Parses an enum option given by the snake case name of its variant.
+fn parse_variant<T: Copy>(
+    key: &str,
+    value: &Bound<'_, PyAny>,
+    variants: &[(&str, T)],
+) -> PyResult<T> {
+    variant(key, &value.extract::<String>()?, variants)
+}
+
+/// Parses a dict option whose values are enum variants given by their snake case name.
+fn parse_variant_map<T: Copy>(
+    key: &str,
+    value: &Bound<'_, PyAny>,
+    variants: &[(&str, T)],
+) -> PyResult<HashMap<String, T>> {
+    value
+        .extract::<HashMap<String, String>>()?
+        .into_iter()
+        .map(|(k, name)| Ok((k, variant(key, &name, variants)?)))
+        .collect()
+}
+
+/// Sets the `ConversionConfig` field named `key`. Options with plain values take them as they
+/// are, enum options the snake case name of a variant, and `defaults`, `default_text_value`
+/// and `truncation_placeholder` any JSON-compatible value. `text_key_for_type` is keyed by the
+/// snake case name of an `XMLNodeType` variant. `id_references`, `attribute_parser` and
+/// `text_parser` can't be set; `detect_dates` and `date_patterns` only with the `dates` feature.
+fn set_option(cfg: &mut ConversionConfig, key: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+    macro_rules! fields {
+        ($($field:ident),* $(,)?) => {
+            match key {
+                $(stringify!($field) => {
+                    cfg.$field = value.extract()?;
+                    return Ok(());
+                })*
+                _ => {}
+            }
+        };
+    }
+
+    fields!(
+        include_root,
+        include_xml_declaration,
+        text_key,
+        reserved_key_escape,
+        root_element_name_key,
+        inject_source_tag,
+        source_tag_key,
+        group_by_xml_lang,
+        strict,
+        expand_entities,
+        attribute_as_child,
+        merge_attributes_into_root,
+        attributes_key,
+        max_depth,
+        truncate_at_max_depth,
+        child_as_attribute,
+        drop_attributes,
+        skip_attributes,
+        strip_xmlns_attributes,
+        prefix_map,
+        strip_namespaces,
+        attribute_namespace_separator,
+        namespace_key,
+        collapse_wrappers,
+        merge_siblings,
+        force_array,
+        max_occurrences,
+        truncated_count_key,
+        single_element_array_threshold,
+        harmonize_sibling_types,
+        heterogeneous_arrays_allow_null,
+        array_key_rename,
+        arrays_as_indexed_objects,
+        indexed_object_base,
+        include_positions,
+        position_key,
+        separate_cdata,
+        cdata_key,
+        text_merge_separator,
+        comment_key,
+        pi_key,
+        trim_text,
+        coerce_padded_numbers,
+        coerce_numbers,
+        parse_radix_literals,
+        allow_thousands_separators,
+        allow_leading_plus,
+        coerce_scientific_notation,
+        strict_number_roundtrip,
+        omit_empty_attributes,
+        ensure_array_keys,
+        defaults_for_attribute_only,
+        preserve_original_string,
+        boolean_in_attributes,
+        boolean_in_text,
+        all_scalars_as_strings,
+    );
+    #[cfg(feature = "dates")]
+    fields!(detect_dates, date_patterns);
+    match key {
+        "mode" => {
+            cfg.mode = parse_variant(
+                key,
+                value,
+                &[
+                    ("standard", ConversionMode::Standard),
+                    ("gdata", ConversionMode::GData),
+                ],
+            )?
+        }
+        "empty_element_as" => {
+            cfg.empty_element_as = parse_variant(
+                key,
+                value,
+                &[
+                    ("omit", EmptyElement::Omit),
+                    ("null", EmptyElement::Null),
+                    ("empty_string", EmptyElement::EmptyString),
+                    ("empty_object", EmptyElement::EmptyObject),
+                    ("boolean", EmptyElement::Boolean),
+                ],
+            )?
+        }
+        "empty_attribute_as" => {
+            cfg.empty_attribute_as = parse_variant(
+                key,
+                value,
+                &[
+                    ("empty_string", EmptyAttribute::EmptyString),
+                    ("null", EmptyAttribute::Null),
+                    ("skip", EmptyAttribute::Skip),
+                ],
+            )?
+        }
+        "text_merge_order" => {
+            cfg.text_merge_order = parse_variant(
+                key,
+                value,
+                &[
+                    ("text_first", TextMergeOrder::TextFirst),
+                    ("cdata_first", TextMergeOrder::CdataFirst),
+                ],
+            )?
+        }
+        "namespace_prefixes" => {
+            cfg.namespace_prefixes = parse_variant(
+                key,
+                value,
+                &[
+                    ("attributes_only", PrefixHandling::AttributesOnly),
+                    ("keep", PrefixHandling::Keep),
+                    ("strip", PrefixHandling::Strip),
+                ],
+            )?
+        }
+        "decimal_separator" => {
+            cfg.decimal_separator = parse_variant(
+                key,
+                value,
+                &[
+                    ("dot", DecimalSeparator::Dot),
+                    ("comma", DecimalSeparator::Comma),
+                ],
+            )?
+        }
+        "attribute_position" => {
+            cfg.attribute_position = parse_variant(
+                key,
+                value,
+                &[
+                    ("first", AttrPosition::First),
+                    ("last", AttrPosition::Last),
+                    ("sorted", AttrPosition::Sorted),
+                ],
+            )?
+        }
+        "attribute_child_collision" => {
+            cfg.attribute_child_collision = parse_variant(
+                key,
+                value,
+                &[
+                    ("attribute_wins", CollisionStrategy::AttributeWins),
+                    ("child_wins", CollisionStrategy::ChildWins),
+                    ("error", CollisionStrategy::Error),
+                ],
+            )?
+        }
+        "heterogeneous_arrays" => {
+            cfg.heterogeneous_arrays = parse_variant(
+                key,
+                value,
+                &[
+                    ("allow", HeteroPolicy::Allow),
+                    ("stringify", HeteroPolicy::Stringify),
+                    ("error", HeteroPolicy::Error),
+                ],
+            )?
+        }
+        "collapsed_wrapper_key" => {
+            cfg.collapsed_wrapper_key = parse_variant(
+                key,
+                value,
+                &[
+                    ("wrapper", WrapperKey::Wrapper),
+                    ("child", WrapperKey::Child),
+                ],
+            )?
+        }
+        "entity_handling" => {
+            cfg.entity_handling = parse_variant(
+                key,
+                value,
+                &[
+                    ("default", EntityHandling::Default),
+                    ("expand_builtin", EntityHandling::ExpandBuiltin),
+                    ("preserve_as_string", EntityHandling::PreserveAsString),
+                    ("error", EntityHandling::Error),
+                ],
+            )?
+        }
+        "context_conflict" => {
+            cfg.context_conflict = parse_variant(
+                key,
+                value,
+                &[
+                    ("context_wins", ContextConflict::ContextWins),
+                    ("xml_wins", ContextConflict::XmlWins),
+                    ("error", ContextConflict::Error),
+                ],
+            )?
+        }
+        // `ApplyPrefix` carries its prefix, given as `{'apply_prefix': prefix}`.
+        "default_namespace" => {
+            cfg.default_namespace = match value.extract::<HashMap<String, String>>() {
+                Ok(mut prefix) => match prefix.remove("apply_prefix") {
+                    Some(prefix_name) if prefix.is_empty() => {
+                        DefaultNsHandling::ApplyPrefix(prefix_name)
+                    }
+                    _ => return Err(invalid(key, &value.to_string())),
+                },
+                Err(_) => match value.extract::<String>()?.as_str() {
+                    "ignore" => DefaultNsHandling::Ignore,
+                    "expand" => DefaultNsHandling::Expand,
+                    name => return Err(invalid(key, name)),
+                },
+            }
+        }
+        "sort_arrays_by" => {
+            cfg.sort_arrays_by = match value.is_none() {
+                true => None,
+                false => Some(match value.extract::<String>()?.as_str() {
+                    "first_key" => ArraySortKey::FirstKey,
+                    "all_keys" => ArraySortKey::AllKeys,
+                    name => return Err(invalid(key, name)),
+                }),
+            }
+        }
+        "text_key_for_type" => {
+            let types = &[
+                ("empty", XMLNodeType::Empty),
+                ("text", XMLNodeType::Text),
+                ("attributes", XMLNodeType::Attributes),
+                ("text_and_attributes", XMLNodeType::TextAndAttributes),
+                ("parent", XMLNodeType::Parent),
+                ("semi_structured", XMLNodeType::SemiStructured),
+            ];
+            cfg.text_key_for_type = value
+                .extract::<HashMap<String, String>>()?
+                .into_iter()
+                .map(|(kind, text_key)| Ok((variant(key, &kind, types)?, text_key)))
+                .collect::<PyResult<_>>()?
+        }
+        // A single character other than the named separators splits on that character.
+        "split_attribute_values" => {
+            cfg.split_attribute_values = value
+                .extract::<HashMap<String, String>>()?
+                .into_iter()
+                .map(|(name, strategy)| {
+                    let mut chars = strategy.chars();
+                    let strategy = match (strategy.as_str(), chars.next(), chars.next()) {
+                        ("whitespace", ..) => SplitStrategy::Whitespace,
+                        ("comma", ..) => SplitStrategy::Comma,
+                        ("semicolon", ..) => SplitStrategy::Semicolon,
+                        (_, Some(c), None) => SplitStrategy::Char(c),
+                        _ => return Err(invalid(key, &strategy)),
+                    };
+                    Ok((name, strategy))
+                })
+                .collect::<PyResult<_>>()?
+        }
+        "base64_paths" => {
+            cfg.base64_paths = parse_variant_map(
+                key,
+                value,
+                &[
+                    ("tagged", Base64Handling::Tagged),
+                    ("metadata", Base64Handling::Metadata),
+                    ("hex", Base64Handling::Reencode(BinaryEncoding::Hex)),
+                    (
+                        "base64_url",
+                        Base64Handling::Reencode(BinaryEncoding::Base64Url),
+                    ),
+                ],
+            )?
+        }
+        "truncation_placeholder" => cfg.truncation_placeholder = from_python(value)?,
+        "default_text_value" => {
+            cfg.default_text_value = match from_python(value)? {
+                Value::Null => None,
+                v => Some(v),
+            }
+        }
+        "defaults" => {
+            cfg.defaults = match from_python(value)? {
+                Value::Object(defaults) => defaults.into_iter().collect(),
+                _ => return Err(PyValueError::new_err("defaults must be a dict")),
+            }
+        }
+        _ => return Err(PyKeyError::new_err(format!("unknown option {}", key))),
+    }
+    Ok(())
+}
+
+/// Builds a configuration from a dict keyed by `ConversionConfig` field names.
+fn config_from_dict(config: Option<&Bound<'_, PyDict>>) -> PyResult<ConversionConfig> {
+    let mut cfg = ConversionConfig::default();
+    for (key, value) in config.into_iter().flatten() {
+        set_option(&mut cfg, &key.extract::<String>()?, &value)?;
+    }
+    Ok(cfg)
+}
+
+/// Parses `xml_str` and converts its root element, see `node2object_with_config`.
+#[pyfunction]
+#[pyo3(name = "node2object", signature = (xml_str, config = None))]
+fn py_node2object<'py>(
+    py: Python<'py>,
+    xml_str: &str,
+    config: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let cfg = config_from_dict(config)?;
+    let root = treexml::Document::parse(xml_str.as_bytes())
+        .map_err(|e| PyValueError::new_err(e.to_string()))?
+        .root
+        .ok_or_else(|| PyValueError::new_err("document has no root element"))?;
+    let data =
+        node2object_with_config(&root, &cfg).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    to_python(py, &Value::Object(data))
+}
+
+/// Converts `data` into an XML string whose root element is named `root_tag`, see
+/// `value_to_element`.
+#[pyfunction]
+fn object2node(data: &Bound<'_, PyAny>, root_tag: &str) -> PyResult<String> {
+    let element = value_to_element(root_tag, &from_python(data)?)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(element.to_string())
+}
+
+/// The `node2object` Python module.
+#[pymodule]
+#[pyo3(name = "node2object")]
+pub fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(py_node2object, m)?)?;
+    m.add_function(wrap_pyfunction!(object2node, m)?)?;
+    Ok(())
+}
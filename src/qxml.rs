@@ -0,0 +1,77 @@
+use std::io::BufRead;
+
+use quick_xml::{events::BytesStart, Reader};
+use serde_json::{Map, Value};
+
+use crate::{
+    annotations::Recorded,
+    namespaces::convert_declared,
+    pull::{BuildError, References, TreeBuilder},
+    ConversionConfig, ConversionError, JsonPointer, XmlPath,
+};
+
+/// Reads the element opened by `element` from `reader`, up to its end tag, and converts it like
+/// `node2object_with_config`. `element` must be the start tag of an `Event::Start` that
+/// `reader` has just returned, so that elements can be picked out of a document without
+/// building a tree of the rest. Self-closing elements come as `Event::Start` too when the
+/// reader is configured with `expand_empty_elements`.
+///
+/// Whitespace-only text is skipped, as with treexml. The namespace declarations read from
+/// `element` down are kept for `prefix_map`, `namespace_key` and the other namespace options
+/// to resolve, but never become attributes, whatever `strip_xmlns_attributes` says. Those made
+/// above `element` aren't known, so its names in them are taken to be in no namespace. As
+/// quick-xml reads the references in text and attribute values as written,
+/// `expand_entities` and `entity_handling` apply, and comments and processing instructions are
+/// kept under `comment_key` and `pi_key` when these are set.
+pub fn qxml_node2object<R: BufRead>(
+    element: &BytesStart,
+    reader: &mut Reader<R>,
+    cfg: &ConversionConfig,
+) -> Result<Map<String, Value>, ConversionError> {
+    let mut builder = TreeBuilder {
+        references: References::new(cfg),
+        declarations: true,
+        annotate: cfg.comment_key.is_some() || cfg.pi_key.is_some(),
+        ..TreeBuilder::default()
+    };
+    let failed = |builder: &TreeBuilder, e: BuildError| {
+        let mut xml_path = XmlPath::default();
+        for e in &builder.open {
+            xml_path.push_element(&e.name, None);
+        }
+        let path = JsonPointer::default();
+        match e {
+            BuildError::Malformed(message) => ConversionError::Malformed {
+                message,
+                path,
+                xml_path,
+            },
+            BuildError::UnresolvableEntity(entity) => ConversionError::UnresolvableEntity {
+                entity,
+                path,
+                xml_path,
+            },
+        }
+    };
+
+    let start = builder.element(element).map_err(|e| failed(&builder, e))?;
+    builder.open(start);
+    let mut buf = Vec::new();
+    while builder.root.is_none() {
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| failed(&builder, e.into()))?;
+        builder.event(event).map_err(|e| failed(&builder, e))?;
+        buf.clear();
+    }
+
+    let root = builder
+        .root
+        .expect("the loop ends once the element is closed");
+    let recorded = Recorded {
+        references: true,
+        comments: true,
+        positions: false,
+    };
+    convert_declared(&root, builder.notes.as_ref(), recorded, cfg)
+}
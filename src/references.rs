@@ -0,0 +1,314 @@
+use std::{cell::Cell, collections::HashMap};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    attribute_key, element_key, push_xml_step, text_key, ConversionConfig, ConversionError,
+    IdReferences, JsonPointer, ReferenceMode, XmlPath,
+};
+
+/// Identifier stored in a converted attribute value, which may have been coerced to a number
+/// or boolean, or wrapped in an object by `attribute_as_child`.
+fn identifier(v: &Value, cfg: &ConversionConfig) -> Option<String> {
+    match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(v.to_string()),
+        Value::Object(data) if data.len() == 1 => identifier(data.get(text_key(cfg))?, cfg),
+        _ => None,
+    }
+}
+
+/// Attributes of the element converted to `data`, grouped under `attributes_key` if set.
+fn attributes<'v>(data: &'v Map<String, Value>, cfg: &ConversionConfig) -> &'v Map<String, Value> {
+    match cfg.attributes_key.as_ref().map(|key| data.get(key)) {
+        Some(Some(Value::Object(attributes))) => attributes,
+        _ => data,
+    }
+}
+
+fn attributes_mut<'v>(
+    data: &'v mut Map<String, Value>,
+    cfg: &ConversionConfig,
+) -> &'v mut Map<String, Value> {
+    let grouped = matches!(
+        cfg.attributes_key.as_ref().map(|key| data.get(key)),
+        Some(Some(Value::Object(_)))
+    );
+    match (grouped, &cfg.attributes_key) {
+        (true, Some(key)) => match data.get_mut(key) {
+            Some(Value::Object(attributes)) => attributes,
+            _ => unreachable!("checked above"),
+        },
+        _ => data,
+    }
+}
+
+/// Reference resolution state for one document.
+struct Resolver<'a> {
+    cfg: &'a ConversionConfig,
+    refs: &'a IdReferences,
+    /// Root of the converted tree, for locating errors in the source document.
+    root: &'a treexml::Element,
+    /// Output as converted, before any reference was replaced.
+    original: Value,
+    /// Location of the element carrying each identifier, as reference tokens.
+    targets: HashMap<String, Vec<String>>,
+    /// Number of values inlined so far, see `IdReferences::max_inlined`.
+    inlined: Cell<usize>,
+}
+
+/// Where a value being resolved is, as reference tokens: `path` in the output and `source` in
+/// the output as converted, which differ inside inlined values.
+struct Location {
+    path: Vec<String>,
+    source: Vec<String>,
+}
+
+impl Location {
+    fn push(&mut self, token: &str) {
+        self.path.push(token.to_string());
+        self.source.push(token.to_string());
+    }
+
+    fn pop(&mut self) {
+        self.path.pop();
+        self.source.pop();
+    }
+}
+
+/// JSON pointer made of `tokens`.
+fn pointer(tokens: &[String]) -> JsonPointer {
+    let mut pointer = JsonPointer::default();
+    for token in tokens {
+        pointer.push(token);
+    }
+    pointer
+}
+
+/// Records the location of every element with an identifier below `v`, found at `path`.
+fn collect_targets(
+    v: &Value,
+    path: &mut Vec<String>,
+    id_key: &str,
+    cfg: &ConversionConfig,
+    targets: &mut HashMap<String, Vec<String>>,
+) {
+    match v {
+        Value::Object(data) => {
+            if let Some(id) = attributes(data, cfg)
+                .get(id_key)
+                .and_then(|id| identifier(id, cfg))
+            {
+                targets.entry(id).or_insert_with(|| path.clone());
+            }
+            for (k, v) in data {
+                path.push(k.clone());
+                collect_targets(v, path, id_key, cfg, targets);
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (i, v) in items.iter().enumerate() {
+                path.push(i.to_string());
+                collect_targets(v, path, id_key, cfg, targets);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+impl Resolver<'_> {
+    /// Replaces the references below `v`, found `at`. `inlining` lists the identifiers of the
+    /// values being inlined around `v`, to detect cycles.
+    fn resolve(
+        &self,
+        v: &mut Value,
+        at: &mut Location,
+        inlining: &mut Vec<String>,
+    ) -> Result<(), ConversionError> {
+        match v {
+            Value::Object(data) => {
+                for name in &self.refs.reference_attributes {
+                    let key = attribute_key(name, self.cfg);
+                    let attributes = attributes_mut(data, self.cfg);
+                    let id = match attributes.get(&key).and_then(|v| identifier(v, self.cfg)) {
+                        Some(id) => id,
+                        None => continue,
+                    };
+                    let reference = Reference { id: &id, name, at };
+                    if let Some(target) = self.target(reference, inlining)? {
+                        attributes.insert(key, target);
+                    }
+                }
+                for (k, v) in data.iter_mut() {
+                    at.push(k);
+                    self.resolve(v, at, inlining)?;
+                    at.pop();
+                }
+            }
+            Value::Array(items) => {
+                for (i, v) in items.iter_mut().enumerate() {
+                    at.push(&i.to_string());
+                    self.resolve(v, at, inlining)?;
+                    at.pop();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Replacement for `reference`, or `None` to leave a dangling reference alone.
+    fn target(
+        &self,
+        reference: Reference,
+        inlining: &mut Vec<String>,
+    ) -> Result<Option<Value>, ConversionError> {
+        let id = reference.id;
+        let target = match self.targets.get(id) {
+            Some(target) => target,
+            None if self.cfg.strict => {
+                return Err(ConversionError::DanglingReference {
+                    id: id.to_string(),
+                    path: reference.path(self.cfg),
+                    xml_path: self.xml_path(&reference),
+                })
+            }
+            None => return Ok(None),
+        };
+        match self.refs.mode {
+            ReferenceMode::Pointer => Ok(Some(Value::String(pointer(target).to_string()))),
+            ReferenceMode::Inline => {
+                if inlining.iter().any(|i| i == id) {
+                    return Err(ConversionError::ReferenceCycle {
+                        id: id.to_string(),
+                        path: reference.path(self.cfg),
+                        xml_path: self.xml_path(&reference),
+                    });
+                }
+                let inlined = self.inlined.get() + 1;
+                if inlined > self.refs.max_inlined {
+                    return Err(ConversionError::InliningLimitExceeded {
+                        id: id.to_string(),
+                        limit: self.refs.max_inlined,
+                        path: reference.path(self.cfg),
+                        xml_path: self.xml_path(&reference),
+                    });
+                }
+                self.inlined.set(inlined);
+
+                let mut value = self
+                    .original
+                    .pointer(pointer(target).as_str())
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                let mut at = Location {
+                    path: reference.at.path.clone(),
+                    source: target.clone(),
+                };
+                at.path.push(attribute_key(reference.name, self.cfg));
+                inlining.push(id.to_string());
+                self.resolve(&mut value, &mut at, inlining)?;
+                inlining.pop();
+                Ok(Some(value))
+            }
+        }
+    }
+
+    /// Locates `reference` in the source document, following its element keys and array
+    /// indices in the output as converted down from the root as far as they match.
+    fn xml_path(&self, reference: &Reference) -> XmlPath {
+        let mut path = XmlPath::default();
+        let mut e = self.root;
+        push_xml_step(&mut path, None, e);
+        let mut tokens = reference.at.source.iter().peekable();
+        if self.cfg.include_root {
+            tokens.next();
+        }
+        while let Some(token) = tokens.next() {
+            // Element names never start with a digit, so such tokens are array indices.
+            let index = match tokens.peek().and_then(|i| i.parse::<usize>().ok()) {
+                Some(index) => {
+                    tokens.next();
+                    index
+                }
+                None => 0,
+            };
+            let member = e
+                .children
+                .iter()
+                .filter(|c| element_key(c, self.cfg) == token.as_str())
+                .nth(index);
+            match member {
+                Some(c) => {
+                    push_xml_step(&mut path, Some(e), c);
+                    e = c;
+                }
+                None => break,
+            }
+        }
+        path.push_attribute(reference.name);
+        path
+    }
+}
+
+/// A reference attribute being resolved.
+struct Reference<'r> {
+    /// Identifier it holds.
+    id: &'r str,
+    /// Name of the attribute.
+    name: &'r str,
+    /// Location of the element carrying it.
+    at: &'r Location,
+}
+
+impl Reference<'_> {
+    /// Location of the attribute in the output.
+    fn path(&self, cfg: &ConversionConfig) -> JsonPointer {
+        let mut path = pointer(&self.at.path);
+        path.push(&attribute_key(self.name, cfg));
+        path
+    }
+}
+
+/// Replaces the reference attributes in the converted output `data` of `root`, an element with
+/// the namespace options applied, according to `ConversionConfig::id_references`.
+pub(crate) fn resolve_references(
+    data: &mut Map<String, Value>,
+    root: &treexml::Element,
+    cfg: &ConversionConfig,
+) -> Result<(), ConversionError> {
+    let refs = match &cfg.id_references {
+        Some(refs) if !refs.reference_attributes.is_empty() => refs,
+        _ => return Ok(()),
+    };
+    let original = Value::Object(data.clone());
+    let mut targets = HashMap::new();
+    collect_targets(
+        &original,
+        &mut Vec::new(),
+        &attribute_key(&refs.id_attribute, cfg),
+        cfg,
+        &mut targets,
+    );
+    let resolver = Resolver {
+        cfg,
+        refs,
+        root,
+        original,
+        targets,
+        inlined: Cell::new(0),
+    };
+
+    let mut value = Value::Object(std::mem::take(data));
+    let mut at = Location {
+        path: Vec::new(),
+        source: Vec::new(),
+    };
+    resolver.resolve(&mut value, &mut at, &mut Vec::new())?;
+    if let Value::Object(resolved) = value {
+        *data = resolved;
+    }
+    Ok(())
+}
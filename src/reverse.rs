@@ -0,0 +1,197 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{JsonPointer, ReverseError};
+
+/// Checks that `name` can be used as an XML element or attribute name.
+pub(crate) fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' || c == ':' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
+
+/// Renders a scalar as XML text. Integral floats are written without a fractional part, so
+/// that `42.0` (as produced by `node2object` for `<n>42</n>`) turns back into `42`.
+fn scalar_text(v: &Value) -> Option<String> {
+    match v {
+        Value::Null => Some(String::new()),
+        Value::Bool(v) => Some(v.to_string()),
+        Value::Number(n) => Some(match n.as_f64() {
+            Some(f) if n.is_f64() => f.to_string(),
+            _ => n.to_string(),
+        }),
+        Value::String(s) => Some(s.clone()),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+fn named_element(tag: &str, path: &JsonPointer) -> Result<treexml::Element, ReverseError> {
+    if valid_name(tag) {
+        Ok(treexml::Element::new(tag))
+    } else {
+        Err(ReverseError::InvalidName {
+            name: tag.into(),
+            path: path.clone(),
+        })
+    }
+}
+
+/// Options controlling `value_to_element_with_config` and `struct2node`.
+#[derive(Clone, Debug)]
+pub struct ReverseConfig {
+    /// Tag of the elements created for the items of arrays that aren't stored under an object
+    /// key, i.e. arrays passed in directly and arrays nested in other arrays.
+    pub mixed_array_item_tag: String,
+    /// Prefix of the keys that become attributes.
+    pub attribute_prefix: String,
+    /// Key that becomes the text content of its element.
+    pub text_key: String,
+}
+
+impl Default for ReverseConfig {
+    fn default() -> Self {
+        Self {
+            mixed_array_item_tag: "item".into(),
+            attribute_prefix: "@".into(),
+            text_key: "#text".into(),
+        }
+    }
+}
+
+/// Strips `ReverseConfig::attribute_prefix` from `key`, if it names an attribute.
+pub(crate) fn attribute_name<'a>(key: &'a str, cfg: &ReverseConfig) -> Option<&'a str> {
+    if cfg.attribute_prefix.is_empty() {
+        None
+    } else {
+        key.strip_prefix(cfg.attribute_prefix.as_str())
+    }
+}
+
+fn fill_object(
+    e: &mut treexml::Element,
+    data: &Map<String, Value>,
+    path: &JsonPointer,
+    cfg: &ReverseConfig,
+    item_tag: Option<&str>,
+) -> Result<(), ReverseError> {
+    for (k, v) in data {
+        let mut child_path = path.clone();
+        child_path.push(k);
+
+        if let Some(name) = attribute_name(k, cfg) {
+            if !valid_name(name) {
+                return Err(ReverseError::InvalidName {
+                    name: name.into(),
+                    path: child_path,
+                });
+            }
+            let text =
+                scalar_text(v).ok_or(ReverseError::NonScalarAttribute { path: child_path })?;
+            e.attributes.insert(name.into(), text);
+        } else if *k == cfg.text_key {
+            let text = scalar_text(v).ok_or(ReverseError::NonScalarText { path: child_path })?;
+            e.text = Some(text);
+        } else if let Value::Array(items) = v {
+            for (i, item) in items.iter().enumerate() {
+                let mut item_path = child_path.clone();
+                item_path.push(&i.to_string());
+                if item.is_array() && item_tag.is_none() {
+                    return Err(ReverseError::NestedArray { path: item_path });
+                }
+                e.children
+                    .push(build_element(k, item, &item_path, cfg, item_tag)?);
+            }
+        } else {
+            e.children
+                .push(build_element(k, v, &child_path, cfg, item_tag)?);
+        }
+    }
+    Ok(())
+}
+
+/// Builds the element `tag` for `value`. Arrays reaching this point have no key of their own;
+/// their items become `item_tag` children, or fail the conversion if no item tag is given.
+fn build_element(
+    tag: &str,
+    value: &Value,
+    path: &JsonPointer,
+    cfg: &ReverseConfig,
+    item_tag: Option<&str>,
+) -> Result<treexml::Element, ReverseError> {
+    let mut e = named_element(tag, path)?;
+    match value {
+        Value::Object(data) => fill_object(&mut e, data, path, cfg, item_tag)?,
+        Value::Array(items) => match item_tag {
+            Some(item_tag) => {
+                for (i, item) in items.iter().enumerate() {
+                    let mut item_path = path.clone();
+                    item_path.push(&i.to_string());
+                    e.children.push(build_element(
+                        item_tag,
+                        item,
+                        &item_path,
+                        cfg,
+                        Some(item_tag),
+                    )?);
+                }
+            }
+            None => return Err(ReverseError::NestedArray { path: path.clone() }),
+        },
+        Value::Null => {}
+        v => e.text = scalar_text(v),
+    }
+    Ok(e)
+}
+
+/// Creates an element named `tag` from a JSON value, reversing the mapping of `node2object`:
+/// object keys starting with `@` become attributes, `#text` becomes text content, arrays become
+/// repeated children named after their key, nested objects recurse, and scalars become text.
+/// `null` produces an empty element. Arrays that aren't stored under an object key can't be
+/// represented and fail with `ReverseError::NestedArray`; see `value_to_element_with_config`.
+pub fn value_to_element(tag: &str, value: &Value) -> Result<treexml::Element, ReverseError> {
+    build_element(
+        tag,
+        value,
+        &JsonPointer::default(),
+        &ReverseConfig::default(),
+        None,
+    )
+}
+
+/// Same as `value_to_element`, but the items of arrays that aren't stored under an object key
+/// become children named `ReverseConfig::mixed_array_item_tag`, whatever their JSON type:
+/// `[1, null]` under `list` gives `<list><item>1</item><item/></list>`. Attributes and text are
+/// recognized by `ReverseConfig::attribute_prefix` and `ReverseConfig::text_key`.
+pub fn value_to_element_with_config(
+    tag: &str,
+    value: &Value,
+    cfg: &ReverseConfig,
+) -> Result<treexml::Element, ReverseError> {
+    build_element(
+        tag,
+        value,
+        &JsonPointer::default(),
+        cfg,
+        Some(&cfg.mixed_array_item_tag),
+    )
+}
+
+/// Creates an element named `root_tag` from any serializable value by serializing it to JSON
+/// and passing the result to `value_to_element`.
+///
+/// Values that JSON can't represent, such as maps with non-string keys, fail with
+/// `ReverseError::Serialize`. Fields serializing to shapes XML can't represent, such as nested
+/// sequences or `@`-prefixed fields holding structs, fail like they do in `value_to_element`.
+pub fn element_from<T: Serialize>(
+    value: &T,
+    root_tag: &str,
+) -> Result<treexml::Element, ReverseError> {
+    let value = serde_json::to_value(value).map_err(|e| ReverseError::Serialize {
+        message: e.to_string(),
+        path: JsonPointer::default(),
+    })?;
+    value_to_element(root_tag, &value)
+}
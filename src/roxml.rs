@@ -0,0 +1,116 @@
+use roxmltree::Node;
+use serde_json::{Map, Value};
+
+use crate::{
+    annotations::{Annotations, Recorded},
+    namespaces::{convert_declared, declaration_attribute, XML_NAMESPACE},
+    ConversionConfig, ConversionError, JsonPointer, XmlPath,
+};
+
+/// Prefix of a name in the namespace `uri`, as seen from `node`. Names in the default
+/// namespace have none.
+fn prefix(node: Node, uri: Option<&str>) -> Option<String> {
+    node.lookup_prefix(uri?).map(str::to_string)
+}
+
+/// Copies the element `node` and its descendants into a treexml element tree, mirroring what
+/// `treexml::Document::parse` produces for the same document. Namespace declarations are kept
+/// among the attributes so that the namespace options can resolve them: all those in scope on
+/// the top element, whose ancestors aren't copied, and only new ones below it. `scope` is the
+/// copied parent of `node`, if any. Positions, comments and processing instructions go to
+/// `notes` if given.
+fn element(
+    node: Node,
+    scope: Option<Node>,
+    mut notes: Option<&mut Annotations>,
+) -> treexml::Element {
+    let tag = node.tag_name();
+    let mut e = treexml::Element {
+        prefix: prefix(node, tag.namespace()),
+        ..treexml::Element::new(tag.name())
+    };
+
+    for ns in node.namespaces().filter(|ns| ns.uri() != XML_NAMESPACE) {
+        let inherited = scope.is_some_and(|p| {
+            p.namespaces()
+                .any(|p| p.name() == ns.name() && p.uri() == ns.uri())
+        });
+        if !inherited {
+            e.attributes
+                .insert(declaration_attribute(ns.name()), ns.uri().to_string());
+        }
+    }
+    for attr in node.attributes() {
+        let name = match prefix(node, attr.namespace()) {
+            Some(prefix) => format!("{}:{}", prefix, attr.name()),
+            None => attr.name().to_string(),
+        };
+        e.attributes.insert(name, attr.value().to_string());
+    }
+
+    if let Some(notes) = notes.as_deref_mut() {
+        let position = node.document().text_pos_at(node.range().start);
+        notes.position = Some((position.row, position.col));
+    }
+    for c in node.children() {
+        if c.is_element() {
+            let mut child_notes = notes.as_ref().map(|_| Annotations::default());
+            e.children
+                .push(element(c, Some(node), child_notes.as_mut()));
+            if let (Some(notes), Some(child_notes)) = (notes.as_deref_mut(), child_notes) {
+                notes.children.push(child_notes);
+            }
+        } else if let Some(text) = c.text().filter(|_| c.is_text()) {
+            if !text.chars().all(char::is_whitespace) {
+                e.text.get_or_insert_with(String::new).push_str(text);
+            }
+        } else if let Some(notes) = notes.as_deref_mut() {
+            if let Some(comment) = c.text().filter(|_| c.is_comment()) {
+                notes.comments.push(comment.to_string());
+            } else if let Some(pi) = c.pi() {
+                notes.pis.push(match pi.value {
+                    Some(value) => format!("{} {}", pi.target, value),
+                    None => pi.target.to_string(),
+                });
+            }
+        }
+    }
+    e
+}
+
+/// Converts the element `node` of a roxmltree document like `node2object_with_config`. A
+/// document node is converted through its root element.
+///
+/// Unlike treexml, roxmltree resolves namespaces itself, so `prefix_map`, `namespace_key` and
+/// the other namespace options see the URIs of every element and attribute, including those
+/// declared above `node`. Namespace declarations never become attributes, as with treexml,
+/// whatever `strip_xmlns_attributes` says. roxmltree doesn't tell CDATA
+/// sections apart from text, so they are always converted as text, and prefixes are looked up
+/// from the namespace URIs, so of two prefixes bound to the same namespace either may be used.
+/// As roxmltree keeps the source positions, comments and processing instructions,
+/// `include_positions`, `comment_key` and `pi_key` apply.
+pub fn roxmltree_node2object(
+    node: Node<'_, '_>,
+    cfg: &ConversionConfig,
+) -> Result<Map<String, Value>, ConversionError> {
+    let node = match node.document().root_element() {
+        root if node.is_root() => root,
+        _ if node.is_element() => node,
+        _ => {
+            return Err(ConversionError::Malformed {
+                message: format!("expected an element, found a {:?} node", node.node_type()),
+                path: JsonPointer::default(),
+                xml_path: XmlPath::default(),
+            })
+        }
+    };
+    let annotate = cfg.include_positions || cfg.comment_key.is_some() || cfg.pi_key.is_some();
+    let mut notes = Some(Annotations::default()).filter(|_| annotate);
+    let e = element(node, None, notes.as_mut());
+    let recorded = Recorded {
+        references: false,
+        comments: true,
+        positions: true,
+    };
+    convert_declared(&e, notes.as_ref(), recorded, cfg)
+}
@@ -0,0 +1,121 @@
+use serde_json::{json, Map, Value};
+
+use crate::{node2object_with_config, ConversionConfig, ConversionError};
+
+const SCHEMA_DRAFT: &str = "http://json-schema.org/draft-07/schema#";
+
+/// JSON Schema type name of a value.
+pub(crate) fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Schema for a set of objects: every key seen becomes a property, and keys present in every
+/// object are required.
+fn object_schema(objects: &[&Map<String, Value>]) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    let mut keys: Vec<&String> = objects.iter().flat_map(|o| o.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let values: Vec<&Value> = objects.iter().filter_map(|o| o.get(key)).collect();
+        if values.len() == objects.len() {
+            required.push(Value::String(key.clone()));
+        }
+        properties.insert(key.clone(), infer_values(&values));
+    }
+
+    let mut schema = Map::new();
+    schema.insert("type".into(), "object".into());
+    schema.insert("properties".into(), Value::Object(properties));
+    if !required.is_empty() {
+        schema.insert("required".into(), Value::Array(required));
+    }
+    Value::Object(schema)
+}
+
+/// Schema accepting any value of the observed scalar types.
+fn scalar_schema(types: &[&'static str]) -> Value {
+    match types {
+        [t] => json!({ "type": t }),
+        _ => json!({ "type": types }),
+    }
+}
+
+/// Schema accepting every non-array value in `values`.
+fn infer_items(values: &[&Value]) -> Value {
+    let mut types: Vec<&'static str> = values
+        .iter()
+        .filter(|v| !v.is_object())
+        .map(|v| type_name(v))
+        .collect();
+    types.sort_unstable();
+    types.dedup();
+    let objects: Vec<&Map<String, Value>> = values.iter().filter_map(|v| v.as_object()).collect();
+
+    match (types.is_empty(), objects.is_empty()) {
+        (true, true) => json!({}),
+        (false, true) => scalar_schema(&types),
+        (true, false) => object_schema(&objects),
+        (false, false) => json!({ "anyOf": [scalar_schema(&types), object_schema(&objects)] }),
+    }
+}
+
+/// Schema accepting every value in `values`. Arrays are described by the union of their
+/// elements; when a name is an array in some occurrences and a single value in others, the
+/// schema accepts either form of the same item schema.
+fn infer_values(values: &[&Value]) -> Value {
+    let mut singles = Vec::new();
+    let mut elements = Vec::new();
+    let mut has_arrays = false;
+    for v in values {
+        match v {
+            Value::Array(items) => {
+                has_arrays = true;
+                elements.extend(items);
+            }
+            v => singles.push(*v),
+        }
+    }
+
+    if !has_arrays {
+        return infer_items(&singles);
+    }
+    let has_singles = !singles.is_empty();
+    elements.extend(singles);
+    let items = infer_items(&elements);
+    let array = json!({ "type": "array", "items": items });
+    if has_singles {
+        json!({ "anyOf": [items, array] })
+    } else {
+        array
+    }
+}
+
+/// Infers a draft-07 JSON Schema describing the output of `node2object_with_config` for every
+/// element in `elems`. Each element is converted with `cfg`, so the schema reflects the same
+/// options (root inclusion, forced arrays, scalar coercions and so on) as the actual output.
+/// Names that repeat become arrays, properties missing from some occurrences are not
+/// required, and disagreeing occurrences produce a union of types.
+pub fn infer_schema(
+    elems: &[&treexml::Element],
+    cfg: &ConversionConfig,
+) -> Result<Value, ConversionError> {
+    let values = elems
+        .iter()
+        .map(|e| node2object_with_config(e, cfg).map(Value::Object))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut schema = infer_values(&values.iter().collect::<Vec<_>>());
+    if let Value::Object(schema) = &mut schema {
+        schema.insert("$schema".into(), SCHEMA_DRAFT.into());
+    }
+    Ok(schema)
+}
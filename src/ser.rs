@@ -0,0 +1,455 @@
+use serde::ser::{self, Serialize};
+
+use crate::{
+    reverse::{attribute_name, valid_name},
+    JsonPointer, ReverseConfig, ReverseError,
+};
+
+impl ser::Error for ReverseError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        ReverseError::Serialize {
+            message: msg.to_string(),
+            path: JsonPointer::default(),
+        }
+    }
+}
+
+/// Serialized form of a value, before it is placed in an element.
+enum Node {
+    /// `None`, which leaves out the element or attribute it would produce.
+    Absent,
+    /// `()` and unit structs, which produce empty elements.
+    Empty,
+    /// Scalars and unit variants.
+    Text(String),
+    /// Entries of structs, maps and data-carrying enum variants, in order.
+    Fields(Vec<(String, Node)>),
+    /// Sequences and tuples.
+    Seq(Vec<Node>),
+}
+
+/// Serializer turning any `Serialize` value into a `Node`.
+struct NodeSerializer;
+
+fn text<T: ToString>(v: T) -> Result<Node, ReverseError> {
+    Ok(Node::Text(v.to_string()))
+}
+
+/// Wraps `node` into the externally tagged representation of the enum variant `variant`.
+fn tagged(variant: &str, node: Node) -> Node {
+    Node::Fields(vec![(variant.to_string(), node)])
+}
+
+impl ser::Serializer for NodeSerializer {
+    type Ok = Node;
+    type Error = ReverseError;
+    type SerializeSeq = SeqBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = SeqBuilder;
+    type SerializeTupleVariant = SeqBuilder;
+    type SerializeMap = FieldsBuilder;
+    type SerializeStruct = FieldsBuilder;
+    type SerializeStructVariant = FieldsBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_i8(self, v: i8) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_i16(self, v: i16) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_i32(self, v: i32) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_i64(self, v: i64) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_i128(self, v: i128) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_u8(self, v: u8) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_u16(self, v: u16) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_u32(self, v: u32) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_u64(self, v: u64) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_u128(self, v: u128) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_f32(self, v: f32) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_f64(self, v: f64) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_char(self, v: char) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_str(self, v: &str) -> Result<Node, ReverseError> {
+        text(v)
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, ReverseError> {
+        Ok(Node::Seq(
+            v.iter().map(|b| Node::Text(b.to_string())).collect(),
+        ))
+    }
+    fn serialize_none(self) -> Result<Node, ReverseError> {
+        Ok(Node::Absent)
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Node, ReverseError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Node, ReverseError> {
+        Ok(Node::Empty)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node, ReverseError> {
+        Ok(Node::Empty)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Node, ReverseError> {
+        text(variant)
+    }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Node, ReverseError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Node, ReverseError> {
+        Ok(tagged(variant, value.serialize(self)?))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, ReverseError> {
+        Ok(SeqBuilder {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, ReverseError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, ReverseError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, ReverseError> {
+        Ok(SeqBuilder {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<FieldsBuilder, ReverseError> {
+        Ok(FieldsBuilder {
+            variant: None,
+            fields: Vec::with_capacity(len.unwrap_or(0)),
+            next_key: None,
+        })
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<FieldsBuilder, ReverseError> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<FieldsBuilder, ReverseError> {
+        Ok(FieldsBuilder {
+            variant: Some(variant),
+            fields: Vec::with_capacity(len),
+            next_key: None,
+        })
+    }
+}
+
+/// Collects the items of sequences, tuples and tuple variants.
+struct SeqBuilder {
+    variant: Option<&'static str>,
+    items: Vec<Node>,
+}
+
+impl SeqBuilder {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ReverseError> {
+        self.items.push(value.serialize(NodeSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Node, ReverseError> {
+        let seq = Node::Seq(self.items);
+        Ok(match self.variant {
+            Some(variant) => tagged(variant, seq),
+            None => seq,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqBuilder {
+    type Ok = Node;
+    type Error = ReverseError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ReverseError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Node, ReverseError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+    type Ok = Node;
+    type Error = ReverseError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ReverseError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Node, ReverseError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+    type Ok = Node;
+    type Error = ReverseError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ReverseError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Node, ReverseError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqBuilder {
+    type Ok = Node;
+    type Error = ReverseError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ReverseError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Node, ReverseError> {
+        self.finish()
+    }
+}
+
+/// Collects the entries of maps, structs and struct variants.
+struct FieldsBuilder {
+    variant: Option<&'static str>,
+    fields: Vec<(String, Node)>,
+    next_key: Option<String>,
+}
+
+impl FieldsBuilder {
+    fn push<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), ReverseError> {
+        self.fields
+            .push((key.to_string(), value.serialize(NodeSerializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Node, ReverseError> {
+        let fields = Node::Fields(self.fields);
+        Ok(match self.variant {
+            Some(variant) => tagged(variant, fields),
+            None => fields,
+        })
+    }
+}
+
+impl ser::SerializeMap for FieldsBuilder {
+    type Ok = Node;
+    type Error = ReverseError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), ReverseError> {
+        match key.serialize(NodeSerializer)? {
+            Node::Text(key) => {
+                self.next_key = Some(key);
+                Ok(())
+            }
+            _ => Err(ser::Error::custom("map keys must be strings or scalars")),
+        }
+    }
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), ReverseError> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| ser::Error::custom("map value without a key"))?;
+        self.push(&key, value)
+    }
+    fn end(self) -> Result<Node, ReverseError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for FieldsBuilder {
+    type Ok = Node;
+    type Error = ReverseError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ReverseError> {
+        self.push(key, value)
+    }
+    fn end(self) -> Result<Node, ReverseError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for FieldsBuilder {
+    type Ok = Node;
+    type Error = ReverseError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ReverseError> {
+        self.push(key, value)
+    }
+    fn end(self) -> Result<Node, ReverseError> {
+        self.finish()
+    }
+}
+
+/// Builds the element `tag` holding `node`. Sequences reaching this point have no key of their
+/// own, so their items become `ReverseConfig::mixed_array_item_tag` children.
+fn build_element(
+    tag: &str,
+    node: Node,
+    path: &JsonPointer,
+    cfg: &ReverseConfig,
+) -> Result<treexml::Element, ReverseError> {
+    if !valid_name(tag) {
+        return Err(ReverseError::InvalidName {
+            name: tag.into(),
+            path: path.clone(),
+        });
+    }
+    let mut e = treexml::Element::new(tag);
+    match node {
+        Node::Absent | Node::Empty => {}
+        Node::Text(text) => e.text = Some(text),
+        Node::Fields(fields) => {
+            for (key, node) in fields {
+                place(&mut e, &key, node, path, cfg)?;
+            }
+        }
+        Node::Seq(items) => {
+            for (i, item) in items.into_iter().enumerate() {
+                if let Node::Absent = item {
+                    continue;
+                }
+                let mut item_path = path.clone();
+                item_path.push(&i.to_string());
+                e.children.push(build_element(
+                    &cfg.mixed_array_item_tag,
+                    item,
+                    &item_path,
+                    cfg,
+                )?);
+            }
+        }
+    }
+    Ok(e)
+}
+
+/// Stores the entry `key` of the element `e` as an attribute, text or children.
+fn place(
+    e: &mut treexml::Element,
+    key: &str,
+    node: Node,
+    path: &JsonPointer,
+    cfg: &ReverseConfig,
+) -> Result<(), ReverseError> {
+    let mut child_path = path.clone();
+    child_path.push(key);
+
+    if let Node::Absent = node {
+        return Ok(());
+    }
+    if let Some(name) = attribute_name(key, cfg) {
+        if !valid_name(name) {
+            return Err(ReverseError::InvalidName {
+                name: name.into(),
+                path: child_path,
+            });
+        }
+        let text = match node {
+            Node::Text(text) => text,
+            Node::Empty => String::new(),
+            _ => return Err(ReverseError::NonScalarAttribute { path: child_path }),
+        };
+        e.attributes.insert(name.into(), text);
+    } else if key == cfg.text_key {
+        match node {
+            Node::Text(text) => e.text = Some(text),
+            Node::Empty => {}
+            _ => return Err(ReverseError::NonScalarText { path: child_path }),
+        }
+    } else if let Node::Seq(items) = node {
+        for (i, item) in items.into_iter().enumerate() {
+            if let Node::Absent = item {
+                continue;
+            }
+            let mut item_path = child_path.clone();
+            item_path.push(&i.to_string());
+            e.children.push(build_element(key, item, &item_path, cfg)?);
+        }
+    } else {
+        e.children.push(build_element(key, node, &child_path, cfg)?);
+    }
+    Ok(())
+}
+
+/// Creates an element named `root_name` from any serializable value, without going through
+/// JSON. Struct fields and map entries become child elements, except for keys starting with
+/// `ReverseConfig::attribute_prefix`, which become attributes, and `ReverseConfig::text_key`,
+/// which becomes text content. Sequences become repeated elements named after their field,
+/// and `None` leaves out the element or attribute. Unit values produce empty elements and
+/// scalars produce text.
+///
+/// Enums are externally tagged as with `serde_json`: unit variants become text holding the
+/// variant name, and other variants become a child element named after the variant. Map keys
+/// must serialize to strings or scalars. Sequences that aren't stored under a key, such as
+/// the root value or sequences nested in sequences, become `ReverseConfig::mixed_array_item_tag`
+/// children. Attribute and text entries must hold scalars.
+pub fn struct2node<T: ?Sized + Serialize>(
+    root_name: &str,
+    value: &T,
+    cfg: &ReverseConfig,
+) -> Result<treexml::Element, ReverseError> {
+    let node = value.serialize(NodeSerializer)?;
+    build_element(root_name, node, &JsonPointer::default(), cfg)
+}
@@ -0,0 +1,23 @@
+//! Conversion into `simd_json` values, mirroring the `serde_json` API at the crate root.
+
+use simd_json::owned::{Object, Value};
+
+use crate::{node2object_with_builder, ConversionConfig, ConversionError, SimdJsonBuilder};
+
+/// Same as `node2object`, producing a `simd_json` object.
+pub fn node2object(e: &treexml::Element) -> Object {
+    node2object_with_config(e, &ConversionConfig::default())
+        .expect("default configuration never fails")
+}
+
+/// Same as `node2object_with_config`, producing a `simd_json` object built as the conversion
+/// goes.
+pub fn node2object_with_config(
+    e: &treexml::Element,
+    cfg: &ConversionConfig,
+) -> Result<Object, ConversionError> {
+    match node2object_with_builder(e, cfg, &SimdJsonBuilder)? {
+        Value::Object(data) => Ok(*data),
+        _ => unreachable!("objects are built as objects"),
+    }
+}
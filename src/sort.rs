@@ -0,0 +1,76 @@
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+use crate::ArraySortKey;
+
+/// Position of each JSON type in the total order used by `compare_values`.
+fn type_rank(v: &Value) -> u8 {
+    match v {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+/// Total order on JSON values: values of different types are ordered by type, numbers
+/// numerically, strings and object keys lexicographically, arrays and objects element-wise.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64().unwrap_or(0.0), b.as_f64().unwrap_or(0.0));
+            a.total_cmp(&b)
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare_values(a, b))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Value::Object(a), Value::Object(b)) => a
+            .iter()
+            .zip(b)
+            .map(|((ka, va), (kb, vb))| ka.cmp(kb).then_with(|| compare_values(va, vb)))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (a, b) => type_rank(a).cmp(&type_rank(b)),
+    }
+}
+
+/// Compares objects by their first entry only, falling back to `compare_values` otherwise.
+fn compare_first_keys(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => match (a.iter().next(), b.iter().next()) {
+            (Some((ka, va)), Some((kb, vb))) => ka.cmp(kb).then_with(|| compare_values(va, vb)),
+            (a, b) => a.is_some().cmp(&b.is_some()),
+        },
+        (a, b) => compare_values(a, b),
+    }
+}
+
+/// Sorts every array within `v`, innermost arrays first.
+pub(crate) fn sort_arrays(v: &mut Value, key: &ArraySortKey) {
+    match v {
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                sort_arrays(item, key);
+            }
+            match key {
+                ArraySortKey::FirstKey => items.sort_by(compare_first_keys),
+                ArraySortKey::AllKeys => items.sort_by(compare_values),
+                ArraySortKey::Custom(cmp) => items.sort_by(|a, b| cmp(a, b)),
+            }
+        }
+        Value::Object(data) => {
+            for v in data.values_mut() {
+                sort_arrays(v, key);
+            }
+        }
+        _ => {}
+    }
+}
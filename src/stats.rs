@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+
+use serde_json::{Map, Value};
+
+/// Structural metadata of an element tree, see `element_tree_stats`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    /// Number of elements, including the root.
+    pub element_count: usize,
+    /// Number of elements on the longest path from the root down, 1 for a lone root.
+    pub max_depth: usize,
+    /// Names of the elements, without namespace prefixes.
+    pub unique_tags: HashSet<String>,
+    pub total_attributes: usize,
+    /// Number of elements with text or CDATA content.
+    pub text_node_count: usize,
+    /// Number of elements without attributes, children, text or CDATA.
+    pub empty_node_count: usize,
+}
+
+/// Collects structural metadata about `e` and its descendants without converting them, e.g. to
+/// decide how to convert a document or to reject oversized ones up front.
+pub fn element_tree_stats(e: &treexml::Element) -> TreeStats {
+    let mut stats = TreeStats::default();
+    let mut pending = vec![(e, 1)];
+    while let Some((e, depth)) = pending.pop() {
+        stats.element_count += 1;
+        stats.max_depth = stats.max_depth.max(depth);
+        if !stats.unique_tags.contains(&e.name) {
+            stats.unique_tags.insert(e.name.clone());
+        }
+        stats.total_attributes += e.attributes.len();
+        let has_text = e.text.is_some() || e.cdata.is_some();
+        if has_text {
+            stats.text_node_count += 1;
+        }
+        if !has_text && e.attributes.is_empty() && e.children.is_empty() {
+            stats.empty_node_count += 1;
+        }
+        pending.extend(e.children.iter().map(|c| (c, depth + 1)));
+    }
+    stats
+}
+
+/// Counters collected while converting an element tree, see `node2object_with_stats`. They are
+/// exact and updated as the conversion goes, so collecting them costs a few additions per
+/// element.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConversionStats {
+    /// Number of converted elements, including the root.
+    pub element_count: usize,
+    /// Number of converted attributes, leaving out skipped ones.
+    pub attribute_count: usize,
+    /// Number of elements on the longest converted path from the root down, 1 for a lone root.
+    pub max_depth: usize,
+    /// Length in bytes of the text and CDATA of the converted elements, before trimming.
+    pub text_bytes: usize,
+    /// Number of arrays holding sibling elements, including the empty ones added by
+    /// `ensure_array_keys`.
+    pub array_count: usize,
+    /// Number of text and attribute values converted to JSON numbers.
+    pub number_coercions: usize,
+    /// Number of text and attribute values converted to JSON booleans.
+    pub boolean_coercions: usize,
+}
+
+impl ConversionStats {
+    /// Counts the numbers and booleans in `v`, the conversion of a text or attribute value.
+    pub(crate) fn count_coercions(&mut self, v: &Value) {
+        match v {
+            Value::Number(_) => self.number_coercions += 1,
+            Value::Bool(_) => self.boolean_coercions += 1,
+            Value::Array(items) => items.iter().for_each(|v| self.count_coercions(v)),
+            Value::Object(data) => self.count_coercions_in(data),
+            Value::Null | Value::String(_) => {}
+        }
+    }
+
+    /// Counts the numbers and booleans among the values of `data`.
+    pub(crate) fn count_coercions_in(&mut self, data: &Map<String, Value>) {
+        data.values().for_each(|v| self.count_coercions(v));
+    }
+
+    /// Adds the counters of `other`, collected over another part of the same document.
+    #[cfg(feature = "streaming")]
+    pub(crate) fn add(&mut self, other: &ConversionStats) {
+        self.element_count += other.element_count;
+        self.attribute_count += other.attribute_count;
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.text_bytes += other.text_bytes;
+        self.array_count += other.array_count;
+        self.number_coercions += other.number_coercions;
+        self.boolean_coercions += other.boolean_coercions;
+    }
+}
@@ -0,0 +1,246 @@
+use std::io::{BufReader, Read};
+
+use quick_xml::events::{BytesStart, Event};
+use serde_json::Value;
+
+use crate::{
+    annotations::{check_options, Recorded},
+    attribute_entries, convert_node_aux, element_key, finish_value, node_attributes,
+    pull::{io_error, start_element, TreeBuilder},
+    Context, ConversionConfig, ConversionStats, NodeContext, OccurrenceLimits, ReadError,
+};
+
+/// Errors that can occur in `stream_convert`.
+pub type StreamError = ReadError;
+
+/// Receives the parts of a document converted by `stream_convert`, in document order.
+pub trait StreamHandler {
+    /// The root element starts. `name` is the key it would be stored under by `node2object`.
+    fn on_start(&mut self, _name: &str) {}
+    /// The root element ends.
+    fn on_end(&mut self, _name: &str) {}
+    /// Text or CDATA directly inside the root element, with references expanded. Text made of
+    /// whitespace only is skipped.
+    fn on_text(&mut self, _text: &str) {}
+    /// An attribute of the root element, or one of its child elements once its end tag is read,
+    /// converted as by `node2object_with_config`.
+    fn on_object(&mut self, key: &str, value: Value);
+}
+
+/// Creates the root element from its start tag and reports it and its attributes to `handler`.
+fn start_root<H: StreamHandler>(
+    start: &BytesStart,
+    handler: &mut H,
+    stats: &mut ConversionStats,
+    cfg: &ConversionConfig,
+) -> Result<treexml::Element, String> {
+    let e = start_element(start).map_err(|e| e.to_string())?;
+    handler.on_start(&element_key(&e, cfg));
+    let node = NodeContext::new(&[], &e);
+    let attributes = node_attributes(&e, None, cfg);
+    stats.element_count += 1;
+    stats.max_depth = stats.max_depth.max(1);
+    stats.attribute_count += attributes.len();
+    for (key, value) in attribute_entries(attributes, &node, cfg) {
+        stats.count_coercions(&value);
+        handler.on_object(&key, value);
+    }
+    Ok(e)
+}
+
+/// Converts `e`, a child of `root`, and reports it to `handler`.
+fn emit_child<H: StreamHandler>(
+    root: &treexml::Element,
+    e: &treexml::Element,
+    handler: &mut H,
+    stats: &mut ConversionStats,
+    cfg: &ConversionConfig,
+) -> Result<(), StreamError> {
+    let mut ctx = Context::with_parent(cfg, root);
+    let value = convert_node_aux(e, &mut ctx).map_err(ReadError::Conversion)?;
+    stats.add(ctx.stats.get_mut());
+    if let Some(mut value) = value {
+        finish_value(&mut value, cfg);
+        handler.on_object(&element_key(e, cfg), value);
+    }
+    Ok(())
+}
+
+/// Reads an XML document from `reader` and reports its root element to `handler` piece by
+/// piece: the attributes of the root first, then each child element as soon as it has been
+/// read, so only one child subtree is held in memory at a time. This makes documents of any
+/// size convertible as long as their top-level elements are reasonably small.
+///
+/// Repeated children are reported one by one rather than grouped into arrays, and options
+/// that look at siblings, such as `force_array`, `merge_siblings` or the namespace options,
+/// don't apply. `max_occurrences` applies to the children of the root as well, whose skipped
+/// subtrees are read past without being parsed into elements, and the entry of
+/// `truncated_count_key` is reported right before the root ends.
+pub fn stream_convert<R: Read, H: StreamHandler>(
+    reader: R,
+    handler: &mut H,
+    cfg: &ConversionConfig,
+) -> Result<(), StreamError> {
+    stream_convert_with_stats(reader, handler, cfg).map(|_| ())
+}
+
+/// Same as `stream_convert`, but also returns counters collected during the conversion, see
+/// `ConversionStats`. No arrays are counted, as repeated children aren't grouped.
+pub fn stream_convert_with_stats<R: Read, H: StreamHandler>(
+    reader: R,
+    handler: &mut H,
+    cfg: &ConversionConfig,
+) -> Result<ConversionStats, StreamError> {
+    let mut streamer = Streamer::new(cfg)?;
+    let mut reader = quick_xml::Reader::from_reader(BufReader::new(reader));
+    let mut buf = Vec::new();
+    loop {
+        let event = match reader.read_event_into(&mut buf) {
+            Ok(event) => event,
+            Err(quick_xml::Error::Io(e)) => return Err(ReadError::Io(io_error(e))),
+            Err(e) => {
+                return Err(ReadError::Parse {
+                    message: e.to_string(),
+                    position: Some(reader.error_position()),
+                })
+            }
+        };
+        if streamer.event(event, reader.buffer_position(), handler)? {
+            break;
+        }
+        buf.clear();
+    }
+    streamer.finish().map(|(_, stats)| stats)
+}
+
+/// Conversion state of `stream_convert`, fed one parser event at a time, so that sync and
+/// async readers share it.
+pub(crate) struct Streamer<'c> {
+    cfg: &'c ConversionConfig,
+    stats: ConversionStats,
+    /// The root element without its children, which are converted and dropped one at a time.
+    root: Option<treexml::Element>,
+    /// The root element once it is closed.
+    closed: Option<treexml::Element>,
+    limits: OccurrenceLimits,
+    builder: TreeBuilder,
+    /// Depth within a child of the root left out by `max_occurrences`.
+    skipped: usize,
+}
+
+impl<'c> Streamer<'c> {
+    pub(crate) fn new(cfg: &'c ConversionConfig) -> Result<Self, StreamError> {
+        check_options(cfg, Recorded::default()).map_err(ReadError::Conversion)?;
+        Ok(Self {
+            cfg,
+            stats: ConversionStats::default(),
+            root: None,
+            closed: None,
+            limits: OccurrenceLimits::default(),
+            builder: TreeBuilder::default(),
+            skipped: 0,
+        })
+    }
+
+    /// Handles `event`, read up to byte `position`, reporting what it completes to `handler`.
+    /// Returns `true` once the end of the document is reached.
+    pub(crate) fn event<H: StreamHandler>(
+        &mut self,
+        event: Event,
+        position: u64,
+        handler: &mut H,
+    ) -> Result<bool, StreamError> {
+        let cfg = self.cfg;
+        let parse_error = |message: String| ReadError::Parse {
+            message,
+            position: Some(position),
+        };
+
+        if self.skipped > 0 {
+            match event {
+                Event::Start(_) => self.skipped += 1,
+                Event::End(_) => self.skipped -= 1,
+                Event::Eof => {
+                    let name = self.root.as_ref().map_or("", |root| &root.name);
+                    return Err(parse_error(format!("element {} is not closed", name)));
+                }
+                _ => {}
+            }
+            return Ok(false);
+        }
+
+        let skipped = match (&self.root, &event) {
+            (Some(root), Event::Start(start)) | (Some(root), Event::Empty(start))
+                if self.builder.open.is_empty() && !cfg.max_occurrences.is_empty() =>
+            {
+                let c = start_element(start).map_err(|e| parse_error(e.to_string()))?;
+                !self.limits.admit(&[root], &c, cfg)
+            }
+            _ => false,
+        };
+
+        let closed = self.closed.is_some();
+        match (&self.root, event) {
+            (None, Event::Start(start)) if !closed => {
+                let e = start_root(&start, handler, &mut self.stats, cfg).map_err(parse_error)?;
+                self.root = Some(e);
+            }
+            (None, Event::Empty(start)) if !closed => {
+                let e = start_root(&start, handler, &mut self.stats, cfg).map_err(parse_error)?;
+                handler.on_end(&element_key(&e, cfg));
+                self.closed = Some(e);
+            }
+            (_, Event::Start(_)) | (_, Event::Empty(_)) if closed => {
+                return Err(parse_error("multiple root elements".to_string()))
+            }
+            (Some(_), Event::Start(_)) if skipped => self.skipped = 1,
+            (Some(_), Event::Empty(_)) if skipped => {}
+            (Some(e), Event::End(_)) if self.builder.open.is_empty() => {
+                let limits = std::mem::take(&mut self.limits);
+                if let Some((key, counts)) = limits.truncated_counts(cfg) {
+                    handler.on_object(&key, counts);
+                }
+                handler.on_end(&element_key(e, cfg));
+                self.closed = self.root.take();
+            }
+            (Some(_), Event::Text(text)) if self.builder.open.is_empty() => {
+                let text = text.unescape().map_err(|e| parse_error(e.to_string()))?;
+                if !text.chars().all(char::is_whitespace) {
+                    self.stats.text_bytes += text.len();
+                    handler.on_text(&text);
+                }
+            }
+            (Some(_), Event::CData(cdata)) if self.builder.open.is_empty() => {
+                let cdata = cdata.decode().map_err(|e| parse_error(e.to_string()))?;
+                self.stats.text_bytes += cdata.len();
+                handler.on_text(&cdata);
+            }
+            (Some(e), Event::Eof) if self.builder.open.is_empty() => {
+                return Err(parse_error(format!("element {} is not closed", e.name)))
+            }
+            (_, event) => {
+                if self
+                    .builder
+                    .event(event)
+                    .map_err(|e| parse_error(e.to_string()))?
+                {
+                    return Ok(true);
+                }
+            }
+        }
+
+        if let (Some(root), Some(child)) = (&self.root, self.builder.root.take()) {
+            emit_child(root, &child, handler, &mut self.stats, cfg)?;
+        }
+        Ok(false)
+    }
+
+    /// Returns the root element, without its children, and the counters collected once the
+    /// end of the document is reached.
+    pub(crate) fn finish(self) -> Result<(treexml::Element, ConversionStats), StreamError> {
+        match self.closed {
+            Some(root) => Ok((root, self.stats)),
+            None => Err(ReadError::NoRoot),
+        }
+    }
+}
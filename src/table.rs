@@ -0,0 +1,99 @@
+use serde_json::{Map, Value};
+
+use crate::{
+    annotations::{check_options, Recorded},
+    convert_node_aux, text_key, Context, ConversionConfig, ConversionError,
+};
+
+/// Options for `element_to_markdown_table`.
+#[derive(Clone, Debug, Default)]
+pub struct MarkdownTableConfig {
+    /// Conversion options applied to each row element.
+    pub conversion: ConversionConfig,
+    /// Cell contents for missing and `null` values.
+    pub empty_cell: String,
+}
+
+/// Column headers and converted rows of a table made of the repeated children of an element.
+struct Table {
+    headers: Vec<String>,
+    rows: Vec<Map<String, Value>>,
+}
+
+/// Treats the direct children of `e` named like its first child as table rows. Columns are
+/// the keys of the first row; rows converting to a scalar get a single column under the text key.
+fn collect_table(e: &treexml::Element, cfg: &ConversionConfig) -> Result<Table, ConversionError> {
+    check_options(cfg, Recorded::default())?;
+    let mut ctx = Context::with_parent(cfg, e);
+
+    let mut rows = Vec::new();
+    if let Some(first) = e.children.first() {
+        for c in e.children.iter().filter(|c| c.name == first.name) {
+            rows.push(match convert_node_aux(c, &mut ctx)? {
+                Some(Value::Object(data)) => data,
+                Some(v) => vec![(text_key(cfg).to_string(), v)].into_iter().collect(),
+                None => Map::new(),
+            });
+        }
+    }
+
+    let headers = rows
+        .first()
+        .map(|row| row.keys().cloned().collect())
+        .unwrap_or_default();
+    Ok(Table { headers, rows })
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', "<br>")
+}
+
+fn markdown_cell(v: Option<&Value>, empty_cell: &str) -> String {
+    match v {
+        None | Some(Value::Null) => escape_markdown(empty_cell),
+        Some(Value::String(s)) => escape_markdown(s),
+        Some(v) => escape_markdown(&v.to_string()),
+    }
+}
+
+fn markdown_row(cells: impl Iterator<Item = String>) -> String {
+    let mut line = String::from("|");
+    for cell in cells {
+        line.push(' ');
+        line.push_str(&cell);
+        line.push_str(" |");
+    }
+    line.push('\n');
+    line
+}
+
+/// Renders the repeated children of `e` as a GitHub-Flavored Markdown table, one row per child.
+/// Column headers come from the keys of the first child; nested values are rendered as JSON.
+pub fn element_to_markdown_table(
+    e: &treexml::Element,
+    cfg: &MarkdownTableConfig,
+) -> Result<String, ConversionError> {
+    let table = collect_table(e, &cfg.conversion)?;
+    if table.headers.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut out = markdown_row(
+        table
+            .headers
+            .iter()
+            .map(|h| markdown_cell(Some(&Value::String(h.clone())), "")),
+    );
+    out.push_str(&markdown_row(
+        table.headers.iter().map(|_| "---".to_string()),
+    ));
+    for row in &table.rows {
+        out.push_str(&markdown_row(
+            table
+                .headers
+                .iter()
+                .map(|h| markdown_cell(row.get(h), &cfg.empty_cell)),
+        ));
+    }
+    Ok(out)
+}
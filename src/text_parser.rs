@@ -0,0 +1,61 @@
+use std::{fmt, sync::OnceLock};
+
+use serde_json::Value;
+
+use crate::{coerce_text, ConversionConfig, TextSource};
+
+/// Converts text content and attribute values into JSON values, replacing the built-in
+/// coercion when set as `ConversionConfig::text_parser`.
+pub trait TextParser: Send + Sync {
+    /// Returns the value stored for `text`, already trimmed if `ConversionConfig::trim_text`
+    /// is set.
+    fn parse(&self, text: &str) -> Value;
+}
+
+impl fmt::Debug for dyn TextParser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TextParser(..)")
+    }
+}
+
+/// The coercion used by `node2object`: numbers become floats, then `true` and `false` become
+/// booleans, and anything else stays a string.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StandardTextParser;
+
+impl TextParser for StandardTextParser {
+    fn parse(&self, text: &str) -> Value {
+        static DEFAULT: OnceLock<ConversionConfig> = OnceLock::new();
+        let cfg = DEFAULT.get_or_init(ConversionConfig::default);
+        coerce_text(text, TextSource::Text, cfg).unwrap_or_else(|| Value::String(text.into()))
+    }
+}
+
+/// Keeps every value as a string.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StrictStringParser;
+
+impl TextParser for StrictStringParser {
+    fn parse(&self, text: &str) -> Value {
+        Value::String(text.into())
+    }
+}
+
+/// Like `StandardTextParser`, but integers that fit in 64 bits become JSON integers instead of
+/// floats, e.g. `42` rather than `42.0`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IntegerPreferringTextParser;
+
+impl TextParser for IntegerPreferringTextParser {
+    fn parse(&self, text: &str) -> Value {
+        if !text.starts_with('+') {
+            if let Ok(v) = text.parse::<i64>() {
+                return Value::from(v);
+            }
+            if let Ok(v) = text.parse::<u64>() {
+                return Value::from(v);
+            }
+        }
+        StandardTextParser.parse(text)
+    }
+}
@@ -0,0 +1,26 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{node2object, value_to_element};
+
+/// Parses `xml_string` and converts its root element with the default configuration,
+/// returning the result as a plain JavaScript object. Throws if the XML can't be parsed.
+#[wasm_bindgen]
+pub fn node2json_js(xml_string: &str) -> Result<JsValue, JsError> {
+    let root = treexml::Document::parse(xml_string.as_bytes())
+        .map_err(|e| JsError::new(&e.to_string()))?
+        .root
+        .ok_or_else(|| JsError::new("document has no root element"))?;
+    let value = serde_json::Value::Object(node2object(&root));
+    // Plain objects rather than ES2015 `Map`s, so the result looks like `JSON.parse` output.
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+/// Converts a JavaScript value back into an XML string whose root element is named
+/// `root_tag`, see `value_to_element`. Throws if the value can't be represented as XML.
+#[wasm_bindgen]
+pub fn json2xml_js(json: JsValue, root_tag: &str) -> Result<String, JsError> {
+    let value: serde_json::Value = serde_wasm_bindgen::from_value(json)?;
+    let element = value_to_element(root_tag, &value)?;
+    Ok(element.to_string())
+}
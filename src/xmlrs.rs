@@ -0,0 +1,211 @@
+use std::io::Read;
+
+use serde_json::{Map, Value};
+use xml::{
+    attribute::OwnedAttribute,
+    name::OwnedName,
+    reader::{EventReader, ParserConfig, XmlEvent},
+};
+
+use crate::{
+    annotations::{check_options, Recorded},
+    attribute_entries, attribute_keys, check_reserved_keys, convert_child, insert_ensured_arrays,
+    insert_text_contents,
+    namespaces::{resolve_namespaces, resolve_namespaces_below},
+    node2object_with_config, node_attributes, position_attributes, tag_source, wrap_root, Context,
+    ConversionConfig, ConversionError, NodeContext, SiblingGroups, XMLNodeType,
+    XmlRsConversionError,
+};
+
+/// Creates an element from a start tag the way treexml does.
+fn start_element(name: OwnedName, attributes: Vec<OwnedAttribute>) -> treexml::Element {
+    let mut e = treexml::Element {
+        prefix: name.prefix,
+        ..treexml::Element::new(name.local_name)
+    };
+    for attr in attributes {
+        let name = match attr.name.prefix {
+            Some(prefix) => format!("{}:{}", prefix, attr.name.local_name),
+            None => attr.name.local_name,
+        };
+        e.attributes.insert(name, attr.value);
+    }
+    e
+}
+
+/// The root element being converted, with the entries of its object built so far.
+struct RootObject<'c> {
+    cfg: &'c ConversionConfig,
+    /// The root, with the namespace options applied unless its children are kept. Children are
+    /// otherwise converted and dropped one at a time.
+    e: treexml::Element,
+    data: Map<String, Value>,
+    attribute_keys: Vec<String>,
+    /// Grouping of the children converted so far, or `None` if the options need all of them
+    /// at hand, in which case they are kept in `e` and converted at the end.
+    groups: Option<SiblingGroups<'c>>,
+    /// Number of children started so far, including the skipped ones.
+    children: usize,
+}
+
+impl<'c> RootObject<'c> {
+    fn new(e: treexml::Element, cfg: &'c ConversionConfig) -> Result<Self, ConversionError> {
+        if SiblingGroups::needs_all_children(cfg) || cfg.id_references.is_some() {
+            return Ok(Self {
+                cfg,
+                e,
+                data: Map::new(),
+                attribute_keys: Vec::new(),
+                groups: None,
+                children: 0,
+            });
+        }
+        let e = resolve_namespaces(&e, cfg)?.root.into_owned();
+        let attributes = node_attributes(&e, None, cfg);
+        check_reserved_keys(&attributes, &Context::with_parent(cfg, &e))?;
+        let data = attribute_entries(attributes, &NodeContext::new(&[], &e), cfg);
+        let groups = SiblingGroups::incremental(Some(&e), cfg);
+        Ok(Self {
+            cfg,
+            attribute_keys: attribute_keys(&data, cfg),
+            data,
+            groups: Some(groups),
+            e,
+            children: 0,
+        })
+    }
+
+    /// Registers the child started with `c`, which has no children yet, and checks whether it
+    /// is within `ConversionConfig::max_occurrences`. Children kept in `e` are checked when
+    /// they are converted at the end.
+    fn admit(&mut self, c: &treexml::Element) -> Result<bool, ConversionError> {
+        self.children += 1;
+        let groups = match &mut self.groups {
+            Some(groups) => groups,
+            None => return Ok(true),
+        };
+        let resolved = resolve_namespaces_below(&[&self.e], c, self.cfg)?;
+        Ok(groups.admit(&[&self.e], &resolved.root))
+    }
+
+    /// Converts the complete child `c`, the last one admitted, and stores it, grouping it with
+    /// its siblings.
+    fn push(&mut self, c: treexml::Element) -> Result<(), ConversionError> {
+        let groups = match &mut self.groups {
+            Some(groups) => groups,
+            None => {
+                self.e.children.push(c);
+                return Ok(());
+            }
+        };
+        let resolved = resolve_namespaces_below(&[&self.e], &c, self.cfg)?;
+        let mut ctx = Context::with_parent(self.cfg, &self.e);
+        ctx.namespaces = Some(&resolved.uris);
+        if let Some(v) = convert_child(self.children - 1, &resolved.root, groups, &mut ctx)? {
+            groups.insert(&mut self.data, &resolved.root, v, &ctx)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Map<String, Value>, ConversionError> {
+        let groups = match self.groups.take() {
+            Some(groups) if self.children > 0 => groups,
+            _ => return node2object_with_config(&self.e, self.cfg),
+        };
+
+        let kind = match (&self.e.text, &self.e.cdata) {
+            (None, None) => XMLNodeType::Parent,
+            _ => XMLNodeType::SemiStructured,
+        };
+        let ctx = Context::with_parent(self.cfg, &self.e);
+        groups.finish(&mut self.data, &ctx)?;
+        if kind == XMLNodeType::SemiStructured {
+            insert_text_contents(&mut self.data, &self.e, kind, &ctx)?;
+        }
+        let data = position_attributes(self.data, &self.attribute_keys, self.cfg);
+        let value = insert_ensured_arrays(Some(Value::Object(data)), &self.e, kind, &ctx);
+        let value = tag_source(value, &self.e, self.cfg);
+        Ok(wrap_root(&self.e, value, self.cfg))
+    }
+}
+
+/// Reads an XML document from `reader` with xml-rs and converts its root element, building the
+/// JSON value as the events arrive: each child of the root is converted as soon as its end tag
+/// is read and then dropped, so only one child subtree is held in memory at a time. Text,
+/// CDATA, comments and namespace declarations are treated as by treexml, which parses with
+/// xml-rs as well, so the namespace options only see the prefixes of names, but documents with
+/// several root elements are rejected.
+///
+/// The output matches `node2object_with_config` on the parsed document. Options that need all
+/// the children of an element before grouping the first, namely `child_as_attribute`,
+/// `harmonize_sibling_types`, `group_by_xml_lang` and `array_key_rename`, as well as
+/// `id_references`, which looks up references in the whole tree, make the children of the root
+/// be kept until the end of the document instead. Otherwise, children of the root left
+/// out by `max_occurrences` are read past without being built.
+pub fn xmlrs_document_to_value<R: Read>(
+    reader: R,
+    cfg: &ConversionConfig,
+) -> Result<Value, XmlRsConversionError> {
+    let conversion = XmlRsConversionError::Conversion;
+    check_options(cfg, Recorded::default()).map_err(conversion)?;
+    let mut root: Option<RootObject> = None;
+    // Elements of the child of the root being read, from that child down.
+    let mut open: Vec<treexml::Element> = Vec::new();
+    let mut data = None;
+    // Depth within a child of the root left out by `max_occurrences`.
+    let mut skipped = 0usize;
+    let parser = ParserConfig::new().allow_multiple_root_elements(false);
+    for event in EventReader::new_with_config(reader, parser) {
+        match event.map_err(XmlRsConversionError::Xml)? {
+            XmlEvent::StartElement { .. } if skipped > 0 => skipped += 1,
+            XmlEvent::EndElement { .. } if skipped > 0 => skipped -= 1,
+            XmlEvent::Characters(_) | XmlEvent::CData(_) if skipped > 0 => {}
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let e = start_element(name, attributes);
+                match &mut root {
+                    Some(root) if open.is_empty() => {
+                        if root.admit(&e).map_err(conversion)? {
+                            open.push(e);
+                        } else {
+                            skipped = 1;
+                        }
+                    }
+                    Some(_) => open.push(e),
+                    None => root = Some(RootObject::new(e, cfg).map_err(conversion)?),
+                }
+            }
+            XmlEvent::EndElement { .. } => match (open.pop(), open.last_mut()) {
+                (Some(e), Some(parent)) => parent.children.push(e),
+                (Some(e), None) => {
+                    if let Some(root) = &mut root {
+                        root.push(e).map_err(conversion)?;
+                    }
+                }
+                (None, _) => {
+                    if let Some(root) = root.take() {
+                        data = Some(root.finish().map_err(conversion)?);
+                    }
+                }
+            },
+            XmlEvent::Characters(text) => {
+                if let Some(e) = open.last_mut().or(root.as_mut().map(|root| &mut root.e)) {
+                    e.text.get_or_insert_with(String::new).push_str(&text);
+                }
+            }
+            XmlEvent::CData(cdata) => {
+                if let Some(e) = open.last_mut().or(root.as_mut().map(|root| &mut root.e)) {
+                    e.cdata.get_or_insert_with(String::new).push_str(&cdata);
+                }
+            }
+            XmlEvent::EndDocument => break,
+            XmlEvent::StartDocument { .. }
+            | XmlEvent::ProcessingInstruction { .. }
+            | XmlEvent::Whitespace(_)
+            | XmlEvent::Comment(_) => {}
+        }
+    }
+    let data = data.expect("xml-rs reports documents without a root element");
+    Ok(Value::Object(data))
+}